@@ -71,12 +71,29 @@ impl std::error::Error for ErrorResponse {
 pub enum FugleError {
     MpscSendError,
     MpscRecvError(std::sync::mpsc::RecvError),
+    // subscribing a websocket channel which has not been listened
+    ChannelNotListened,
+    // opening more websocket connections than the limit of the token
+    ConnectionLimitExceeded(usize),
+    // the columns of a chart are not of the same length
+    RaggedChart {
+        column: &'static str,
+        len: usize,
+        expected: usize,
+    },
+    // the paths of the required fields a strict parse did not find
+    MissingFields(Vec<String>),
+    // the async REST requests of a websocket listener made outside of a tokio runtime
+    TokioRuntimeRequired,
+    // the REST fallback of a websocket listener built without the feature it polls by
+    FeatureRequired(&'static str),
     // error from serde_json lib
     SerdeJson(serde_json::Error),
     // error from tungstenite lib
     #[cfg(any(feature = "websocket", feature = "async-websocket"))]
-    Tungstenite(tungstenite::Error),
+    Tungstenite(Box<tungstenite::Error>),
     // error from ureq lib
+    #[cfg(feature = "query")]
     Ureq(Box<ureq::Error>),
     // error from reqwest lib
     #[cfg(feature = "async-query")]
@@ -104,6 +121,7 @@ impl std::fmt::Display for FugleError {
             FugleError::SerdeJson(ref e) => write!(f, "Serde_json Lib error: {}", e),
             #[cfg(any(feature = "websocket", feature = "async-websocket"))]
             FugleError::Tungstenite(ref e) => write!(f, "Tungstenite Lib error: {}", e),
+            #[cfg(feature = "query")]
             FugleError::Ureq(ref e) => write!(f, "Ureq Lib error: {}", e),
             #[cfg(feature = "async-query")]
             FugleError::Reqwest(ref e) => write!(f, "Reqwest Lib error: {}", e),
@@ -115,6 +133,30 @@ impl std::fmt::Display for FugleError {
             FugleError::ResourceNotFound => write!(f, "Resource Not Found"),
             FugleError::MpscSendError => write!(f, "MPSC Send Error"),
             FugleError::MpscRecvError(ref e) => write!(f, "MPSC Receive Error: {}", e),
+            FugleError::ChannelNotListened => write!(f, "Websocket channel not listened yet"),
+            FugleError::ConnectionLimitExceeded(limit) => write!(
+                f,
+                "Websocket connection limit of {} per token exceeded",
+                limit
+            ),
+            FugleError::RaggedChart {
+                column,
+                len,
+                expected,
+            } => write!(
+                f,
+                "Chart column {} has {} rows instead of {}",
+                column, len, expected
+            ),
+            FugleError::MissingFields(ref fields) => {
+                write!(f, "Required fields missing: {}", fields.join(", "))
+            }
+            FugleError::TokioRuntimeRequired => {
+                write!(f, "Async REST requests need a tokio runtime")
+            }
+            FugleError::FeatureRequired(feature) => {
+                write!(f, "The REST fallback needs the {} feature", feature)
+            }
         }
     }
 }
@@ -126,6 +168,7 @@ impl std::error::Error for FugleError {
             FugleError::SerdeJson(ref e) => Some(e),
             #[cfg(any(feature = "websocket", feature = "async-websocket"))]
             FugleError::Tungstenite(ref e) => Some(e),
+            #[cfg(feature = "query")]
             FugleError::Ureq(ref e) => Some(e),
             #[cfg(feature = "async-query")]
             FugleError::Reqwest(ref e) => Some(e),
@@ -137,6 +180,12 @@ impl std::error::Error for FugleError {
             FugleError::ResourceNotFound => None,
             FugleError::MpscSendError => None,
             FugleError::MpscRecvError(ref e) => Some(e),
+            FugleError::ChannelNotListened => None,
+            FugleError::ConnectionLimitExceeded(_) => None,
+            FugleError::RaggedChart { .. } => None,
+            FugleError::MissingFields(_) => None,
+            FugleError::TokioRuntimeRequired => None,
+            FugleError::FeatureRequired(_) => None,
         }
     }
 }
@@ -155,6 +204,7 @@ impl From<std::io::Error> for FugleError {
     }
 }
 
+#[cfg(feature = "query")]
 impl From<ureq::Error> for FugleError {
     #[cfg_attr(coverage, no_coverage)]
     fn from(err: ureq::Error) -> FugleError {
@@ -174,7 +224,7 @@ impl From<reqwest::Error> for FugleError {
 impl From<tungstenite::Error> for FugleError {
     #[cfg_attr(coverage, no_coverage)]
     fn from(err: tungstenite::Error) -> FugleError {
-        FugleError::Tungstenite(err)
+        FugleError::Tungstenite(Box::new(err))
     }
 }
 
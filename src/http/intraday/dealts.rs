@@ -7,6 +7,7 @@ use crate::{
 ///
 /// Fetching today's advantage information.
 ///
+#[derive(Clone)]
 pub struct DealtsRequest<'a> {
     symbol_id: &'a str,
     odd_lot: bool,
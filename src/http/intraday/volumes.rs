@@ -7,6 +7,7 @@ use crate::{
 ///
 /// Fetching today's volume information.
 ///
+#[derive(Clone)]
 pub struct VolumesRequest<'a> {
     odd_lot: bool,
     symbol_id: &'a str,
@@ -1,8 +1,11 @@
 pub mod intraday;
 pub mod marketdata;
+#[cfg(any(feature = "query", feature = "async-query"))]
+pub mod poll;
 
 use serde::de::DeserializeOwned;
-use std::time::Duration;
+#[cfg(any(feature = "query", feature = "async-query"))]
+use std::{borrow::Cow, time::Duration};
 
 #[cfg(feature = "query")]
 use ureq::{Agent, AgentBuilder, OrAnyStatus};
@@ -10,15 +13,17 @@ use ureq::{Agent, AgentBuilder, OrAnyStatus};
 #[cfg(feature = "async-query")]
 use reqwest::{Client, ClientBuilder};
 
+#[cfg(any(feature = "query", feature = "async-query"))]
 use crate::{
     errors::{ErrorResponse, FugleError},
-    schema::Result,
+    schema::{strict, Result},
 };
 
 /// Accumulates options towards building an Restful api instance.
 pub struct RestfulBuilder<'a> {
     token: &'a str,
     read_timeout_sec: u64,
+    strict: bool,
 }
 
 impl<'a> Default for RestfulBuilder<'a> {
@@ -35,6 +40,7 @@ impl<'a> RestfulBuilder<'a> {
         RestfulBuilder {
             token: "demo",
             read_timeout_sec: 3,
+            strict: false,
         }
     }
 
@@ -53,7 +59,7 @@ impl<'a> RestfulBuilder<'a> {
     ///     .token("b52153ae36747b17c8bdee801da19542")
     ///     .build();
     /// ```
-    pub fn token(mut self, token: &'a str) -> RestfulBuilder {
+    pub fn token(mut self, token: &'a str) -> RestfulBuilder<'a> {
         self.token = token;
         self
     }
@@ -73,6 +79,23 @@ impl<'a> RestfulBuilder<'a> {
         self
     }
 
+    /// Fails the responses which left out a field fugle always sends
+    /// with MissingFields error, instead of falling back to its default,
+    /// see [`strict`](crate::schema::strict) for the report of every field left out.
+    ///
+    /// # Example:
+    ///
+    /// ```
+    /// # use fugle::http::RestfulBuilder ;
+    /// let client = RestfulBuilder::new()
+    ///     .strict() // a missing price is an error instead of a zero
+    ///     .build();
+    /// ```
+    pub fn strict(mut self) -> RestfulBuilder<'a> {
+        self.strict = true;
+        self
+    }
+
     /// Create a new Block Request instance.
     ///
     /// # Example:
@@ -84,7 +107,31 @@ impl<'a> RestfulBuilder<'a> {
     #[cfg(feature = "query")]
     pub fn build(&self) -> Result<BlockRequest<'a>> {
         Ok(BlockRequest {
+            token: Cow::Borrowed(self.token),
+            strict: self.strict,
+            agent: AgentBuilder::new()
+                .timeout_read(Duration::from_secs(self.read_timeout_sec))
+                .build(),
+        })
+    }
+
+    /// Create a new Agnostic Request instance,
+    /// which runs the block requests on a thread pool to be awaited on any async runtime.
+    ///
+    /// It is not async IO: every request takes a thread of the `blocking` crate pool
+    /// until its ureq call returns, so the concurrent requests are bounded by that pool.
+    ///
+    /// # Example:
+    ///
+    /// ```
+    /// # use fugle::http::RestfulBuilder ;
+    /// let client = RestfulBuilder::new().build_agnostic();
+    /// ```
+    #[cfg(feature = "agnostic-query")]
+    pub fn build_agnostic(&self) -> Result<AgnosticRequest<'a>> {
+        Ok(AgnosticRequest {
             token: self.token,
+            strict: self.strict,
             agent: AgentBuilder::new()
                 .timeout_read(Duration::from_secs(self.read_timeout_sec))
                 .build(),
@@ -102,7 +149,8 @@ impl<'a> RestfulBuilder<'a> {
     #[cfg(feature = "async-query")]
     pub fn build_async(&self) -> Result<AsyncRequest<'a>> {
         Ok(AsyncRequest {
-            token: self.token,
+            token: Cow::Borrowed(self.token),
+            strict: self.strict,
             client: ClientBuilder::new()
                 .timeout(Duration::from_secs(self.read_timeout_sec))
                 .build()?,
@@ -122,51 +170,141 @@ pub trait Request {
     fn queries(&self) -> Vec<Query>;
 }
 
+#[cfg(feature = "query")]
 pub struct BlockRequest<'a> {
-    token: &'a str,
+    token: Cow<'a, str>,
+    strict: bool,
     agent: Agent,
 }
 
+#[cfg(feature = "query")]
 impl<'a> BlockRequest<'a> {
     pub fn call<R>(&self, request: R) -> Result<R::Response>
     where
         R: Request,
     {
-        let mut req = self.agent.get(R::REQUEST_URL).query("apiToken", self.token);
+        get(
+            &self.agent,
+            R::REQUEST_URL,
+            &self.token,
+            request.queries(),
+            self.strict,
+        )
+    }
 
-        for r in request.queries() {
-            req = req.query(&r.param, &r.value)
+    // detaches the request from the borrowed token, to be moved onto a worker thread.
+    #[cfg(feature = "websocket")]
+    pub(crate) fn into_owned(self) -> BlockRequest<'static> {
+        BlockRequest {
+            token: Cow::Owned(self.token.into_owned()),
+            strict: self.strict,
+            agent: self.agent,
         }
+    }
+}
 
-        match req.call().or_any_status() {
-            Ok(res) => {
-                if res.status() != 200 {
-                    let err: ErrorResponse = res.into_json()?;
-                    return Err(err.into());
-                }
-                Ok(res.into_json()?)
+#[cfg(feature = "query")]
+fn get<T: DeserializeOwned>(
+    agent: &Agent,
+    url: &str,
+    token: &str,
+    queries: Vec<Query>,
+    strict: bool,
+) -> Result<T> {
+    let mut req = agent.get(url).query("apiToken", token);
+
+    for r in queries {
+        req = req.query(&r.param, &r.value)
+    }
+
+    match req.call().or_any_status() {
+        Ok(res) => {
+            if res.status() != 200 {
+                let err: ErrorResponse = res.into_json()?;
+                return Err(err.into());
             }
-            Err(e) => Err(FugleError::Ureq(Box::new(e.into()))),
+            if strict {
+                return strict::from_value_strict(res.into_json()?);
+            }
+            Ok(res.into_json()?)
         }
+        Err(e) => Err(FugleError::Ureq(Box::new(e.into()))),
     }
 }
 
-pub struct AsyncRequest<'a> {
+#[cfg(feature = "agnostic-query")]
+pub struct AgnosticRequest<'a> {
     token: &'a str,
+    strict: bool,
+    agent: Agent,
+}
+
+#[cfg(feature = "agnostic-query")]
+impl<'a> AgnosticRequest<'a> {
+    pub async fn call<R>(&self, request: R) -> Result<R::Response>
+    where
+        R: Request,
+        R::Response: Send + 'static,
+    {
+        unblock_get(
+            self.agent.clone(),
+            R::REQUEST_URL,
+            self.token.to_string(),
+            request.queries(),
+            self.strict,
+        )
+        .await
+    }
+}
+
+// kept apart from the call to move nothing borrowed by the request onto the thread pool.
+#[cfg(feature = "agnostic-query")]
+async fn unblock_get<T>(
+    agent: Agent,
+    url: &'static str,
+    token: String,
+    queries: Vec<Query>,
+    strict: bool,
+) -> Result<T>
+where
+    T: DeserializeOwned + Send + 'static,
+{
+    blocking::unblock(move || get(&agent, url, &token, queries, strict)).await
+}
+
+#[cfg(feature = "async-query")]
+pub struct AsyncRequest<'a> {
+    token: Cow<'a, str>,
+    strict: bool,
     client: Client,
 }
 
+#[cfg(feature = "async-query")]
 impl<'a> AsyncRequest<'a> {
     pub async fn call<R>(&self, request: R) -> Result<R::Response>
     where
         R: Request,
     {
-        let mut req = self
-            .client
-            .get(R::REQUEST_URL)
-            .query(&[("apiToken", self.token)]);
+        self.get(R::REQUEST_URL, request.queries()).await
+    }
+
+    // detaches the request from the borrowed token, to be moved into a spawned task.
+    #[cfg(feature = "async-websocket")]
+    pub(crate) fn into_owned(self) -> AsyncRequest<'static> {
+        AsyncRequest {
+            token: Cow::Owned(self.token.into_owned()),
+            strict: self.strict,
+            client: self.client,
+        }
+    }
 
-        for r in request.queries() {
+    pub(crate) async fn get<T>(&self, url: &str, queries: Vec<Query>) -> Result<T>
+    where
+        T: DeserializeOwned,
+    {
+        let mut req = self.client.get(url).query(&[("apiToken", &*self.token)]);
+
+        for r in queries {
             req = req.query(&[(&r.param, &r.value)])
         }
 
@@ -176,6 +314,9 @@ impl<'a> AsyncRequest<'a> {
                     let err: ErrorResponse = res.json().await?;
                     return Err(err.into());
                 }
+                if self.strict {
+                    return strict::from_value_strict(res.json().await?);
+                }
                 Ok(res.json().await?)
             }
             Err(e) => Err(FugleError::Reqwest(e)),
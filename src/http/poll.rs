@@ -0,0 +1,310 @@
+use std::{
+    collections::{BTreeMap, VecDeque},
+    time::Duration,
+};
+
+#[cfg(feature = "async-query")]
+use futures_util::stream::{self, Stream};
+use rust_decimal::Decimal;
+
+#[cfg(feature = "async-query")]
+use crate::http::AsyncRequest;
+#[cfg(feature = "query")]
+use crate::http::BlockRequest;
+use crate::{
+    errors::FugleError,
+    http::{
+        intraday::{DealtsRequest, VolumesRequest},
+        Request,
+    },
+    schema::{dealts::Dealt, volumes::Volume, DealtsResponse, Result, VolumesResponse},
+};
+
+// the longest wait between two polls after repeated rate limit answers.
+const MAX_BACKOFF: Duration = Duration::from_secs(60);
+
+/// A REST endpoint which can be polled for what is new since its last poll.
+pub trait Poll: Request + Clone {
+    type Item;
+    /// What is remembered from the responses so far.
+    type State: Default;
+
+    /// Returns what is new in the response, remembering it into the state.
+    fn changes(state: &mut Self::State, response: Self::Response) -> Vec<Self::Item>;
+}
+
+/// Yields the dealts in serial order, each one once.
+///
+/// A poll only sees the latest page of the dealts,
+/// so the limit of the request should cover the dealts made within one interval.
+impl Poll for DealtsRequest<'_> {
+    type Item = Dealt;
+    /// The serial of the last dealt yielded.
+    type State = Option<u64>;
+
+    fn changes(last: &mut Option<u64>, response: DealtsResponse) -> Vec<Dealt> {
+        let mut dealts: Vec<Dealt> = response
+            .data
+            .dealts
+            .into_iter()
+            .filter(|d| last.is_none_or(|last| d.serial > last))
+            .collect();
+        dealts.sort_by_key(|d| d.serial);
+        dealts.dedup_by_key(|d| d.serial);
+        if let Some(dealt) = dealts.last() {
+            *last = Some(dealt.serial);
+        }
+        dealts
+    }
+}
+
+/// Yields the price levels which are new or whose volume has changed, in price order.
+impl Poll for VolumesRequest<'_> {
+    type Item = Volume;
+    /// The volume of every price level seen.
+    type State = BTreeMap<Decimal, u64>;
+
+    fn changes(levels: &mut BTreeMap<Decimal, u64>, response: VolumesResponse) -> Vec<Volume> {
+        let mut volumes: Vec<Volume> = response
+            .data
+            .volumes
+            .into_iter()
+            .filter(|v| levels.insert(v.price, v.volume) != Some(v.volume))
+            .collect();
+        volumes.sort_by_key(|v| v.price);
+        volumes
+    }
+}
+
+// the polling shared by the block and async pollers.
+struct Polling<P: Poll> {
+    request: P,
+    state: P::State,
+    pending: VecDeque<P::Item>,
+    interval: Duration,
+    // how long to wait before the next call, none before the first one.
+    wait: Duration,
+}
+
+impl<P: Poll> Polling<P> {
+    fn new(request: P, interval: Duration) -> Self {
+        Polling {
+            request,
+            state: P::State::default(),
+            pending: VecDeque::new(),
+            interval,
+            wait: Duration::ZERO,
+        }
+    }
+
+    // keeps the changes of a response, returns the error of a failed call.
+    fn handle(&mut self, response: Result<P::Response>) -> Option<FugleError> {
+        match response {
+            Ok(response) => {
+                self.wait = self.interval;
+                self.pending.extend(P::changes(&mut self.state, response));
+                None
+            }
+            Err(e) => {
+                self.wait = match e {
+                    // backs off until the rate limit lets the calls pass again.
+                    FugleError::RateLimitExceeded => {
+                        (self.wait * 2).clamp(self.interval, MAX_BACKOFF.max(self.interval))
+                    }
+                    _ => self.interval,
+                };
+                Some(e)
+            }
+        }
+    }
+}
+
+/// Polls an endpoint at the interval as a never ending iterator of what is new,
+/// a failed call is yielded as an error and the polling goes on.
+///
+/// A RateLimitExceeded answer doubles the wait before the next call,
+/// until a call passes or the wait reaches one minute.
+#[cfg(feature = "query")]
+pub struct Poller<'c, 'a, P: Poll> {
+    client: &'c BlockRequest<'a>,
+    polling: Polling<P>,
+}
+
+#[cfg(feature = "query")]
+impl<P: Poll> Iterator for Poller<'_, '_, P> {
+    type Item = Result<P::Item>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if let Some(item) = self.polling.pending.pop_front() {
+                return Some(Ok(item));
+            }
+            std::thread::sleep(self.polling.wait);
+            let response = self.client.call(self.polling.request.clone());
+            if let Some(e) = self.polling.handle(response) {
+                return Some(Err(e));
+            }
+        }
+    }
+}
+
+#[cfg(feature = "query")]
+impl<'a> BlockRequest<'a> {
+    /// Polls the endpoint of the request every interval for what is new,
+    /// the first call is made at once and yields everything it returns.
+    ///
+    /// # Example:
+    ///
+    /// ```no_run
+    /// # fn main() -> fugle::schema::Result<()> {
+    /// # use std::time::Duration;
+    /// # use fugle::http::{RestfulBuilder, intraday::DealtsRequest};
+    /// let client = RestfulBuilder::new().build()?;
+    ///
+    /// let request = DealtsRequest::new().symbol_id("2884").limit(100);
+    /// for dealt in client.poll(request, Duration::from_secs(5)).take(10) {
+    ///     println!("{}", dealt?.serial);
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn poll<P: Poll>(&self, request: P, interval: Duration) -> Poller<'_, 'a, P> {
+        Poller {
+            client: self,
+            polling: Polling::new(request, interval),
+        }
+    }
+}
+
+#[cfg(feature = "async-query")]
+impl<'a> AsyncRequest<'a> {
+    /// Polls the endpoint of the request every interval for what is new as a never ending stream,
+    /// the first call is made at once and yields everything it returns.
+    ///
+    /// A failed call is yielded as an error and the polling goes on,
+    /// a RateLimitExceeded answer doubles the wait before the next call,
+    /// until a call passes or the wait reaches one minute.
+    ///
+    /// # Example:
+    ///
+    /// ```no_run
+    /// # #[tokio::main]
+    /// # async fn main() -> fugle::schema::Result<()> {
+    /// # use std::time::Duration;
+    /// # use futures_util::StreamExt;
+    /// # use fugle::http::{RestfulBuilder, intraday::VolumesRequest};
+    /// let client = RestfulBuilder::new().build_async()?;
+    ///
+    /// let request = VolumesRequest::new().symbol_id("2884");
+    /// let volumes = client.poll(request, Duration::from_secs(5));
+    /// futures_util::pin_mut!(volumes);
+    /// while let Some(volume) = volumes.next().await {
+    ///     let volume = volume?;
+    ///     println!("{} {}", volume.price, volume.volume);
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn poll<'s, P>(
+        &'s self,
+        request: P,
+        interval: Duration,
+    ) -> impl Stream<Item = Result<P::Item>> + 's
+    where
+        P: Poll + 's,
+    {
+        stream::unfold(
+            Polling::new(request, interval),
+            move |mut polling| async move {
+                loop {
+                    if let Some(item) = polling.pending.pop_front() {
+                        return Some((Ok(item), polling));
+                    }
+                    async_io::Timer::after(polling.wait).await;
+                    let response = self.call(polling.request.clone()).await;
+                    if let Some(e) = polling.handle(response) {
+                        return Some((Err(e), polling));
+                    }
+                }
+            },
+        )
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn dealts(serials: &[u64]) -> DealtsResponse {
+        let mut response = DealtsResponse::default();
+        response.data.dealts = serials
+            .iter()
+            .map(|&serial| Dealt {
+                serial,
+                ..Default::default()
+            })
+            .collect();
+        response
+    }
+
+    fn volumes(levels: &[(i64, u64)]) -> VolumesResponse {
+        let mut response = VolumesResponse::default();
+        response.data.volumes = levels
+            .iter()
+            .map(|&(price, volume)| Volume {
+                price: Decimal::from(price),
+                volume,
+            })
+            .collect();
+        response
+    }
+
+    #[test]
+    fn test_poll_dealts() {
+        let serials = |dealts: Vec<Dealt>| dealts.iter().map(|d| d.serial).collect::<Vec<u64>>();
+        let mut last = None;
+
+        // fugle returns the latest dealts first.
+        let first = DealtsRequest::changes(&mut last, dealts(&[3, 2, 1]));
+        assert_eq!(serials(first), vec![1, 2, 3]);
+        let next = DealtsRequest::changes(&mut last, dealts(&[5, 4, 3, 2]));
+        assert_eq!(serials(next), vec![4, 5]);
+        assert!(DealtsRequest::changes(&mut last, dealts(&[5, 4])).is_empty());
+        assert!(DealtsRequest::changes(&mut last, dealts(&[])).is_empty());
+        assert_eq!(last, Some(5));
+    }
+
+    #[test]
+    fn test_poll_volumes() {
+        let mut levels = BTreeMap::new();
+        let first = VolumesRequest::changes(&mut levels, volumes(&[(11, 3), (10, 5)]));
+        assert_eq!(first.len(), 2);
+        assert_eq!(first[0].price, Decimal::from(10));
+
+        let next = VolumesRequest::changes(&mut levels, volumes(&[(11, 4), (10, 5), (12, 1)]));
+        let changed: Vec<(Decimal, u64)> = next.iter().map(|v| (v.price, v.volume)).collect();
+        assert_eq!(
+            changed,
+            vec![(Decimal::from(11), 4), (Decimal::from(12), 1)]
+        );
+        assert!(VolumesRequest::changes(&mut levels, volumes(&[(11, 4)])).is_empty());
+    }
+
+    #[test]
+    fn test_poll_backoff() {
+        let interval = Duration::from_secs(5);
+        let mut polling = Polling::new(VolumesRequest::new(), interval);
+        assert_eq!(polling.wait, Duration::ZERO);
+
+        for wait in [5, 10, 20, 40, 60, 60] {
+            assert!(polling.handle(Err(FugleError::RateLimitExceeded)).is_some());
+            assert_eq!(polling.wait, Duration::from_secs(wait));
+        }
+        assert!(polling.handle(Ok(volumes(&[(10, 1)]))).is_none());
+        assert_eq!(polling.wait, interval);
+        assert_eq!(polling.pending.len(), 1);
+
+        assert!(polling.handle(Err(FugleError::Unauthorized)).is_some());
+        assert_eq!(polling.wait, interval);
+    }
+}
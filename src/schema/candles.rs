@@ -2,19 +2,25 @@ use rust_decimal::Decimal;
 use serde::{Deserialize, Serialize};
 use time::Date;
 
-use crate::schema::de_date;
+use crate::schema::{de_date, is_min_date, se_date, se_decimal, Exchange, Market, SecurityType};
 
-#[derive(Debug, Deserialize, Serialize)]
+#[derive(Debug, Clone, Deserialize, Serialize)]
 #[serde(rename_all = "camelCase", default)]
 pub struct Candle {
     #[serde(deserialize_with = "de_date")]
+    #[serde(serialize_with = "se_date", skip_serializing_if = "is_min_date")]
     pub date: Date,
+    #[serde(serialize_with = "se_decimal")]
     pub open: Decimal,
+    #[serde(serialize_with = "se_decimal")]
     pub high: Decimal,
+    #[serde(serialize_with = "se_decimal")]
     pub low: Decimal,
+    #[serde(serialize_with = "se_decimal")]
     pub close: Decimal,
     pub volume: u64,
     pub turnover: u64,
+    #[serde(serialize_with = "se_decimal")]
     pub change: Decimal,
 }
 
@@ -33,15 +39,15 @@ impl Default for Candle {
     }
 }
 
-#[derive(Debug, Deserialize, Serialize)]
+#[derive(Debug, Clone, Deserialize, Serialize)]
 #[serde(rename_all = "camelCase", default)]
 pub struct CandlesResponse {
     #[serde(rename = "symbol")]
     pub symbol_id: String,
     #[serde(rename = "type")]
-    pub typ: String,
-    pub exchange: String,
-    pub market: String,
+    pub typ: SecurityType,
+    pub exchange: Exchange,
+    pub market: Market,
     #[serde(rename = "data")]
     pub candles: Vec<Candle>,
 }
@@ -50,9 +56,9 @@ impl Default for CandlesResponse {
     fn default() -> CandlesResponse {
         CandlesResponse {
             symbol_id: "".to_string(),
-            typ: "".to_string(),
-            exchange: "".to_string(),
-            market: "".to_string(),
+            typ: SecurityType::default(),
+            exchange: Exchange::default(),
+            market: Market::default(),
             candles: Vec::with_capacity(0),
         }
     }
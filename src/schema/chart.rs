@@ -1,35 +1,277 @@
 use rust_decimal::Decimal;
 use serde::{Deserialize, Serialize};
+use time::OffsetDateTime;
 
-use crate::schema::Info;
+use crate::{
+    errors::FugleError,
+    schema::{de_unix_millis, se_decimals, se_unix_millis, Info, Result},
+};
 
-#[derive(Default, Debug, Deserialize, Serialize)]
+#[derive(Default, Debug, Clone, Deserialize, Serialize)]
 #[serde(default, rename_all = "camelCase")]
 pub struct Chart {
-    #[serde(rename = "o")]
+    #[serde(rename = "o", serialize_with = "se_decimals")]
     pub open: Vec<Decimal>,
-    #[serde(rename = "h")]
+    #[serde(rename = "h", serialize_with = "se_decimals")]
     pub high: Vec<Decimal>,
-    #[serde(rename = "l")]
+    #[serde(rename = "l", serialize_with = "se_decimals")]
     pub low: Vec<Decimal>,
-    #[serde(rename = "c")]
+    #[serde(rename = "c", serialize_with = "se_decimals")]
     pub close: Vec<Decimal>,
     #[serde(rename = "v")]
     pub volume: Vec<u64>,
-    #[serde(rename = "t")]
-    pub unix_timestamp: Vec<u64>,
+    /// The start of every bar in Asia/Taipei.
+    #[serde(
+        rename = "t",
+        serialize_with = "se_unix_millis",
+        deserialize_with = "de_unix_millis"
+    )]
+    pub at: Vec<OffsetDateTime>,
 }
 
-#[derive(Default, Debug, Deserialize, Serialize)]
+/// One row of the chart.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Bar {
+    /// The start of the bar in Asia/Taipei.
+    pub at: OffsetDateTime,
+    pub open: Decimal,
+    pub high: Decimal,
+    pub low: Decimal,
+    pub close: Decimal,
+    pub volume: u64,
+}
+
+impl Chart {
+    /// Returns an iterator over the rows of the chart as bars, borrowing the columns.
+    ///
+    /// Returns RaggedChart error if the columns are not of the same length.
+    ///
+    /// # Example:
+    ///
+    /// ```
+    /// # fn main() -> fugle::schema::Result<()> {
+    /// # use fugle::schema::chart::Chart;
+    /// let chart = Chart::default();
+    /// for bar in chart.bars()? {
+    ///     println!("{} {}", bar.at, bar.close);
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn bars(&self) -> Result<Bars<'_>> {
+        let expected = self.at.len();
+        let columns = [
+            ("o", self.open.len()),
+            ("h", self.high.len()),
+            ("l", self.low.len()),
+            ("c", self.close.len()),
+            ("v", self.volume.len()),
+        ];
+        for (column, len) in columns {
+            if len != expected {
+                return Err(FugleError::RaggedChart {
+                    column,
+                    len,
+                    expected,
+                });
+            }
+        }
+
+        Ok(Bars {
+            chart: self,
+            front: 0,
+            back: expected,
+        })
+    }
+
+    /// Returns the rows of the chart as bars, see [`Chart::bars`].
+    pub fn to_bars(&self) -> Result<Vec<Bar>> {
+        Ok(self.bars()?.collect())
+    }
+}
+
+/// The rows of a chart whose columns have been checked, yielded as bars.
+#[derive(Debug, Clone)]
+pub struct Bars<'a> {
+    chart: &'a Chart,
+    front: usize,
+    back: usize,
+}
+
+impl Bars<'_> {
+    fn bar(&self, i: usize) -> Bar {
+        let chart = self.chart;
+        Bar {
+            at: chart.at[i],
+            open: chart.open[i],
+            high: chart.high[i],
+            low: chart.low[i],
+            close: chart.close[i],
+            volume: chart.volume[i],
+        }
+    }
+}
+
+impl Iterator for Bars<'_> {
+    type Item = Bar;
+
+    fn next(&mut self) -> Option<Bar> {
+        if self.front >= self.back {
+            return None;
+        }
+        self.front += 1;
+        Some(self.bar(self.front - 1))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let len = self.back - self.front;
+        (len, Some(len))
+    }
+}
+
+impl DoubleEndedIterator for Bars<'_> {
+    fn next_back(&mut self) -> Option<Bar> {
+        if self.front >= self.back {
+            return None;
+        }
+        self.back -= 1;
+        Some(self.bar(self.back))
+    }
+}
+
+impl ExactSizeIterator for Bars<'_> {}
+
+/// Builds the columns back from the bars, e.g. for serializing.
+impl FromIterator<Bar> for Chart {
+    fn from_iter<I: IntoIterator<Item = Bar>>(bars: I) -> Chart {
+        let mut chart = Chart::default();
+        for bar in bars {
+            chart.at.push(bar.at);
+            chart.open.push(bar.open);
+            chart.high.push(bar.high);
+            chart.low.push(bar.low);
+            chart.close.push(bar.close);
+            chart.volume.push(bar.volume);
+        }
+        chart
+    }
+}
+
+impl From<&[Bar]> for Chart {
+    fn from(bars: &[Bar]) -> Chart {
+        bars.iter().copied().collect()
+    }
+}
+
+#[derive(Default, Debug, Clone, Deserialize, Serialize)]
 #[serde(rename_all = "camelCase", default)]
 pub struct ChartData {
     pub info: Info,
     pub chart: Chart,
 }
 
-#[derive(Default, Debug, Deserialize, Serialize)]
+#[derive(Default, Debug, Clone, Deserialize, Serialize)]
 #[serde(rename_all = "camelCase", default)]
 pub struct ChartResponse {
     pub api_version: String,
     pub data: ChartData,
 }
+
+#[cfg(test)]
+mod test {
+    use time::{Date, Month, Time};
+
+    use super::*;
+    use crate::schema::TAIPEI;
+
+    fn chart(rows: &[(i64, i64, u64)]) -> Chart {
+        let mut chart = Chart::default();
+        for &(t, price, volume) in rows {
+            let price = Decimal::from(price);
+            let at = OffsetDateTime::from_unix_timestamp(t / 1000).unwrap();
+            chart.at.push(at.to_offset(TAIPEI));
+            chart.open.push(price);
+            chart.high.push(price);
+            chart.low.push(price);
+            chart.close.push(price);
+            chart.volume.push(volume);
+        }
+        chart
+    }
+
+    #[test]
+    fn test_chart_bars() {
+        // 2021-10-22 09:01 and 09:02 in Asia/Taipei.
+        let chart = chart(&[(1634864460000, 10, 1), (1634864520000, 11, 2)]);
+        let bars = chart.to_bars().unwrap();
+        assert_eq!(bars.len(), 2);
+
+        let at = bars[0].at;
+        assert_eq!(at.offset(), TAIPEI);
+        assert_eq!(
+            at.date(),
+            Date::from_calendar_date(2021, Month::October, 22).unwrap()
+        );
+        assert_eq!(at.time(), Time::from_hms(9, 1, 0).unwrap());
+        assert_eq!(bars[1].close, Decimal::from(11));
+        assert_eq!(bars[1].volume, 2);
+
+        let reversed: Vec<u64> = chart.bars().unwrap().rev().map(|b| b.volume).collect();
+        assert_eq!(reversed, vec![2, 1]);
+    }
+
+    #[test]
+    fn test_chart_bars_round_trip() {
+        let chart = chart(&[(1634864460000, 10, 1), (1634864520000, 11, 2)]);
+        let back = Chart::from(chart.to_bars().unwrap().as_slice());
+        assert_eq!(back.at, chart.at);
+        assert_eq!(back.close, chart.close);
+        assert_eq!(back.volume, chart.volume);
+        assert_eq!(
+            serde_json::to_value(&back).unwrap(),
+            serde_json::to_value(&chart).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_chart_timestamps() {
+        let chart: Chart = serde_json::from_str(r#"{"t":[1634864460000]}"#).unwrap();
+        assert_eq!(chart.at[0].offset(), TAIPEI);
+        assert_eq!(chart.at[0].time(), Time::from_hms(9, 1, 0).unwrap());
+        assert_eq!(
+            serde_json::to_value(&chart).unwrap()["t"],
+            serde_json::json!([1634864460000i64])
+        );
+    }
+
+    #[test]
+    fn test_chart_bars_ragged() {
+        let mut ragged = chart(&[(1634864460000, 10, 1), (1634864520000, 11, 2)]);
+        ragged.volume.pop();
+        assert!(matches!(
+            ragged.bars(),
+            Err(FugleError::RaggedChart {
+                column: "v",
+                len: 1,
+                expected: 2
+            })
+        ));
+        assert!(Chart::default().to_bars().unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_chart_timestamps_out_of_range() {
+        // a bar before the unix epoch is kept as it is.
+        let before_epoch = chart(&[(-60000, 10, 1)]);
+        let back = Chart::from(before_epoch.to_bars().unwrap().as_slice());
+        assert_eq!(back.at, before_epoch.at);
+        assert_eq!(
+            serde_json::to_value(&back).unwrap()["t"],
+            serde_json::json!([-60000])
+        );
+
+        // a timestamp no date time can hold fails the parsing instead of making up a bar.
+        let json = format!(r#"{{"t":[{}]}}"#, i64::MAX);
+        assert!(serde_json::from_str::<Chart>(&json).is_err());
+    }
+}
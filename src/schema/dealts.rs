@@ -1,16 +1,26 @@
 use rust_decimal::Decimal;
 use serde::{Deserialize, Serialize};
-use time::PrimitiveDateTime;
+use time::OffsetDateTime;
 
-use crate::schema::{de_primitive_date_time, Info};
+use crate::schema::{
+    de_offset_date_time, is_min_date_time, quote::QuoteTrade, se_decimal, se_offset_date_time,
+    Info, MIN_DATE_TIME,
+};
 
-#[derive(Debug, Deserialize, Serialize)]
+#[derive(Debug, Clone, Deserialize, Serialize)]
 #[serde(rename_all = "camelCase", default)]
 pub struct Dealt {
-    #[serde(deserialize_with = "de_primitive_date_time")]
-    pub at: PrimitiveDateTime,
+    #[serde(deserialize_with = "de_offset_date_time")]
+    #[serde(
+        serialize_with = "se_offset_date_time",
+        skip_serializing_if = "is_min_date_time"
+    )]
+    pub at: OffsetDateTime,
+    #[serde(serialize_with = "se_decimal")]
     pub bid: Decimal,
+    #[serde(serialize_with = "se_decimal")]
     pub ask: Decimal,
+    #[serde(serialize_with = "se_decimal")]
     pub price: Decimal,
     pub volume: u64,
     pub serial: u64,
@@ -19,7 +29,7 @@ pub struct Dealt {
 impl Default for Dealt {
     fn default() -> Dealt {
         Dealt {
-            at: PrimitiveDateTime::MIN,
+            at: MIN_DATE_TIME,
             bid: Decimal::new(0, 2),
             ask: Decimal::new(0, 2),
             price: Decimal::new(0, 2),
@@ -29,14 +39,27 @@ impl Default for Dealt {
     }
 }
 
-#[derive(Default, Debug, Deserialize, Serialize)]
+impl From<&QuoteTrade> for Dealt {
+    fn from(t: &QuoteTrade) -> Dealt {
+        Dealt {
+            at: t.at,
+            bid: t.bid,
+            ask: t.ask,
+            price: t.price,
+            volume: t.volume,
+            serial: t.serial,
+        }
+    }
+}
+
+#[derive(Default, Debug, Clone, Deserialize, Serialize)]
 #[serde(rename_all = "camelCase", default)]
 pub struct DealtsData {
     pub info: Info,
     pub dealts: Vec<Dealt>,
 }
 
-#[derive(Default, Debug, Deserialize, Serialize)]
+#[derive(Default, Debug, Clone, Deserialize, Serialize)]
 #[serde(rename_all = "camelCase", default)]
 pub struct DealtsResponse {
     pub api_version: String,
@@ -50,7 +73,7 @@ mod test {
     #[test]
     fn test_dealt_default() {
         let d = Dealt::default();
-        assert_eq!(d.at, PrimitiveDateTime::MIN);
+        assert_eq!(d.at, MIN_DATE_TIME);
         assert_eq!(d.bid, Decimal::new(0, 2));
         assert_eq!(d.ask, Decimal::new(0, 2));
         assert_eq!(d.price, Decimal::new(0, 2));
@@ -0,0 +1,142 @@
+use std::fmt;
+
+use serde::{Deserialize, Serialize};
+
+// defines an enum of the codes fugle sends for a string field,
+// with an Unknown variant keeping any code fugle adds later.
+macro_rules! code_enum {
+    ($(#[$meta:meta])* $name:ident { $($(#[$variant_meta:meta])* $variant:ident => $code:literal,)+ }) => {
+        $(#[$meta])*
+        #[derive(Debug, Clone, PartialEq, Eq, Hash, Deserialize, Serialize)]
+        #[serde(from = "String", into = "String")]
+        pub enum $name {
+            $($(#[$variant_meta])* $variant,)+
+            /// A code this version does not know yet, empty if fugle does not send one.
+            Unknown(String),
+        }
+
+        impl $name {
+            /// Returns the code fugle sends.
+            pub fn as_str(&self) -> &str {
+                match self {
+                    $($name::$variant => $code,)+
+                    $name::Unknown(code) => code,
+                }
+            }
+        }
+
+        impl Default for $name {
+            fn default() -> $name {
+                $name::Unknown("".to_string())
+            }
+        }
+
+        impl From<&str> for $name {
+            fn from(code: &str) -> $name {
+                match code {
+                    $($code => $name::$variant,)+
+                    _ => $name::Unknown(code.to_string()),
+                }
+            }
+        }
+
+        impl From<String> for $name {
+            fn from(code: String) -> $name {
+                match $name::from(code.as_str()) {
+                    $name::Unknown(_) => $name::Unknown(code),
+                    known => known,
+                }
+            }
+        }
+
+        impl From<$name> for String {
+            fn from(value: $name) -> String {
+                match value {
+                    $name::Unknown(code) => code,
+                    known => known.as_str().to_string(),
+                }
+            }
+        }
+
+        impl fmt::Display for $name {
+            fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                f.write_str(self.as_str())
+            }
+        }
+    };
+}
+
+code_enum! {
+    /// The market a security is listed on.
+    Market {
+        /// Taiwan Stock Exchange.
+        Tse => "TSE",
+        /// Taipei Exchange, the over-the-counter market.
+        Otc => "OTC",
+        /// Emerging Stock Board.
+        Esb => "ESB",
+        /// Taiwan Innovation Board.
+        Tib => "TIB",
+        /// Pioneer Stock Board.
+        Psb => "PSB",
+    }
+}
+
+code_enum! {
+    /// The exchange operating the market.
+    Exchange {
+        /// Taiwan Stock Exchange Corporation.
+        Twse => "TWSE",
+        /// Taipei Exchange.
+        Tpex => "TPEx",
+    }
+}
+
+code_enum! {
+    /// The type of a security, the odd lot trading of a stock has its own type.
+    SecurityType {
+        Equity => "EQUITY",
+        OddLot => "ODDLOT",
+        Index => "INDEX",
+        Etf => "ETF",
+        Warrant => "WARRANT",
+    }
+}
+
+code_enum! {
+    /// The currency a security is traded in.
+    Currency {
+        /// New Taiwan dollar.
+        Twd => "TWD",
+        /// United States dollar.
+        Usd => "USD",
+        /// Chinese yuan.
+        Cny => "CNY",
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_market_codes() {
+        assert_eq!(Market::from("TSE"), Market::Tse);
+        assert_eq!(Exchange::from("TPEx"), Exchange::Tpex);
+        assert_eq!(SecurityType::from("ODDLOT"), SecurityType::OddLot);
+        assert_eq!(Currency::from("TWD").to_string(), "TWD");
+        assert_eq!(Market::from("NEW"), Market::Unknown("NEW".to_string()));
+        assert_eq!(Market::default().as_str(), "");
+    }
+
+    #[test]
+    fn test_market_serde() {
+        let typ: SecurityType = serde_json::from_str(r#""EQUITY""#).unwrap();
+        assert_eq!(typ, SecurityType::Equity);
+        assert_eq!(serde_json::to_string(&typ).unwrap(), r#""EQUITY""#);
+
+        let unknown: Currency = serde_json::from_str(r#""EUR""#).unwrap();
+        assert_eq!(unknown, Currency::Unknown("EUR".to_string()));
+        assert_eq!(serde_json::to_string(&unknown).unwrap(), r#""EUR""#);
+    }
+}
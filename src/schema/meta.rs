@@ -1,23 +1,26 @@
 use rust_decimal::Decimal;
 use serde::{Deserialize, Serialize};
 
-use crate::schema::Info;
+use crate::schema::{se_decimal, Currency, Info, Market};
 
-#[derive(Default, Debug, Deserialize, Serialize)]
+#[derive(Default, Debug, Clone, Deserialize, Serialize)]
 #[serde(default, rename_all = "camelCase")]
 pub struct Meta {
-    pub market: String,
+    pub market: Market,
     pub name_zh_tw: String,
     pub industry_zh_tw: String,
+    #[serde(serialize_with = "se_decimal")]
     pub price_reference: Decimal,
+    #[serde(serialize_with = "se_decimal")]
     pub price_high_limit: Decimal,
+    #[serde(serialize_with = "se_decimal")]
     pub price_low_limit: Decimal,
     pub can_day_buy_sell: bool,
     pub can_day_sell_buy: bool,
     pub can_short_margin: bool,
     pub can_short_lend: bool,
     pub trading_unit: u64,
-    pub currency: String,
+    pub currency: Currency,
     pub is_terminated: bool,
     pub is_suspended: bool,
     pub type_zh_tw: String,
@@ -25,7 +28,7 @@ pub struct Meta {
     pub is_unusually_recommended: bool,
 }
 
-#[derive(Default, Debug, Deserialize, Serialize)]
+#[derive(Default, Debug, Clone, Deserialize, Serialize)]
 #[serde(rename_all = "camelCase")]
 pub struct MetaData {
     #[serde(default)]
@@ -34,7 +37,7 @@ pub struct MetaData {
     pub meta: Meta,
 }
 
-#[derive(Default, Debug, Deserialize, Serialize)]
+#[derive(Default, Debug, Clone, Deserialize, Serialize)]
 #[serde(rename_all = "camelCase")]
 pub struct MetaResponse {
     #[serde(default)]
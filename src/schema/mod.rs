@@ -10,62 +10,209 @@ pub mod volumes;
 pub use volumes::VolumesResponse;
 pub mod candles;
 pub use candles::CandlesResponse;
+pub mod market;
+pub use market::{Currency, Exchange, Market, SecurityType};
+pub mod strict;
 
-use serde::{de, Deserialize, Deserializer, Serialize};
-use time::{Date, PrimitiveDateTime};
+use rust_decimal::{prelude::ToPrimitive, Decimal};
+use serde::{de, ser, ser::SerializeSeq, Deserialize, Deserializer, Serialize, Serializer};
+use time::{
+    format_description::BorrowedFormatItem, Date, OffsetDateTime, PrimitiveDateTime, UtcOffset,
+};
 
 use crate::errors::FugleError;
 
 pub type Result<T> = std::result::Result<T, FugleError>;
 
+// the formats fugle sends the dates and the times in.
+const DATE_FORMAT: &str = "[year]-[month]-[day]";
+const DATE_TIME_FORMAT: &str = "[year]-[month]-[day]T[hour]:[minute]:[second].[subsecond digits:3]\
+     [offset_hour sign:mandatory]:[offset_minute]";
+
+fn format<S: Serializer>(
+    description: &str,
+    format: impl FnOnce(&[BorrowedFormatItem<'_>]) -> std::result::Result<String, time::error::Format>,
+    serializer: S,
+) -> std::result::Result<S::Ok, S::Error> {
+    let description =
+        time::format_description::parse_borrowed::<2>(description).map_err(ser::Error::custom)?;
+    serializer.serialize_str(&format(&description).map_err(ser::Error::custom)?)
+}
+
 pub fn de_date<'de, D>(deserializer: D) -> std::result::Result<Date, D::Error>
 where
     D: Deserializer<'de>,
 {
     let s = String::deserialize(deserializer)?;
     let format =
-        time::format_description::parse("[year]-[month]-[day]").map_err(de::Error::custom)?;
+        time::format_description::parse_borrowed::<2>(DATE_FORMAT).map_err(de::Error::custom)?;
     Date::parse(&s, &format).map_err(de::Error::custom)
 }
 
-pub fn de_primitive_date_time<'de, D>(
-    deserializer: D,
-) -> std::result::Result<PrimitiveDateTime, D::Error>
+/// The time zone of the Taiwan markets, Asia/Taipei has no daylight saving time.
+pub const TAIPEI: UtcOffset = match UtcOffset::from_hms(8, 0, 0) {
+    Ok(offset) => offset,
+    Err(_) => panic!("UTC+8 is a valid offset"),
+};
+
+/// The default of a time the response does not carry.
+pub const MIN_DATE_TIME: OffsetDateTime = PrimitiveDateTime::MIN.assume_utc();
+
+pub fn de_offset_date_time<'de, D>(deserializer: D) -> std::result::Result<OffsetDateTime, D::Error>
 where
     D: Deserializer<'de>,
 {
     let s = String::deserialize(deserializer)?;
-    PrimitiveDateTime::parse(&s, &time::format_description::well_known::Rfc3339)
+    OffsetDateTime::parse(&s, &time::format_description::well_known::Rfc3339)
         .map_err(de::Error::custom)
 }
 
-#[derive(Debug, Deserialize, Serialize)]
+pub fn se_date<S: Serializer>(date: &Date, serializer: S) -> std::result::Result<S::Ok, S::Error> {
+    format(DATE_FORMAT, |f| date.format(f), serializer)
+}
+
+pub fn se_offset_date_time<S: Serializer>(
+    at: &OffsetDateTime,
+    serializer: S,
+) -> std::result::Result<S::Ok, S::Error> {
+    format(DATE_TIME_FORMAT, |f| at.format(f), serializer)
+}
+
+// the chart timestamps are unix timestamps in milliseconds.
+const NANOS_PER_MILLI: i128 = 1_000_000;
+
+/// Deserializes the unix timestamps in milliseconds of a chart as date times in Asia/Taipei.
+pub fn de_unix_millis<'de, D>(deserializer: D) -> std::result::Result<Vec<OffsetDateTime>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    Vec::<i64>::deserialize(deserializer)?
+        .into_iter()
+        .map(|t| {
+            OffsetDateTime::from_unix_timestamp_nanos(t as i128 * NANOS_PER_MILLI)
+                .map(|at| at.to_offset(TAIPEI))
+                .map_err(de::Error::custom)
+        })
+        .collect()
+}
+
+/// Serializes the date times of a chart back as the unix timestamps in milliseconds.
+pub fn se_unix_millis<S: Serializer>(
+    ats: &[OffsetDateTime],
+    serializer: S,
+) -> std::result::Result<S::Ok, S::Error> {
+    let mut seq = serializer.serialize_seq(Some(ats.len()))?;
+    for at in ats {
+        let t = at.unix_timestamp_nanos() / NANOS_PER_MILLI;
+        seq.serialize_element(&(t as i64))?;
+    }
+    seq.end()
+}
+
+// the defaults of the dates and the times are left out, as fugle leaves them out.
+pub(crate) fn is_min_date(date: &Date) -> bool {
+    *date == Date::MIN
+}
+
+pub(crate) fn is_min_date_time(at: &OffsetDateTime) -> bool {
+    *at == MIN_DATE_TIME
+}
+
+/// Serializes a decimal as the JSON number fugle sends instead of a string.
+pub fn se_decimal<S: Serializer>(
+    decimal: &Decimal,
+    serializer: S,
+) -> std::result::Result<S::Ok, S::Error> {
+    match decimal
+        .fract()
+        .is_zero()
+        .then(|| decimal.to_i64())
+        .flatten()
+    {
+        Some(integer) => serializer.serialize_i64(integer),
+        None => serializer.serialize_f64(decimal.to_f64().unwrap_or(f64::NAN)),
+    }
+}
+
+struct Number<'a>(&'a Decimal);
+
+impl Serialize for Number<'_> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error> {
+        se_decimal(self.0, serializer)
+    }
+}
+
+pub fn se_decimals<S: Serializer>(
+    decimals: &[Decimal],
+    serializer: S,
+) -> std::result::Result<S::Ok, S::Error> {
+    let mut seq = serializer.serialize_seq(Some(decimals.len()))?;
+    for decimal in decimals {
+        seq.serialize_element(&Number(decimal))?;
+    }
+    seq.end()
+}
+
+/// Converts the offset-aware times of the schemas into UTC or the Taipei local time,
+/// both of which are still the same instant.
+///
+/// # Example:
+///
+/// ```
+/// # use fugle::schema::{DateTimeExt, TAIPEI};
+/// # use time::{OffsetDateTime, UtcOffset};
+/// let at = OffsetDateTime::UNIX_EPOCH.in_taipei();
+/// assert_eq!(at.offset(), TAIPEI);
+/// assert_eq!(at.in_utc().offset(), UtcOffset::UTC);
+/// assert_eq!(at, OffsetDateTime::UNIX_EPOCH);
+/// ```
+pub trait DateTimeExt {
+    fn in_utc(self) -> OffsetDateTime;
+    fn in_taipei(self) -> OffsetDateTime;
+}
+
+impl DateTimeExt for OffsetDateTime {
+    fn in_utc(self) -> OffsetDateTime {
+        self.to_offset(UtcOffset::UTC)
+    }
+
+    fn in_taipei(self) -> OffsetDateTime {
+        self.to_offset(TAIPEI)
+    }
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
 #[serde(rename_all = "camelCase", default)]
 pub struct Info {
-    #[serde(deserialize_with = "de_primitive_date_time")]
-    pub last_updated_at: PrimitiveDateTime,
+    #[serde(deserialize_with = "de_offset_date_time")]
+    #[serde(
+        serialize_with = "se_offset_date_time",
+        skip_serializing_if = "is_min_date_time"
+    )]
+    pub last_updated_at: OffsetDateTime,
     #[serde(deserialize_with = "de_date")]
+    #[serde(serialize_with = "se_date", skip_serializing_if = "is_min_date")]
     pub date: Date,
     pub symbol_id: String,
     pub country_code: String,
     pub time_zone: String,
-    pub exchange: String,
-    pub market: String,
+    pub exchange: Exchange,
+    pub market: Market,
     #[serde(rename = "type")]
-    pub typ: String,
+    pub typ: SecurityType,
 }
 
 impl Default for Info {
     fn default() -> Info {
         Info {
-            last_updated_at: PrimitiveDateTime::MIN,
+            last_updated_at: MIN_DATE_TIME,
             date: Date::MIN,
             symbol_id: "".to_string(),
             country_code: "".to_string(),
             time_zone: "".to_string(),
-            exchange: "".to_string(),
-            market: "".to_string(),
-            typ: "".to_string(),
+            exchange: Exchange::default(),
+            market: Market::default(),
+            typ: SecurityType::default(),
         }
     }
 }
@@ -77,13 +224,34 @@ mod test {
     #[test]
     fn test_info_default() {
         let i = Info::default();
-        assert_eq!(i.last_updated_at, PrimitiveDateTime::MIN);
+        assert_eq!(i.last_updated_at, MIN_DATE_TIME);
         assert_eq!(i.date, Date::MIN);
         assert_eq!(i.symbol_id, "".to_string());
         assert_eq!(i.country_code, "".to_string());
         assert_eq!(i.time_zone, "".to_string());
-        assert_eq!(i.exchange, "".to_string());
-        assert_eq!(i.market, "".to_string());
-        assert_eq!(i.typ, "".to_string());
+        assert_eq!(i.exchange, Exchange::default());
+        assert_eq!(i.market, Market::default());
+        assert_eq!(i.typ, SecurityType::default());
+    }
+
+    #[test]
+    fn test_info_serialize() {
+        let json = serde_json::to_value(Info::default()).unwrap();
+        assert!(json.get("date").is_none());
+        assert!(json.get("lastUpdatedAt").is_none());
+
+        let sent = r#"{"date":"2021-10-22","lastUpdatedAt":"2021-10-22T13:30:00.050+08:00"}"#;
+        let info: Info = serde_json::from_str(sent).unwrap();
+        let json = serde_json::to_value(&info).unwrap();
+        assert_eq!(json["date"], "2021-10-22");
+        assert_eq!(json["lastUpdatedAt"], "2021-10-22T13:30:00.050+08:00");
+    }
+
+    #[test]
+    fn test_decimal_serialize() {
+        let json = |d: Decimal| serde_json::to_string(&Number(&d)).unwrap();
+        assert_eq!(json(Decimal::new(2650, 2)), "26.5");
+        assert_eq!(json(Decimal::new(2600, 2)), "26");
+        assert_eq!(json(Decimal::new(-19, 2)), "-0.19");
     }
 }
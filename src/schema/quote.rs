@@ -1,15 +1,22 @@
 use rust_decimal::Decimal;
 use serde::{Deserialize, Serialize};
-use time::PrimitiveDateTime;
+use time::OffsetDateTime;
 
-use crate::schema::{de_primitive_date_time, Info};
+use crate::schema::{
+    de_offset_date_time, is_min_date_time, se_decimal, se_offset_date_time, Info, MIN_DATE_TIME,
+};
 
-#[derive(Debug, Deserialize, Serialize)]
+#[derive(Debug, Clone, Deserialize, Serialize)]
 #[serde(rename_all = "camelCase", default)]
 pub struct QuoteTotal {
-    #[serde(deserialize_with = "de_primitive_date_time")]
-    pub at: PrimitiveDateTime,
+    #[serde(deserialize_with = "de_offset_date_time")]
+    #[serde(
+        serialize_with = "se_offset_date_time",
+        skip_serializing_if = "is_min_date_time"
+    )]
+    pub at: OffsetDateTime,
     pub transaction: u64,
+    #[serde(serialize_with = "se_decimal")]
     pub trade_value: Decimal,
     pub trade_volume: u64,
     pub trade_volume_at_bid: u64,
@@ -24,7 +31,7 @@ pub struct QuoteTotal {
 impl Default for QuoteTotal {
     fn default() -> QuoteTotal {
         QuoteTotal {
-            at: PrimitiveDateTime::MIN,
+            at: MIN_DATE_TIME,
             transaction: 0,
             trade_value: Decimal::new(0, 2),
             trade_volume: 0,
@@ -39,13 +46,20 @@ impl Default for QuoteTotal {
     }
 }
 
-#[derive(Debug, Deserialize, Serialize)]
+#[derive(Debug, Clone, Deserialize, Serialize)]
 #[serde(rename_all = "camelCase", default)]
 pub struct QuoteTrial {
-    #[serde(deserialize_with = "de_primitive_date_time")]
-    pub at: PrimitiveDateTime,
+    #[serde(deserialize_with = "de_offset_date_time")]
+    #[serde(
+        serialize_with = "se_offset_date_time",
+        skip_serializing_if = "is_min_date_time"
+    )]
+    pub at: OffsetDateTime,
+    #[serde(serialize_with = "se_decimal")]
     pub bid: Decimal,
+    #[serde(serialize_with = "se_decimal")]
     pub ask: Decimal,
+    #[serde(serialize_with = "se_decimal")]
     pub price: Decimal,
     pub volume: u64,
 }
@@ -53,7 +67,7 @@ pub struct QuoteTrial {
 impl Default for QuoteTrial {
     fn default() -> QuoteTrial {
         QuoteTrial {
-            at: PrimitiveDateTime::MIN,
+            at: MIN_DATE_TIME,
             bid: Decimal::new(0, 2),
             ask: Decimal::new(0, 2),
             price: Decimal::new(0, 2),
@@ -62,13 +76,20 @@ impl Default for QuoteTrial {
     }
 }
 
-#[derive(Debug, Deserialize, Serialize)]
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
 #[serde(rename_all = "camelCase", default)]
 pub struct QuoteTrade {
-    #[serde(deserialize_with = "de_primitive_date_time")]
-    pub at: PrimitiveDateTime,
+    #[serde(deserialize_with = "de_offset_date_time")]
+    #[serde(
+        serialize_with = "se_offset_date_time",
+        skip_serializing_if = "is_min_date_time"
+    )]
+    pub at: OffsetDateTime,
+    #[serde(serialize_with = "se_decimal")]
     pub bid: Decimal,
+    #[serde(serialize_with = "se_decimal")]
     pub ask: Decimal,
+    #[serde(serialize_with = "se_decimal")]
     pub price: Decimal,
     pub volume: u64,
     pub serial: u64,
@@ -77,7 +98,7 @@ pub struct QuoteTrade {
 impl Default for QuoteTrade {
     fn default() -> QuoteTrade {
         QuoteTrade {
-            at: PrimitiveDateTime::MIN,
+            at: MIN_DATE_TIME,
             price: Decimal::new(0, 2),
             bid: Decimal::new(0, 2),
             ask: Decimal::new(0, 2),
@@ -87,18 +108,23 @@ impl Default for QuoteTrade {
     }
 }
 
-#[derive(Default, Debug, Deserialize, Serialize)]
+#[derive(Default, Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
 #[serde(rename_all = "camelCase", default)]
 pub struct QuoteBidAsk {
+    #[serde(serialize_with = "se_decimal")]
     pub price: Decimal,
     pub volume: u64,
 }
 
-#[derive(Debug, Deserialize, Serialize)]
+#[derive(Debug, Clone, Deserialize, Serialize)]
 #[serde(rename_all = "camelCase", default)]
 pub struct QuoteOrder {
-    #[serde(deserialize_with = "de_primitive_date_time")]
-    pub at: PrimitiveDateTime,
+    #[serde(deserialize_with = "de_offset_date_time")]
+    #[serde(
+        serialize_with = "se_offset_date_time",
+        skip_serializing_if = "is_min_date_time"
+    )]
+    pub at: OffsetDateTime,
     pub bids: Vec<QuoteBidAsk>,
     pub asks: Vec<QuoteBidAsk>,
 }
@@ -106,31 +132,36 @@ pub struct QuoteOrder {
 impl Default for QuoteOrder {
     fn default() -> QuoteOrder {
         QuoteOrder {
-            at: PrimitiveDateTime::MIN,
+            at: MIN_DATE_TIME,
             bids: Vec::with_capacity(0),
             asks: Vec::with_capacity(0),
         }
     }
 }
 
-#[derive(Debug, Deserialize, Serialize)]
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
 #[serde(rename_all = "camelCase", default)]
 pub struct QuotePrice {
+    #[serde(serialize_with = "se_decimal")]
     pub price: Decimal,
-    #[serde(deserialize_with = "de_primitive_date_time")]
-    pub at: PrimitiveDateTime,
+    #[serde(deserialize_with = "de_offset_date_time")]
+    #[serde(
+        serialize_with = "se_offset_date_time",
+        skip_serializing_if = "is_min_date_time"
+    )]
+    pub at: OffsetDateTime,
 }
 
 impl Default for QuotePrice {
     fn default() -> QuotePrice {
         QuotePrice {
-            at: PrimitiveDateTime::MIN,
+            at: MIN_DATE_TIME,
             price: Decimal::new(0, 2),
         }
     }
 }
 
-#[derive(Default, Debug, Deserialize, Serialize)]
+#[derive(Default, Debug, Clone, Deserialize, Serialize)]
 #[serde(rename_all = "camelCase", default)]
 pub struct Quote {
     pub is_curbing: bool,
@@ -149,20 +180,23 @@ pub struct Quote {
     pub price_low: QuotePrice,
     pub price_open: QuotePrice,
     pub price_avg: QuotePrice,
+    #[serde(serialize_with = "se_decimal")]
     pub change: Decimal,
+    #[serde(serialize_with = "se_decimal")]
     pub change_percent: Decimal,
+    #[serde(serialize_with = "se_decimal")]
     pub amplitude: Decimal,
     pub price_limit: u8,
 }
 
-#[derive(Default, Debug, Deserialize, Serialize)]
+#[derive(Default, Debug, Clone, Deserialize, Serialize)]
 #[serde(rename_all = "camelCase", default)]
 pub struct QuoteData {
     pub info: Info,
     pub quote: Quote,
 }
 
-#[derive(Default, Debug, Deserialize, Serialize)]
+#[derive(Default, Debug, Clone, Deserialize, Serialize)]
 #[serde(rename_all = "camelCase", default)]
 pub struct QuoteResponse {
     pub api_version: String,
@@ -176,7 +210,7 @@ mod test {
     #[test]
     fn test_quote_total_default() {
         let q = QuoteTotal::default();
-        assert_eq!(q.at, PrimitiveDateTime::MIN);
+        assert_eq!(q.at, MIN_DATE_TIME);
         assert_eq!(q.transaction, 0);
         assert_eq!(q.trade_value, Decimal::new(0, 2));
         assert_eq!(q.trade_volume, 0);
@@ -192,7 +226,7 @@ mod test {
     #[test]
     fn test_quote_trial_default() {
         let q = QuoteTrial::default();
-        assert_eq!(q.at, PrimitiveDateTime::MIN);
+        assert_eq!(q.at, MIN_DATE_TIME);
         assert_eq!(q.bid, Decimal::new(0, 2));
         assert_eq!(q.ask, Decimal::new(0, 2));
         assert_eq!(q.price, Decimal::new(0, 2));
@@ -202,7 +236,7 @@ mod test {
     #[test]
     fn test_quote_trade_default() {
         let q = QuoteTrade::default();
-        assert_eq!(q.at, PrimitiveDateTime::MIN);
+        assert_eq!(q.at, MIN_DATE_TIME);
         assert_eq!(q.bid, Decimal::new(0, 2));
         assert_eq!(q.ask, Decimal::new(0, 2));
         assert_eq!(q.price, Decimal::new(0, 2));
@@ -213,7 +247,7 @@ mod test {
     #[test]
     fn test_quote_order_default() {
         let q = QuoteOrder::default();
-        assert_eq!(q.at, PrimitiveDateTime::MIN);
+        assert_eq!(q.at, MIN_DATE_TIME);
         assert_eq!(q.bids.len(), 0);
         assert_eq!(q.asks.len(), 0);
     }
@@ -221,7 +255,7 @@ mod test {
     #[test]
     fn test_quote_price() {
         let q = QuotePrice::default();
-        assert_eq!(q.at, PrimitiveDateTime::MIN);
+        assert_eq!(q.at, MIN_DATE_TIME);
         assert_eq!(q.price, Decimal::new(0, 2));
     }
 }
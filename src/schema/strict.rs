@@ -0,0 +1,421 @@
+use std::cell::{Cell, RefCell};
+
+use serde::{
+    de::{self, value::StringDeserializer, DeserializeOwned, DeserializeSeed, IntoDeserializer},
+    forward_to_deserialize_any,
+};
+use serde_json::{Map, Value};
+
+use crate::{errors::FugleError, schema::Result};
+
+/// The fields of a response fugle did not send, which fell back to their defaults.
+#[derive(Default, Debug, Clone, PartialEq, Eq)]
+pub struct Report {
+    /// The path of every field left out, e.g. `data.quote.trade.price`.
+    pub defaulted: Vec<String>,
+    /// The defaulted fields fugle always sends, a strict parse fails on any of them.
+    pub required: Vec<String>,
+}
+
+impl Report {
+    /// Returns true if every field was sent.
+    pub fn is_complete(&self) -> bool {
+        self.defaulted.is_empty()
+    }
+}
+
+// the fields fugle leaves out when they are zero, false, or not happened yet,
+// by the response and the path of their struct, `[]` standing for any index.
+const OPTIONAL: &[(&str, &str, &[&str])] = &[
+    // the dealts do not carry the last updated time.
+    ("DealtsResponse", "data.info", &["lastUpdatedAt"]),
+    (
+        "QuoteResponse",
+        "data.quote",
+        &[
+            "isCurbing",
+            "isCurbingRise",
+            "isCurbingFall",
+            "isTrial",
+            "isOpenDelayed",
+            "isCloseDelayed",
+            "isHalting",
+            "isClosed",
+            "trial",
+            "trade",
+            "order",
+            "priceHigh",
+            "priceLow",
+            "priceOpen",
+            "priceAvg",
+            "change",
+            "changePercent",
+            "amplitude",
+            "priceLimit",
+        ],
+    ),
+    (
+        "QuoteResponse",
+        "data.quote.total",
+        &[
+            "transaction",
+            "tradeValue",
+            "tradeVolume",
+            "tradeVolumeAtBid",
+            "tradeVolumeAtAsk",
+            "bidOrders",
+            "askOrders",
+            "bidVolume",
+            "askVolume",
+            "serial",
+        ],
+    ),
+    ("QuoteResponse", "data.quote.order", &["bids", "asks"]),
+    // the odd lot meta leaves out what does not apply to odd lots.
+    (
+        "MetaResponse",
+        "data.meta",
+        &[
+            "industryZhTw",
+            "canShortMargin",
+            "canShortLend",
+            "currency",
+            "abnormal",
+            "isUnusuallyRecommended",
+            "typeZhTw",
+        ],
+    ),
+    (
+        "ChartResponse",
+        "data.chart",
+        &["o", "h", "l", "c", "v", "t"],
+    ),
+    // the candles carry only the fields requested.
+    (
+        "CandlesResponse",
+        "data[]",
+        &[
+            "open", "high", "low", "close", "volume", "turnover", "change",
+        ],
+    ),
+];
+
+fn is_optional(response: &str, path: &str, field: &str) -> bool {
+    let shape = unindexed(path);
+    OPTIONAL
+        .iter()
+        .any(|(r, p, fields)| *r == response && *p == shape && fields.contains(&field))
+}
+
+// the path without its indices, e.g. `data[]` of `data[3]`.
+fn unindexed(path: &str) -> String {
+    let mut shape = String::with_capacity(path.len());
+    let mut indexing = false;
+    for c in path.chars() {
+        match c {
+            '[' => {
+                indexing = true;
+                shape.push(c);
+            }
+            ']' => {
+                indexing = false;
+                shape.push(c);
+            }
+            _ if indexing => {}
+            _ => shape.push(c),
+        }
+    }
+    shape
+}
+
+// what a parse collects along the whole response.
+#[derive(Default)]
+struct Context {
+    report: RefCell<Report>,
+    // the name of the response struct, the first one deserialized.
+    response: Cell<&'static str>,
+}
+
+/// Parses a response, reporting the fields which fell back to their defaults.
+///
+/// # Example:
+///
+/// ```
+/// # fn main() -> fugle::schema::Result<()> {
+/// # use fugle::schema::{strict, VolumesResponse};
+/// let json = r#"{"apiVersion":"0.3.0","data":{"volumes":[{"price":26.5}]}}"#;
+/// let (_, report) = strict::from_str_reported::<VolumesResponse>(json)?;
+/// assert!(report.defaulted.contains(&"data.volumes[0].volume".to_string()));
+/// # Ok(())
+/// # }
+/// ```
+pub fn from_str_reported<T: DeserializeOwned>(json: &str) -> Result<(T, Report)> {
+    from_value_reported(serde_json::from_str(json)?)
+}
+
+/// Parses a response, failing with MissingFields error
+/// if a field fugle always sends fell back to its default.
+pub fn from_str_strict<T: DeserializeOwned>(json: &str) -> Result<T> {
+    from_value_strict(serde_json::from_str(json)?)
+}
+
+/// Same as [`from_str_reported`], from a parsed JSON value.
+pub fn from_value_reported<T: DeserializeOwned>(value: Value) -> Result<(T, Report)> {
+    let ctx = Context::default();
+    let data = T::deserialize(Tracked {
+        value,
+        path: String::new(),
+        ctx: &ctx,
+    })?;
+    Ok((data, ctx.report.into_inner()))
+}
+
+/// Same as [`from_str_strict`], from a parsed JSON value.
+pub fn from_value_strict<T: DeserializeOwned>(value: Value) -> Result<T> {
+    let (data, report) = from_value_reported(value)?;
+    if !report.required.is_empty() {
+        return Err(FugleError::MissingFields(report.required));
+    }
+    Ok(data)
+}
+
+fn join(path: &str, key: &str) -> String {
+    if path.is_empty() {
+        key.to_string()
+    } else {
+        format!("{}.{}", path, key)
+    }
+}
+
+// a JSON value deserializer telling the structs deserialized from it
+// which of their fields are not in the value.
+struct Tracked<'r> {
+    value: Value,
+    path: String,
+    ctx: &'r Context,
+}
+
+impl<'de, 'r> de::Deserializer<'de> for Tracked<'r> {
+    type Error = serde_json::Error;
+
+    fn deserialize_any<V: de::Visitor<'de>>(self, visitor: V) -> serde_json::Result<V::Value> {
+        match self.value {
+            Value::Object(map) => visitor.visit_map(MapAccess {
+                entries: map.into_iter(),
+                value: None,
+                path: self.path,
+                ctx: self.ctx,
+            }),
+            Value::Array(values) => visitor.visit_seq(SeqAccess {
+                values: values.into_iter().enumerate(),
+                path: self.path,
+                ctx: self.ctx,
+            }),
+            value => de::Deserializer::deserialize_any(value, visitor),
+        }
+    }
+
+    fn deserialize_struct<V: de::Visitor<'de>>(
+        self,
+        name: &'static str,
+        fields: &'static [&'static str],
+        visitor: V,
+    ) -> serde_json::Result<V::Value> {
+        if self.path.is_empty() && self.ctx.response.get().is_empty() {
+            self.ctx.response.set(name);
+        }
+        if let Value::Object(map) = &self.value {
+            report_missing(map, fields, &self.path, self.ctx);
+        }
+        self.deserialize_any(visitor)
+    }
+
+    fn deserialize_option<V: de::Visitor<'de>>(self, visitor: V) -> serde_json::Result<V::Value> {
+        match self.value {
+            Value::Null => visitor.visit_none(),
+            _ => visitor.visit_some(self),
+        }
+    }
+
+    fn deserialize_newtype_struct<V: de::Visitor<'de>>(
+        self,
+        _: &'static str,
+        visitor: V,
+    ) -> serde_json::Result<V::Value> {
+        visitor.visit_newtype_struct(self)
+    }
+
+    fn deserialize_enum<V: de::Visitor<'de>>(
+        self,
+        name: &'static str,
+        variants: &'static [&'static str],
+        visitor: V,
+    ) -> serde_json::Result<V::Value> {
+        de::Deserializer::deserialize_enum(self.value, name, variants, visitor)
+    }
+
+    forward_to_deserialize_any! {
+        bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char str string
+        bytes byte_buf unit unit_struct seq tuple tuple_struct map identifier ignored_any
+    }
+}
+
+fn report_missing(map: &Map<String, Value>, fields: &[&str], path: &str, ctx: &Context) {
+    let mut report = ctx.report.borrow_mut();
+    for field in fields.iter().filter(|f| !map.contains_key(**f)) {
+        if !is_optional(ctx.response.get(), path, field) {
+            report.required.push(join(path, field));
+        }
+        report.defaulted.push(join(path, field));
+    }
+}
+
+struct MapAccess<'r> {
+    entries: serde_json::map::IntoIter,
+    value: Option<(String, Value)>,
+    path: String,
+    ctx: &'r Context,
+}
+
+impl<'de, 'r> de::MapAccess<'de> for MapAccess<'r> {
+    type Error = serde_json::Error;
+
+    fn next_key_seed<K: DeserializeSeed<'de>>(
+        &mut self,
+        seed: K,
+    ) -> serde_json::Result<Option<K::Value>> {
+        match self.entries.next() {
+            Some((key, value)) => {
+                let deserializer: StringDeserializer<serde_json::Error> =
+                    key.clone().into_deserializer();
+                self.value = Some((key, value));
+                seed.deserialize(deserializer).map(Some)
+            }
+            None => Ok(None),
+        }
+    }
+
+    fn next_value_seed<V: DeserializeSeed<'de>>(
+        &mut self,
+        seed: V,
+    ) -> serde_json::Result<V::Value> {
+        let (key, value) = self
+            .value
+            .take()
+            .ok_or_else(|| de::Error::custom("value is missing"))?;
+        seed.deserialize(Tracked {
+            value,
+            path: join(&self.path, &key),
+            ctx: self.ctx,
+        })
+    }
+}
+
+struct SeqAccess<'r> {
+    values: std::iter::Enumerate<std::vec::IntoIter<Value>>,
+    path: String,
+    ctx: &'r Context,
+}
+
+impl<'de, 'r> de::SeqAccess<'de> for SeqAccess<'r> {
+    type Error = serde_json::Error;
+
+    fn next_element_seed<T: DeserializeSeed<'de>>(
+        &mut self,
+        seed: T,
+    ) -> serde_json::Result<Option<T::Value>> {
+        match self.values.next() {
+            Some((i, value)) => seed
+                .deserialize(Tracked {
+                    value,
+                    path: format!("{}[{}]", self.path, i),
+                    ctx: self.ctx,
+                })
+                .map(Some),
+            None => Ok(None),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::schema::{DealtsResponse, QuoteResponse};
+
+    #[test]
+    fn test_strict_report() {
+        let json = r#"{
+            "apiVersion": "0.3.0",
+            "data": {
+                "info": {"date": "2021-10-22", "symbolId": "2884"},
+                "dealts": [{"at": "2021-10-22T13:30:00.000+08:00", "price": 26.5, "serial": 1}]
+            }
+        }"#;
+        let (dealts, report) = from_str_reported::<DealtsResponse>(json).unwrap();
+        assert_eq!(dealts.data.dealts[0].serial, 1);
+        assert!(!report.is_complete());
+        assert!(report
+            .defaulted
+            .contains(&"data.info.lastUpdatedAt".to_string()));
+        assert!(report.defaulted.contains(&"data.info.market".to_string()));
+        assert!(report
+            .required
+            .contains(&"data.dealts[0].volume".to_string()));
+        assert!(!report
+            .required
+            .contains(&"data.info.lastUpdatedAt".to_string()));
+    }
+
+    #[test]
+    fn test_strict_missing_price() {
+        let json = |quote: &str| {
+            format!(
+                r#"{{"apiVersion": "0.3.0", "data": {{"quote": {}}}}}"#,
+                quote
+            )
+        };
+
+        // the quote of a symbol not traded yet has no trade.
+        let (_, report) = from_str_reported::<QuoteResponse>(&json("{}")).unwrap();
+        assert!(report.defaulted.contains(&"data.quote.trade".to_string()));
+        assert!(report.required.iter().all(|f| !f.contains("trade")));
+
+        let trade = r#"{"at": "2021-10-22T13:30:00.000+08:00", "bid": 26, "ask": 26.05, "volume": 1, "serial": 1}"#;
+        let quote = format!(r#"{{"trade": {}}}"#, trade);
+        match from_str_strict::<QuoteResponse>(&json(&quote)) {
+            Err(FugleError::MissingFields(fields)) => {
+                assert!(fields.contains(&"data.quote.trade.price".to_string()))
+            }
+            _ => unreachable!(),
+        }
+    }
+
+    #[test]
+    fn test_strict_exempt_by_response() {
+        // only the dealts leave out the last updated time.
+        let dealts = include_str!("../../tests/testdata/dealts_response.json");
+        assert!(from_str_strict::<DealtsResponse>(dealts).is_ok());
+
+        let quote = include_str!("../../tests/testdata/quote_response.json");
+        let mut value: Value = serde_json::from_str(quote).unwrap();
+        value["data"]["info"]
+            .as_object_mut()
+            .unwrap()
+            .remove("lastUpdatedAt");
+        match from_value_strict::<QuoteResponse>(value) {
+            Err(FugleError::MissingFields(fields)) => {
+                assert_eq!(fields, vec!["data.info.lastUpdatedAt".to_string()])
+            }
+            _ => unreachable!(),
+        }
+    }
+
+    #[test]
+    fn test_strict_unindexed() {
+        assert_eq!(unindexed("data[12].volume"), "data[].volume");
+        assert_eq!(
+            unindexed("data.quote.order.bids[0]"),
+            "data.quote.order.bids[]"
+        );
+    }
+}
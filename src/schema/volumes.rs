@@ -1,23 +1,24 @@
 use rust_decimal::Decimal;
 use serde::{Deserialize, Serialize};
 
-use crate::schema::Info;
+use crate::schema::{se_decimal, Info};
 
-#[derive(Default, Debug, Deserialize, Serialize)]
+#[derive(Default, Debug, Clone, Deserialize, Serialize)]
 #[serde(default, rename_all = "camelCase")]
 pub struct Volume {
+    #[serde(serialize_with = "se_decimal")]
     pub price: Decimal,
     pub volume: u64,
 }
 
-#[derive(Default, Debug, Deserialize, Serialize)]
+#[derive(Default, Debug, Clone, Deserialize, Serialize)]
 #[serde(default, rename_all = "camelCase")]
 pub struct VolumeData {
     pub info: Info,
     pub volumes: Vec<Volume>,
 }
 
-#[derive(Default, Debug, Deserialize, Serialize)]
+#[derive(Default, Debug, Clone, Deserialize, Serialize)]
 #[serde(default, rename_all = "camelCase")]
 pub struct VolumesResponse {
     pub api_version: String,
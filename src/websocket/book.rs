@@ -0,0 +1,256 @@
+//! Keeps the intraday bars of the chart endpoint.
+//!
+//! Every chart response carries the bars fugle has of the day, which may be a part of them
+//! after a reconnection, so the book merges the responses by their timestamps.
+
+use std::collections::{BTreeMap, HashMap};
+
+use rust_decimal::Decimal;
+use time::{Date, OffsetDateTime};
+
+use super::intraday::Symbol;
+use crate::schema::{chart::Chart, ChartResponse, Result};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Row {
+    open: Decimal,
+    high: Decimal,
+    low: Decimal,
+    close: Decimal,
+    volume: u64,
+}
+
+#[derive(Debug, Clone, Default)]
+struct Series {
+    date: Option<Date>,
+    rows: BTreeMap<OffsetDateTime, Row>,
+}
+
+/// The bar series of every symbol, built from successive chart responses.
+///
+/// A bar sent again replaces the one of the same timestamp, as the latest is the correct one,
+/// a response of a later trading day starts the series of that symbol over,
+/// and a late response of an earlier day is ignored.
+///
+/// # Example:
+///
+/// ```
+/// # fn main() -> fugle::schema::Result<()> {
+/// # use fugle::{schema::ChartResponse, websocket::ChartBook};
+/// let mut book = ChartBook::new();
+///
+/// // every response received from the chart endpoint.
+/// let response = ChartResponse::default();
+/// let symbol = book.ingest(&response)?;
+/// let chart = book.chart(&symbol).unwrap();
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct ChartBook {
+    series: HashMap<Symbol, Series>,
+}
+
+impl ChartBook {
+    pub fn new() -> ChartBook {
+        ChartBook::default()
+    }
+
+    /// Merges the bars of the response, returns the symbol they belong to.
+    ///
+    /// Returns RaggedChart error if the columns of the chart are not of the same length,
+    /// the book is left untouched then.
+    pub fn ingest(&mut self, response: &ChartResponse) -> Result<Symbol> {
+        let bars = response.data.chart.bars()?;
+        let info = &response.data.info;
+        let symbol = Symbol::from(info);
+        let series = self.series.entry(symbol.clone()).or_default();
+
+        // the default date means the response does not tell its day.
+        if info.date != Date::MIN {
+            match series.date {
+                Some(date) if info.date < date => return Ok(symbol),
+                Some(date) if info.date > date => series.rows.clear(),
+                _ => {}
+            }
+            series.date = Some(info.date);
+        }
+
+        for bar in bars {
+            series.rows.insert(
+                bar.at,
+                Row {
+                    open: bar.open,
+                    high: bar.high,
+                    low: bar.low,
+                    close: bar.close,
+                    volume: bar.volume,
+                },
+            );
+        }
+
+        Ok(symbol)
+    }
+
+    /// Returns the full bar series of the symbol in time order,
+    /// None if no response of it has been ingested.
+    pub fn chart(&self, symbol: &Symbol) -> Option<Chart> {
+        let series = self.series.get(symbol)?;
+        let mut chart = Chart::default();
+        for (&t, row) in series.rows.iter() {
+            chart.at.push(t);
+            chart.open.push(row.open);
+            chart.high.push(row.high);
+            chart.low.push(row.low);
+            chart.close.push(row.close);
+            chart.volume.push(row.volume);
+        }
+        Some(chart)
+    }
+
+    /// Returns the trading day of the series of the symbol, if any response has told it.
+    pub fn date(&self, symbol: &Symbol) -> Option<Date> {
+        self.series.get(symbol)?.date
+    }
+
+    /// Returns the symbols ingested so far.
+    pub fn symbols(&self) -> impl Iterator<Item = &Symbol> {
+        self.series.keys()
+    }
+
+    /// Drops the series of the symbol.
+    pub fn remove(&mut self, symbol: &Symbol) {
+        self.series.remove(symbol);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use time::Month;
+
+    use super::*;
+    use crate::{errors::FugleError, schema::SecurityType};
+
+    fn day(day: u8) -> Date {
+        Date::from_calendar_date(2022, Month::August, day).unwrap()
+    }
+
+    fn at(seconds: i64) -> OffsetDateTime {
+        OffsetDateTime::from_unix_timestamp(seconds).unwrap()
+    }
+
+    fn response(symbol: &str, day: Date, bars: &[(i64, i64, u64)]) -> ChartResponse {
+        let mut response = ChartResponse::default();
+        response.data.info.symbol_id = symbol.to_string();
+        response.data.info.typ = SecurityType::Equity;
+        response.data.info.date = day;
+        for &(t, price, volume) in bars {
+            let price = Decimal::from(price);
+            let chart = &mut response.data.chart;
+            chart.at.push(at(t));
+            chart.open.push(price);
+            chart.high.push(price);
+            chart.low.push(price);
+            chart.close.push(price);
+            chart.volume.push(volume);
+        }
+        response
+    }
+
+    #[test]
+    fn test_chart_book_merge() {
+        let today = day(1);
+        let mut book = ChartBook::new();
+        assert!(book.chart(&Symbol::new("2884")).is_none());
+
+        let symbol = book
+            .ingest(&response("2884", today, &[(60, 10, 1), (120, 11, 2)]))
+            .unwrap();
+        assert_eq!(symbol, Symbol::new("2884"));
+        // a partial update after a reconnection, out of order,
+        // repeating a bar and correcting another one.
+        book.ingest(&response(
+            "2884",
+            today,
+            &[(180, 12, 3), (60, 10, 1), (120, 13, 5)],
+        ))
+        .unwrap();
+
+        let chart = book.chart(&symbol).unwrap();
+        assert_eq!(chart.at, vec![at(60), at(120), at(180)]);
+        assert_eq!(
+            chart.close,
+            vec![Decimal::from(10), Decimal::from(13), Decimal::from(12)]
+        );
+        assert_eq!(chart.volume, vec![1, 5, 3]);
+        assert_eq!(book.date(&symbol), Some(today));
+    }
+
+    #[test]
+    fn test_chart_book_symbols() {
+        let today = day(1);
+        let mut book = ChartBook::new();
+        book.ingest(&response("2884", today, &[(60, 10, 1)]))
+            .unwrap();
+        let mut odd_lot = response("2884", today, &[(60, 20, 7)]);
+        odd_lot.data.info.typ = SecurityType::OddLot;
+        book.ingest(&odd_lot).unwrap();
+
+        assert_eq!(book.symbols().count(), 2);
+        let chart = book.chart(&Symbol::odd_lot("2884")).unwrap();
+        assert_eq!(chart.volume, vec![7]);
+
+        book.remove(&Symbol::odd_lot("2884"));
+        assert!(book.chart(&Symbol::odd_lot("2884")).is_none());
+        assert_eq!(book.chart(&Symbol::new("2884")).unwrap().volume, vec![1]);
+    }
+
+    #[test]
+    fn test_chart_book_new_day() {
+        let mut book = ChartBook::new();
+        let symbol = book
+            .ingest(&response("2884", day(1), &[(60, 10, 1)]))
+            .unwrap();
+        book.ingest(&response("2884", day(2), &[(86460, 11, 2)]))
+            .unwrap();
+        assert_eq!(book.chart(&symbol).unwrap().at, vec![at(86460)]);
+        assert_eq!(book.date(&symbol), Some(day(2)));
+    }
+
+    #[test]
+    fn test_chart_book_earlier_day() {
+        let mut book = ChartBook::new();
+        let symbol = book
+            .ingest(&response("2884", day(2), &[(86460, 11, 2)]))
+            .unwrap();
+        // a late response of the day before.
+        book.ingest(&response("2884", day(1), &[(60, 10, 1)]))
+            .unwrap();
+        assert_eq!(book.chart(&symbol).unwrap().at, vec![at(86460)]);
+        assert_eq!(book.date(&symbol), Some(day(2)));
+    }
+
+    #[test]
+    fn test_chart_book_ragged() {
+        let mut ragged = response("2884", day(2), &[(60, 10, 1), (120, 11, 2)]);
+        ragged.data.chart.volume.pop();
+
+        let mut book = ChartBook::new();
+        assert!(matches!(
+            book.ingest(&ragged),
+            Err(FugleError::RaggedChart {
+                column: "v",
+                len: 1,
+                expected: 2
+            })
+        ));
+        assert_eq!(book.symbols().count(), 0);
+
+        let symbol = book
+            .ingest(&response("2884", day(1), &[(60, 10, 1)]))
+            .unwrap();
+        book.ingest(&ragged).unwrap_err();
+        assert_eq!(book.chart(&symbol).unwrap().at, vec![at(60)]);
+        assert_eq!(book.date(&symbol), Some(day(1)));
+    }
+}
@@ -0,0 +1,507 @@
+//! Bounded channels used by the websocket listeners.
+//!
+//! Unlike the unbounded channels, a bounded channel holds at most `capacity` messages,
+//! and what happens on a full channel is decided by its [`Overflow`] policy.
+
+use std::{
+    cell::RefCell,
+    collections::VecDeque,
+    sync::{
+        atomic::{AtomicBool, AtomicU64, Ordering},
+        mpsc::{RecvError, RecvTimeoutError, TryRecvError},
+        Arc, Condvar, Mutex, MutexGuard,
+    },
+    time::{Duration, Instant},
+};
+
+use super::intraday::Symbol;
+
+// how long a send waits on a full channel before checking the done flag of its worker again.
+const WAIT_SLICE: Duration = Duration::from_millis(100);
+
+thread_local! {
+    // the done flag of the worker delivering on this thread.
+    static DONE: RefCell<Option<Arc<AtomicBool>>> = const { RefCell::new(None) };
+}
+
+/// Runs the delivery of a worker,
+/// a send waiting on a full channel in it gives up once the done flag is set,
+/// so a consumer not receiving cannot hold the worker from stopping.
+#[cfg_attr(not(feature = "websocket"), allow(dead_code))]
+pub(crate) fn delivering<R>(done: &Arc<AtomicBool>, f: impl FnOnce() -> R) -> R {
+    let outer = DONE.with(|d| d.replace(Some(done.clone())));
+    let ret = f();
+    DONE.with(|d| *d.borrow_mut() = outer);
+    ret
+}
+
+fn is_done() -> bool {
+    DONE.with(|d| {
+        d.borrow()
+            .as_ref()
+            .is_some_and(|done| done.load(Ordering::SeqCst))
+    })
+}
+
+/// What a bounded channel does when a message comes while it is full.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Overflow {
+    /// The websocket reader waits until the consumer takes a message,
+    /// a reader asked to stop meanwhile drops the incoming message.
+    Block,
+    /// The oldest message in the channel is dropped.
+    DropOldest,
+    /// The incoming message is dropped.
+    DropNewest,
+    /// Only the latest message of each symbol is kept,
+    /// an incoming message replaces the pending one of the same symbol in place,
+    /// and the oldest message is dropped if a new symbol comes while full.
+    Conflate,
+}
+
+struct State<T> {
+    items: VecDeque<(Symbol, T)>,
+    senders: usize,
+    receiving: bool,
+}
+
+struct Shared<T> {
+    state: Mutex<State<T>>,
+    capacity: usize,
+    overflow: Overflow,
+    readable: Condvar,
+    writable: Condvar,
+    #[cfg(feature = "async-websocket")]
+    async_readable: tokio::sync::Notify,
+    #[cfg(feature = "async-websocket")]
+    async_writable: tokio::sync::Notify,
+    dropped: AtomicU64,
+}
+
+enum Push<T> {
+    Done,
+    Full(T),
+    Closed,
+}
+
+impl<T> Shared<T> {
+    fn lock(&self) -> MutexGuard<'_, State<T>> {
+        self.state.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    fn drop_one(&self) {
+        self.dropped.fetch_add(1, Ordering::Relaxed);
+    }
+
+    fn try_push(&self, state: &mut State<T>, symbol: &Symbol, msg: T) -> Push<T> {
+        if !state.receiving {
+            return Push::Closed;
+        }
+
+        match self.overflow {
+            Overflow::Block => {
+                if state.items.len() >= self.capacity {
+                    return Push::Full(msg);
+                }
+            }
+            Overflow::DropOldest => {
+                if state.items.len() >= self.capacity {
+                    state.items.pop_front();
+                    self.drop_one();
+                }
+            }
+            Overflow::DropNewest => {
+                if state.items.len() >= self.capacity {
+                    self.drop_one();
+                    return Push::Done;
+                }
+            }
+            Overflow::Conflate => {
+                if let Some(item) = state.items.iter_mut().find(|(s, _)| s == symbol) {
+                    item.1 = msg;
+                    self.drop_one();
+                    return Push::Done;
+                }
+                if state.items.len() >= self.capacity {
+                    state.items.pop_front();
+                    self.drop_one();
+                }
+            }
+        }
+
+        state.items.push_back((symbol.clone(), msg));
+        self.readable.notify_one();
+        #[cfg(feature = "async-websocket")]
+        self.async_readable.notify_one();
+        Push::Done
+    }
+
+    fn try_pop(&self, state: &mut State<T>) -> Option<T> {
+        let (_, msg) = state.items.pop_front()?;
+        self.writable.notify_all();
+        #[cfg(feature = "async-websocket")]
+        self.async_writable.notify_waiters();
+        Some(msg)
+    }
+}
+
+/// Creates a bounded channel holding at most `capacity` messages.
+pub(crate) fn bounded<T>(capacity: usize, overflow: Overflow) -> (Sender<T>, Receiver<T>) {
+    // the items grow on demand, a large capacity is only an upper bound.
+    let shared = Arc::new(Shared {
+        state: Mutex::new(State {
+            items: VecDeque::new(),
+            senders: 1,
+            receiving: true,
+        }),
+        capacity: capacity.max(1),
+        overflow,
+        readable: Condvar::new(),
+        writable: Condvar::new(),
+        #[cfg(feature = "async-websocket")]
+        async_readable: tokio::sync::Notify::new(),
+        #[cfg(feature = "async-websocket")]
+        async_writable: tokio::sync::Notify::new(),
+        dropped: AtomicU64::new(0),
+    });
+
+    (
+        Sender {
+            shared: shared.clone(),
+        },
+        Receiver { shared },
+    )
+}
+
+/// The sending half of a bounded channel, owned by the websocket workers.
+pub(crate) struct Sender<T> {
+    shared: Arc<Shared<T>>,
+}
+
+impl<T> Sender<T> {
+    #[cfg_attr(not(feature = "websocket"), allow(dead_code))]
+    pub(crate) fn overflow(&self) -> Overflow {
+        self.shared.overflow
+    }
+
+    /// Sends a message of the symbol, waiting on a full channel with the Block policy
+    /// until the worker delivering is done, see [`delivering`].
+    ///
+    /// Returns false if the receiver has been dropped or the message has been given up.
+    #[cfg_attr(not(feature = "websocket"), allow(dead_code))]
+    pub(crate) fn send(&self, symbol: &Symbol, mut msg: T) -> bool {
+        let mut state = self.shared.lock();
+        loop {
+            match self.shared.try_push(&mut state, symbol, msg) {
+                Push::Done => return true,
+                Push::Closed => return false,
+                Push::Full(_) if is_done() => {
+                    self.shared.drop_one();
+                    return false;
+                }
+                Push::Full(m) => {
+                    msg = m;
+                    state = self
+                        .shared
+                        .writable
+                        .wait_timeout(state, WAIT_SLICE)
+                        .unwrap_or_else(|e| e.into_inner())
+                        .0;
+                }
+            }
+        }
+    }
+
+    /// Sends a message of the symbol, waiting on a full channel with the Block policy.
+    ///
+    /// Returns false if the receiver has been dropped.
+    #[cfg(feature = "async-websocket")]
+    pub(crate) async fn async_send(&self, symbol: &Symbol, mut msg: T) -> bool {
+        loop {
+            let notified = self.shared.async_writable.notified();
+            tokio::pin!(notified);
+            // registers before checking, a pop in between won't be missed.
+            notified.as_mut().enable();
+
+            match self.shared.try_push(&mut self.shared.lock(), symbol, msg) {
+                Push::Done => return true,
+                Push::Closed => return false,
+                Push::Full(m) => msg = m,
+            }
+            notified.await;
+        }
+    }
+}
+
+impl<T> Sender<T> {
+    /// Returns whether the receiver has been dropped.
+    #[cfg_attr(not(feature = "websocket"), allow(dead_code))]
+    pub(crate) fn is_closed(&self) -> bool {
+        !self.shared.lock().receiving
+    }
+}
+
+impl<T> Clone for Sender<T> {
+    fn clone(&self) -> Sender<T> {
+        self.shared.lock().senders += 1;
+        Sender {
+            shared: self.shared.clone(),
+        }
+    }
+}
+
+impl<T> Drop for Sender<T> {
+    fn drop(&mut self) {
+        let mut state = self.shared.lock();
+        state.senders -= 1;
+        if state.senders == 0 {
+            self.shared.readable.notify_all();
+            #[cfg(feature = "async-websocket")]
+            self.shared.async_readable.notify_one();
+        }
+    }
+}
+
+/// The receiving half of a bounded channel.
+///
+/// Its methods follow `std::sync::mpsc::Receiver`,
+/// and an error is returned once the Intraday and all of its workers are gone.
+pub struct Receiver<T> {
+    shared: Arc<Shared<T>>,
+}
+
+impl<T> Receiver<T> {
+    /// Blocks until a message comes.
+    pub fn recv(&self) -> std::result::Result<T, RecvError> {
+        let mut state = self.shared.lock();
+        loop {
+            if let Some(msg) = self.shared.try_pop(&mut state) {
+                return Ok(msg);
+            }
+            if state.senders == 0 {
+                return Err(RecvError);
+            }
+            state = self
+                .shared
+                .readable
+                .wait(state)
+                .unwrap_or_else(|e| e.into_inner());
+        }
+    }
+
+    /// Returns a message if there is one without blocking.
+    pub fn try_recv(&self) -> std::result::Result<T, TryRecvError> {
+        let mut state = self.shared.lock();
+        match self.shared.try_pop(&mut state) {
+            Some(msg) => Ok(msg),
+            None if state.senders == 0 => Err(TryRecvError::Disconnected),
+            None => Err(TryRecvError::Empty),
+        }
+    }
+
+    /// Blocks until a message comes or the timeout elapses.
+    pub fn recv_timeout(&self, timeout: Duration) -> std::result::Result<T, RecvTimeoutError> {
+        let deadline = Instant::now() + timeout;
+        let mut state = self.shared.lock();
+        loop {
+            if let Some(msg) = self.shared.try_pop(&mut state) {
+                return Ok(msg);
+            }
+            if state.senders == 0 {
+                return Err(RecvTimeoutError::Disconnected);
+            }
+            let now = Instant::now();
+            if now >= deadline {
+                return Err(RecvTimeoutError::Timeout);
+            }
+            state = self
+                .shared
+                .readable
+                .wait_timeout(state, deadline - now)
+                .unwrap_or_else(|e| e.into_inner())
+                .0;
+        }
+    }
+
+    /// Waits until a message comes.
+    #[cfg(feature = "async-websocket")]
+    pub async fn async_recv(&self) -> std::result::Result<T, RecvError> {
+        loop {
+            let notified = self.shared.async_readable.notified();
+            {
+                let mut state = self.shared.lock();
+                if let Some(msg) = self.shared.try_pop(&mut state) {
+                    return Ok(msg);
+                }
+                if state.senders == 0 {
+                    return Err(RecvError);
+                }
+            }
+            notified.await;
+        }
+    }
+
+    /// Returns the number of messages waiting in the channel.
+    pub fn len(&self) -> usize {
+        self.shared.lock().items.len()
+    }
+
+    /// Returns true if no message is waiting in the channel.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Returns the capacity of the channel.
+    pub fn capacity(&self) -> usize {
+        self.shared.capacity
+    }
+
+    /// Returns the overflow policy of the channel.
+    pub fn overflow(&self) -> Overflow {
+        self.shared.overflow
+    }
+
+    /// Returns how many messages have been dropped or conflated by the overflow policy.
+    pub fn dropped(&self) -> u64 {
+        self.shared.dropped.load(Ordering::Relaxed)
+    }
+}
+
+impl<T> Drop for Receiver<T> {
+    fn drop(&mut self) {
+        let mut state = self.shared.lock();
+        state.receiving = false;
+        state.items.clear();
+        self.shared.writable.notify_all();
+        #[cfg(feature = "async-websocket")]
+        self.shared.async_writable.notify_waiters();
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::thread;
+
+    use super::*;
+
+    #[test]
+    fn test_bounded_block() {
+        let (tx, rx) = bounded(1, Overflow::Block);
+        let symbol = Symbol::new("2884");
+
+        assert!(tx.send(&symbol, 1));
+        let sending = {
+            let symbol = symbol.clone();
+            thread::spawn(move || tx.send(&symbol, 2))
+        };
+
+        thread::sleep(Duration::from_millis(20));
+        assert_eq!(rx.len(), 1);
+        assert_eq!(rx.recv().unwrap(), 1);
+        assert!(sending.join().unwrap());
+        assert_eq!(rx.recv().unwrap(), 2);
+        assert_eq!(rx.dropped(), 0);
+        assert_eq!(rx.recv(), Err(RecvError));
+    }
+
+    #[test]
+    fn test_bounded_drop_oldest() {
+        let (tx, rx) = bounded(2, Overflow::DropOldest);
+        let symbol = Symbol::new("2884");
+
+        for i in 1..=4 {
+            assert!(tx.send(&symbol, i));
+        }
+
+        assert_eq!(rx.dropped(), 2);
+        assert_eq!(rx.try_recv(), Ok(3));
+        assert_eq!(rx.try_recv(), Ok(4));
+        assert_eq!(rx.try_recv(), Err(TryRecvError::Empty));
+    }
+
+    #[test]
+    fn test_bounded_drop_newest() {
+        let (tx, rx) = bounded(2, Overflow::DropNewest);
+        let symbol = Symbol::new("2884");
+
+        for i in 1..=4 {
+            assert!(tx.send(&symbol, i));
+        }
+
+        assert_eq!(rx.dropped(), 2);
+        assert_eq!(rx.try_recv(), Ok(1));
+        assert_eq!(rx.try_recv(), Ok(2));
+        drop(tx);
+        assert_eq!(rx.try_recv(), Err(TryRecvError::Disconnected));
+    }
+
+    #[test]
+    fn test_bounded_conflate() {
+        let (tx, rx) = bounded(2, Overflow::Conflate);
+        let (a, b, c) = (
+            Symbol::new("2884"),
+            Symbol::new("2330"),
+            Symbol::odd_lot("2330"),
+        );
+
+        assert!(tx.send(&a, 1));
+        assert!(tx.send(&b, 2));
+        assert!(tx.send(&a, 3));
+        assert_eq!(rx.dropped(), 1);
+        assert!(tx.send(&c, 4));
+        assert_eq!(rx.dropped(), 2);
+
+        assert_eq!(rx.try_recv(), Ok(2));
+        assert_eq!(rx.try_recv(), Ok(4));
+        assert_eq!(
+            rx.recv_timeout(Duration::from_millis(1)),
+            Err(RecvTimeoutError::Timeout)
+        );
+    }
+
+    #[test]
+    fn test_bounded_receiver_dropped() {
+        let (tx, rx) = bounded(1, Overflow::Block);
+        let symbol = Symbol::new("2884");
+
+        assert!(tx.send(&symbol, 1));
+        drop(rx);
+        assert!(!tx.send(&symbol, 2));
+    }
+
+    #[test]
+    fn test_bounded_block_done() {
+        let (tx, rx) = bounded(1, Overflow::Block);
+        let symbol = Symbol::new("2884");
+        let done = Arc::new(AtomicBool::new(false));
+
+        assert!(tx.send(&symbol, 1));
+        let sending = {
+            let done = done.clone();
+            thread::spawn(move || delivering(&done, || tx.send(&symbol, 2)))
+        };
+
+        // the worker stopping gives up the message instead of waiting for the consumer.
+        thread::sleep(Duration::from_millis(20));
+        done.store(true, Ordering::SeqCst);
+        assert!(!sending.join().unwrap());
+        assert_eq!(rx.dropped(), 1);
+        assert_eq!(rx.try_recv(), Ok(1));
+        assert_eq!(rx.try_recv(), Err(TryRecvError::Disconnected));
+    }
+
+    #[cfg(feature = "async-websocket")]
+    #[tokio::test]
+    async fn test_bounded_async_block() {
+        let (tx, rx) = bounded(1, Overflow::Block);
+        let symbol = Symbol::new("2884");
+
+        assert!(tx.async_send(&symbol, 1).await);
+        let sending = tokio::spawn(async move { tx.async_send(&symbol, 2).await });
+
+        assert_eq!(rx.async_recv().await, Ok(1));
+        assert!(sending.await.unwrap());
+        assert_eq!(rx.async_recv().await, Ok(2));
+        assert_eq!(rx.async_recv().await, Err(RecvError));
+    }
+}
@@ -0,0 +1,107 @@
+//! Fan-out of one websocket feed to many blocking consumers.
+//!
+//! Every consumer owns a bounded channel, so a slow consumer only lags on its own,
+//! how it lags is decided by the [`Overflow`] policy it subscribed with.
+
+use std::sync::{Arc, Mutex, MutexGuard};
+
+use super::{
+    bounded::{self, Overflow, Receiver, Sender},
+    intraday::Symbol,
+};
+
+/// A cloneable handle of one websocket feed,
+/// every subscribed consumer receives a copy of each response.
+///
+/// The receivers are disconnected once the Intraday and every clone of the handle are gone.
+pub struct Broadcast<T> {
+    consumers: Arc<Mutex<Vec<Sender<T>>>>,
+}
+
+impl<T> Broadcast<T> {
+    pub(crate) fn new() -> Self {
+        Broadcast {
+            consumers: Arc::new(Mutex::new(vec![])),
+        }
+    }
+
+    fn lock(&self) -> MutexGuard<'_, Vec<Sender<T>>> {
+        self.consumers.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Adds a consumer which receives the responses coming after this call,
+    /// at most `capacity` of them are held for it.
+    ///
+    /// With the Block policy a full consumer holds the websocket reader back,
+    /// so the other consumers wait for it too.
+    pub fn subscribe(&self, capacity: usize, overflow: Overflow) -> Receiver<T> {
+        let (tx, rx) = bounded::bounded(capacity, overflow);
+        self.lock().push(tx);
+        rx
+    }
+
+    /// Returns how many consumers are still receiving.
+    pub fn receiver_count(&self) -> usize {
+        self.lock().len()
+    }
+}
+
+impl<T: Clone> Broadcast<T> {
+    // hands a copy of the message to every consumer and forgets the dropped ones.
+    pub(crate) fn send(&self, symbol: &Symbol, msg: T) {
+        // sends outside the lock, a blocking consumer must not hold back subscribing.
+        let consumers = self.lock().clone();
+        let mut closed = false;
+        for tx in consumers.iter() {
+            closed |= !tx.send(symbol, msg.clone());
+        }
+
+        if closed {
+            self.lock().retain(|tx| !tx.is_closed());
+        }
+    }
+}
+
+impl<T> Clone for Broadcast<T> {
+    fn clone(&self) -> Self {
+        Broadcast {
+            consumers: self.consumers.clone(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::sync::mpsc::TryRecvError;
+
+    use super::*;
+
+    #[test]
+    fn test_broadcast_consumers() {
+        let broadcast = Broadcast::new();
+        let symbol = Symbol::new("2884");
+
+        let fast = broadcast.subscribe(4, Overflow::DropOldest);
+        let slow = broadcast.clone().subscribe(1, Overflow::DropOldest);
+        assert_eq!(broadcast.receiver_count(), 2);
+
+        for i in 1..=3 {
+            broadcast.send(&symbol, i);
+        }
+
+        assert_eq!(fast.try_recv(), Ok(1));
+        assert_eq!(fast.try_recv(), Ok(2));
+        assert_eq!(fast.try_recv(), Ok(3));
+        assert_eq!(fast.dropped(), 0);
+        assert_eq!(slow.try_recv(), Ok(3));
+        assert_eq!(slow.dropped(), 2);
+
+        drop(slow);
+        broadcast.send(&symbol, 4);
+        assert_eq!(broadcast.receiver_count(), 1);
+        assert_eq!(fast.try_recv(), Ok(4));
+
+        drop(broadcast);
+        assert_eq!(fast.try_recv(), Err(TryRecvError::Disconnected));
+    }
+}
@@ -1,86 +1,287 @@
-use std::sync::{
-    atomic::{AtomicBool, Ordering},
-    Arc,
-};
+use std::{future::Future, panic::AssertUnwindSafe, sync::Arc, time::Instant};
 
-use futures_util::StreamExt;
+use futures_util::{
+    future::{abortable, AbortHandle},
+    FutureExt, StreamExt,
+};
 use log::error;
-use tokio::sync::mpsc::UnboundedSender;
-use tokio_tungstenite::connect_async;
+use tokio::sync::{oneshot, watch};
 
-use crate::schema::Result;
+#[cfg(feature = "async-query")]
+use super::{
+    budget::Permit,
+    fallback::{falls_back, Fallback},
+    feed::Snapshot,
+};
+use super::{
+    connect::{Connector, Stream},
+    decode, is_transient,
+    record::Tap,
+    AsyncSink, ConnectionEvent, Symbol, Worker, WorkerExit,
+};
+#[cfg(feature = "async-query")]
+use crate::websocket::runtime::Rest;
+use crate::{schema::Result, websocket::runtime::timeout};
 
 pub(crate) struct Async {
-    pub(crate) routine: Option<tokio::task::JoinHandle<()>>,
+    // reports how the task exited, the task may run on any executor.
+    exited: Option<oneshot::Receiver<WorkerExit>>,
+    abort: AbortHandle,
+    done: watch::Sender<bool>,
 }
 
 impl Async {
     pub(crate) async fn new<T>(
+        connector: &Connector,
         uri: &str,
-        sender: UnboundedSender<T>,
-        done: Arc<AtomicBool>,
+        symbol: Symbol,
+        sink: Arc<dyn AsyncSink<T>>,
+        tap: Tap,
     ) -> Result<Async>
     where
         T: for<'de> serde::Deserialize<'de> + Send + 'static,
     {
-        let (mut socket, _) = connect_async(uri).await?;
-
-        let routine = tokio::spawn(async move {
-            while !done.load(Ordering::SeqCst) {
-                if let Some(Ok(msg)) = socket.next().await {
-                    if let Ok(m) = msg.to_text() {
-                        if let Ok(m) = serde_json::from_str(m) {
-                            if let Err(e) = sender.send(m) {
-                                error!("{}", e);
-                            }
-                        }
-                    }
+        let permit = connector.async_acquire().await?;
+        let mut socket = connector.async_connect(uri).await?;
+        let (done, mut is_done) = watch::channel(false);
+
+        let routine = async move {
+            listen(&mut socket, &symbol, sink.as_ref(), &tap, &mut is_done).await;
+            drop(permit);
+        };
+
+        Ok(Async::spawn(connector, routine, done))
+    }
+
+    /// Spawns like `new`, but polls the REST endpoint instead
+    /// whenever the websocket cannot connect or has been closed,
+    /// and tries the websocket again after every reconnect period of polling.
+    ///
+    /// The first connection is tried at once, failing up front unless it can fall back.
+    #[cfg(feature = "async-query")]
+    pub(crate) async fn fallback<T>(
+        connector: &Connector,
+        uri: &str,
+        symbol: Symbol,
+        sink: Arc<dyn AsyncSink<T>>,
+        tap: Tap,
+        fallback: Fallback,
+        rest: Rest,
+    ) -> Result<Async>
+    where
+        T: for<'de> serde::Deserialize<'de> + Snapshot + Send + 'static,
+    {
+        let mut connected = match connect(connector, uri).await {
+            Ok(connected) => Some(connected),
+            Err(e) if falls_back(&e) => {
+                error!("polling {} over REST instead: {}", symbol.id, e);
+                sink.fail(&symbol, e).await;
+                None
+            }
+            Err(e) => return Err(e),
+        };
+
+        let (done, mut is_done) = watch::channel(false);
+        let conn = connector.clone();
+        let uri = uri.to_string();
+
+        let routine = async move {
+            let mut last = None;
+            let mut polling = connected.is_none();
+            while !*is_done.borrow() {
+                if let Some((permit, mut socket)) = connected.take() {
+                    polling = false;
+                    listen(&mut socket, &symbol, sink.as_ref(), &tap, &mut is_done).await;
+                    drop(permit);
                 }
+                let polled = fallback
+                    .async_poll(&rest, &symbol, sink.as_ref(), &mut is_done, &mut last)
+                    .await;
+                if !polled {
+                    break;
+                }
+                connected = match connect(&conn, &uri).await {
+                    Ok(connected) => Some(connected),
+                    // reports only the failure starting a polling run.
+                    Err(e) if !polling => {
+                        error!("polling {} over REST instead: {}", symbol.id, e);
+                        sink.fail(&symbol, e).await;
+                        polling = true;
+                        None
+                    }
+                    Err(_) => None,
+                };
+            }
+        };
+
+        Ok(Async::spawn(connector, routine, done))
+    }
+
+    fn spawn<F>(connector: &Connector, routine: F, done: watch::Sender<bool>) -> Async
+    where
+        F: Future<Output = ()> + Send + 'static,
+    {
+        let (routine, abort) = abortable(routine);
+        let (exited_tx, exited) = oneshot::channel();
+        connector.spawner.spawn(Box::pin(async move {
+            let exit = match AssertUnwindSafe(routine).catch_unwind().await {
+                Ok(Ok(_)) => WorkerExit::Clean,
+                Ok(Err(_)) => WorkerExit::Aborted,
+                Err(_) => WorkerExit::Panicked,
+            };
+            let _ = exited_tx.send(exit);
+        }));
+
+        Async {
+            exited: Some(exited),
+            abort,
+            done,
+        }
+    }
+
+    /// Waits the worker task until it stops by itself.
+    pub(crate) async fn wait(&mut self) -> WorkerExit {
+        match self.exited.take() {
+            // the executor dropped the task before it exited.
+            Some(exited) => exited.await.unwrap_or(WorkerExit::Aborted),
+            None => WorkerExit::Clean,
+        }
+    }
+
+    /// Waits the worker task until the deadline,
+    /// a task which is still running after the deadline will be aborted.
+    pub(crate) async fn join(&mut self, deadline: Instant) -> WorkerExit {
+        let mut exited = match self.exited.take() {
+            Some(exited) => exited,
+            None => return WorkerExit::Clean,
+        };
+
+        let remain = deadline.saturating_duration_since(Instant::now());
+        match timeout(remain, &mut exited).await {
+            Some(exit) => exit.unwrap_or(WorkerExit::Aborted),
+            None => {
+                self.abort.abort();
+                WorkerExit::Aborted
             }
-            let _ = socket.close(None).await;
-        });
+        }
+    }
+}
+
+impl Worker for Async {
+    fn signal(&self) {
+        let _ = self.done.send(true);
+    }
+}
 
-        Ok(Async {
-            routine: Some(routine),
-        })
+impl Drop for Async {
+    fn drop(&mut self) {
+        self.signal();
     }
 }
 
-impl super::Worker for Async {
-    fn stop(&mut self) {
-        if let Some(routine) = self.routine.take() {
-            drop(routine)
+// takes a connection of the budget and opens it.
+#[cfg(feature = "async-query")]
+async fn connect(connector: &Connector, uri: &str) -> Result<(Permit, Stream)> {
+    let permit = connector.async_acquire().await?;
+    let socket = connector.async_connect(uri).await?;
+    Ok((permit, socket))
+}
+
+// reads the socket until it is closed, broken or the worker is done,
+// then sends the close frame to let the server release the connection.
+async fn listen<T>(
+    socket: &mut Stream,
+    symbol: &Symbol,
+    sink: &dyn AsyncSink<T>,
+    tap: &Tap,
+    is_done: &mut watch::Receiver<bool>,
+) where
+    T: for<'de> serde::Deserialize<'de> + Send + 'static,
+{
+    sink.connection(symbol, ConnectionEvent::Connected).await;
+    loop {
+        tokio::select! {
+            _ = is_done.changed() => break,
+            msg = socket.next() => match msg {
+                Some(Ok(msg)) => {
+                    tap.frame(symbol, &msg);
+                    match decode(&msg) {
+                        Some(Ok(m)) => {
+                            let sent = sink.send(symbol, m).await;
+                            if !sent {
+                                error!("sending on a closed channel");
+                            }
+                        }
+                        Some(Err(e)) => {
+                            error!("{}", e);
+                            sink.fail(symbol, e).await;
+                        }
+                        None => {}
+                    }
+                }
+                Some(Err(e)) => {
+                    error!("{}", e);
+                    let transient = is_transient(&e);
+                    sink.fail(symbol, e.into()).await;
+                    if !transient {
+                        break;
+                    }
+                }
+                None => break,
+            },
         }
     }
+    let _ = socket.close(None).await;
+    sink.connection(symbol, ConnectionEvent::Disconnected).await;
 }
 
 #[cfg(test)]
 mod test {
-    use tokio::{
-        sync::mpsc::unbounded_channel,
-        time::{sleep, Duration},
-    };
+    use std::time::Duration;
+
+    use tokio::sync::mpsc::unbounded_channel;
 
     use super::{
-        super::{QuoteResponse, Worker, INTRADAY_QUOTE},
+        super::{testing, QuoteResponse},
         *,
     };
 
     #[tokio::test]
     async fn test_async_worker_stop() {
-        let (tx, _) = unbounded_channel::<QuoteResponse>();
-        let done = Arc::new(AtomicBool::new(false));
-        let mut worker = Async::new(
-            &format!("{}?symbolId=2884&apiToken=demo", INTRADAY_QUOTE),
-            tx,
-            done.clone(),
-        )
-        .await
-        .unwrap();
-
-        done.store(true, Ordering::SeqCst);
-        sleep(Duration::from_millis(3)).await;
-
-        worker.stop();
+        let (uri, closed) = testing::async_silent_server().await;
+        let (tx, _rx) = unbounded_channel::<QuoteResponse>();
+        let mut worker = testing::async_worker(&uri, Arc::new(tx)).await;
+
+        worker.signal();
+        let exit = worker.join(Instant::now() + Duration::from_secs(3)).await;
+
+        assert_eq!(exit, WorkerExit::Clean);
+        assert!(closed.await.unwrap());
+    }
+
+    #[tokio::test]
+    async fn test_async_worker_broken() {
+        let (uri, _closed) = testing::async_server(vec![testing::broken()], false).await;
+        let (tx, _rx) = unbounded_channel::<QuoteResponse>();
+        let mut worker = testing::async_worker(&uri, Arc::new(tx)).await;
+
+        // exits by itself instead of reading the broken socket forever.
+        let exit = worker.join(Instant::now() + Duration::from_secs(3)).await;
+        assert_eq!(exit, WorkerExit::Clean);
+    }
+
+    #[tokio::test]
+    async fn test_async_worker_abort() {
+        let (uri, closed) = testing::async_silent_server().await;
+        let (tx, _rx) = unbounded_channel::<QuoteResponse>();
+        let mut worker = testing::async_worker(&uri, Arc::new(tx)).await;
+
+        let exit = worker
+            .join(Instant::now() + Duration::from_millis(10))
+            .await;
+
+        assert_eq!(exit, WorkerExit::Aborted);
+        // an aborted worker drops the socket without the close frame.
+        assert!(!closed.await.unwrap());
     }
 }
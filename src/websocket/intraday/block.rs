@@ -1,80 +1,423 @@
 use std::{
+    io::ErrorKind,
+    net::TcpStream,
     sync::{
         atomic::{AtomicBool, Ordering},
-        mpsc::Sender,
-        Arc,
+        mpsc, Arc, Mutex,
     },
     thread,
+    time::{Duration, Instant},
 };
 
 use log::error;
-use tungstenite::connect;
+use polling::Poller;
+use tungstenite::{stream::MaybeTlsStream, Error, WebSocket};
 
-use crate::schema::Result;
+use super::{
+    budget::Permit, connect::Connector, decode, is_transient, mux::Mux, record::Tap, BlockSink,
+    ConnectionEvent, Symbol, Worker, WorkerExit,
+};
+#[cfg(feature = "query")]
+use super::{
+    fallback::{falls_back, Fallback},
+    feed::Snapshot,
+};
+#[cfg(feature = "query")]
+use crate::http::BlockRequest;
+use crate::{schema::Result, websocket::bounded};
+
+// how long a read may block before the worker checks its done flag again,
+// this bounds how fast a worker reacts on shutdown.
+const READ_TIMEOUT: Duration = Duration::from_millis(100);
+
+// how long sending the close frame may block.
+const CLOSE_TIMEOUT: Duration = Duration::from_secs(1);
+
+type Socket = WebSocket<MaybeTlsStream<TcpStream>>;
+
+// what one read of a connection ended with.
+pub(crate) enum Read {
+    Message,
+    // nothing to read for now.
+    Idle,
+    // a reported error the connection goes on after.
+    Failed,
+    // closed by the server or broken beyond reading on.
+    Closed,
+}
+
+// a connected socket together with where its responses go.
+struct Conn<T> {
+    socket: Socket,
+    symbol: Symbol,
+    sink: Arc<dyn BlockSink<T>>,
+    tap: Tap,
+    // given back once the connection is closed.
+    _permit: Permit,
+}
+
+/// The connections of every response type read by the same loop.
+pub(crate) trait Connection: Send {
+    fn stream(&self) -> &TcpStream;
+    fn connected(&self);
+    fn read(&mut self) -> Read;
+    /// Sends the close frame to let the server release the connection.
+    fn close(self: Box<Self>);
+}
+
+impl<T> Connection for Conn<T>
+where
+    T: for<'de> serde::Deserialize<'de> + Send + 'static,
+{
+    fn stream(&self) -> &TcpStream {
+        match self.socket.get_ref() {
+            MaybeTlsStream::Rustls(s) => &s.sock,
+            MaybeTlsStream::Plain(s) => s,
+            _ => unreachable!("the connector only makes plain or rustls streams"),
+        }
+    }
+
+    fn connected(&self) {
+        self.sink
+            .connection(&self.symbol, ConnectionEvent::Connected);
+    }
+
+    fn read(&mut self) -> Read {
+        match self.socket.read_message() {
+            Ok(msg) => {
+                self.tap.frame(&self.symbol, &msg);
+                match decode(&msg) {
+                    Some(Ok(m)) => {
+                        let sent = self.sink.send(&self.symbol, m);
+                        if !sent {
+                            error!("sending on a closed channel");
+                        }
+                    }
+                    Some(Err(e)) => {
+                        error!("{}", e);
+                        self.sink.fail(&self.symbol, e);
+                    }
+                    None => {}
+                }
+                Read::Message
+            }
+            Err(Error::Io(ref e))
+                if matches!(
+                    e.kind(),
+                    ErrorKind::WouldBlock | ErrorKind::TimedOut | ErrorKind::Interrupted
+                ) =>
+            {
+                Read::Idle
+            }
+            Err(Error::ConnectionClosed) | Err(Error::AlreadyClosed) => Read::Closed,
+            Err(e) => {
+                error!("{}", e);
+                let transient = is_transient(&e);
+                self.sink.fail(&self.symbol, e.into());
+                if transient {
+                    Read::Failed
+                } else {
+                    Read::Closed
+                }
+            }
+        }
+    }
+
+    fn close(mut self: Box<Self>) {
+        let stream = self.stream();
+        let _ = stream.set_nonblocking(false);
+        let _ = stream.set_write_timeout(Some(CLOSE_TIMEOUT));
+        let _ = self.socket.close(None);
+        let _ = self.socket.write_pending();
+        self.sink
+            .connection(&self.symbol, ConnectionEvent::Disconnected);
+    }
+}
+
+// how the end of a worker is waited.
+enum Exit {
+    Thread(thread::JoinHandle<()>),
+    // the multiplexer reports on it once the connection is closed,
+    // it is locked only to keep the Intraday Sync.
+    Muxed(Mutex<mpsc::Receiver<()>>),
+}
 
 pub(crate) struct Block {
-    pub(crate) thread: Option<thread::JoinHandle<()>>,
+    exit: Option<Exit>,
+    done: Arc<AtomicBool>,
+    // wakes the multiplexer up to see the done flag.
+    waker: Option<Arc<Poller>>,
 }
 
 impl Block {
-    pub(crate) fn new<T>(uri: &str, sender: Sender<T>, done: Arc<AtomicBool>) -> Result<Block>
+    fn connect<T>(
+        connector: &Connector,
+        uri: &str,
+        symbol: Symbol,
+        sink: Arc<dyn BlockSink<T>>,
+        tap: Tap,
+    ) -> Result<Conn<T>> {
+        let permit = connector.acquire()?;
+        let socket = connector.connect(uri)?;
+        Ok(Conn {
+            socket,
+            symbol,
+            sink,
+            tap,
+            _permit: permit,
+        })
+    }
+
+    /// Listens on a thread of its own.
+    pub(crate) fn new<T>(
+        connector: &Connector,
+        uri: &str,
+        symbol: Symbol,
+        sink: Arc<dyn BlockSink<T>>,
+        tap: Tap,
+    ) -> Result<Block>
     where
         T: for<'de> serde::Deserialize<'de> + Send + 'static,
     {
-        let (mut socket, _) = connect(uri)?;
+        let mut conn = Block::connect(connector, uri, symbol, sink, tap)?;
+        conn.stream().set_read_timeout(Some(READ_TIMEOUT))?;
+
+        let done = Arc::new(AtomicBool::new(false));
+        let is_done = done.clone();
 
         let thread = thread::spawn(move || {
-            while !done.load(Ordering::SeqCst) {
-                if let Ok(msg) = socket.read_message() {
-                    if let Ok(m) = msg.to_text() {
-                        if let Ok(m) = serde_json::from_str(m) {
-                            if let Err(e) = sender.send(m) {
-                                error!("{}", e);
+            bounded::delivering(&is_done, || {
+                conn.connected();
+                while !is_done.load(Ordering::SeqCst) {
+                    if let Read::Closed = conn.read() {
+                        break;
+                    }
+                }
+                Box::new(conn).close();
+            })
+        });
+
+        Ok(Block {
+            exit: Some(Exit::Thread(thread)),
+            done,
+            waker: None,
+        })
+    }
+
+    /// Listens on a thread of its own like `new`, but polls the REST endpoint instead
+    /// whenever the websocket cannot connect or has been closed,
+    /// and tries the websocket again after every reconnect period of polling.
+    ///
+    /// The first connection is tried at once, failing up front unless it can fall back.
+    #[cfg(feature = "query")]
+    pub(crate) fn fallback<T>(
+        connector: &Connector,
+        uri: &str,
+        symbol: Symbol,
+        sink: Arc<dyn BlockSink<T>>,
+        tap: Tap,
+        fallback: Fallback,
+        client: BlockRequest<'static>,
+    ) -> Result<Block>
+    where
+        T: for<'de> serde::Deserialize<'de> + Snapshot + Send + 'static,
+    {
+        let mut connected =
+            match Block::connect(connector, uri, symbol.clone(), sink.clone(), tap.clone()) {
+                Ok(conn) => Some(conn),
+                Err(e) if falls_back(&e) => {
+                    error!("polling {} over REST instead: {}", symbol.id, e);
+                    sink.fail(&symbol, e);
+                    None
+                }
+                Err(e) => return Err(e),
+            };
+
+        let connector = connector.clone();
+        let uri = uri.to_string();
+        let done = Arc::new(AtomicBool::new(false));
+        let is_done = done.clone();
+
+        let thread = thread::spawn(move || {
+            bounded::delivering(&is_done, || {
+                let mut last = None;
+                let mut polling = connected.is_none();
+                while !is_done.load(Ordering::SeqCst) {
+                    if let Some(mut conn) = connected.take() {
+                        polling = false;
+                        if let Err(e) = conn.stream().set_read_timeout(Some(READ_TIMEOUT)) {
+                            error!("{}", e);
+                        }
+                        conn.connected();
+                        while !is_done.load(Ordering::SeqCst) {
+                            if let Read::Closed = conn.read() {
+                                break;
                             }
                         }
+                        Box::new(conn).close();
+                    }
+                    if !fallback.poll(&client, &symbol, sink.as_ref(), &is_done, &mut last) {
+                        break;
                     }
+                    connected = match Block::connect(
+                        &connector,
+                        &uri,
+                        symbol.clone(),
+                        sink.clone(),
+                        tap.clone(),
+                    ) {
+                        Ok(conn) => Some(conn),
+                        // reports only the failure starting a polling run.
+                        Err(e) if !polling => {
+                            error!("polling {} over REST instead: {}", symbol.id, e);
+                            sink.fail(&symbol, e);
+                            polling = true;
+                            None
+                        }
+                        Err(_) => None,
+                    };
                 }
-            }
-            let _ = socket.close(None);
+            })
         });
 
         Ok(Block {
-            thread: Some(thread),
+            exit: Some(Exit::Thread(thread)),
+            done,
+            waker: None,
         })
     }
+
+    /// Listens on the thread of the multiplexer together with the other connections.
+    pub(crate) fn muxed<T>(
+        mux: &Mux,
+        connector: &Connector,
+        uri: &str,
+        symbol: Symbol,
+        sink: Arc<dyn BlockSink<T>>,
+        tap: Tap,
+    ) -> Result<Block>
+    where
+        T: for<'de> serde::Deserialize<'de> + Send + 'static,
+    {
+        let conn = Block::connect(connector, uri, symbol, sink, tap)?;
+        conn.stream().set_nonblocking(true)?;
+
+        let done = Arc::new(AtomicBool::new(false));
+        let exited = mux.register(Box::new(conn), done.clone())?;
+
+        Ok(Block {
+            exit: Some(Exit::Muxed(Mutex::new(exited))),
+            done,
+            waker: Some(mux.waker()),
+        })
+    }
+
+    /// Waits the worker until it stops by itself.
+    pub(crate) fn wait(&mut self) -> WorkerExit {
+        match self.exit.take() {
+            Some(Exit::Thread(thread)) => match thread.join() {
+                Ok(_) => WorkerExit::Clean,
+                Err(_) => WorkerExit::Panicked,
+            },
+            Some(Exit::Muxed(exited)) => match into_inner(exited).recv() {
+                Ok(_) => WorkerExit::Clean,
+                // the multiplexer is gone without closing the connection.
+                Err(_) => WorkerExit::Panicked,
+            },
+            None => WorkerExit::Clean,
+        }
+    }
+
+    /// Waits the worker until the deadline,
+    /// a thread which is still running after the deadline will be detached.
+    pub(crate) fn join(&mut self, deadline: Instant) -> WorkerExit {
+        let thread = match self.exit.take() {
+            Some(Exit::Thread(thread)) => thread,
+            Some(Exit::Muxed(exited)) => {
+                let remain = deadline.saturating_duration_since(Instant::now());
+                return match into_inner(exited).recv_timeout(remain) {
+                    Ok(_) => WorkerExit::Clean,
+                    Err(mpsc::RecvTimeoutError::Timeout) => WorkerExit::TimedOut,
+                    Err(mpsc::RecvTimeoutError::Disconnected) => WorkerExit::Panicked,
+                };
+            }
+            None => return WorkerExit::Clean,
+        };
+
+        while !thread.is_finished() {
+            if Instant::now() >= deadline {
+                return WorkerExit::TimedOut;
+            }
+            thread::sleep(Duration::from_millis(1));
+        }
+
+        match thread.join() {
+            Ok(_) => WorkerExit::Clean,
+            Err(_) => WorkerExit::Panicked,
+        }
+    }
 }
 
-impl super::Worker for Block {
-    fn stop(&mut self) {
-        if let Some(thread) = self.thread.take() {
-            let _ = thread.join();
+fn into_inner<T>(lock: Mutex<T>) -> T {
+    lock.into_inner().unwrap_or_else(|e| e.into_inner())
+}
+
+impl Worker for Block {
+    fn signal(&self) {
+        self.done.store(true, Ordering::SeqCst);
+        if let Some(ref waker) = self.waker {
+            let _ = waker.notify();
         }
     }
 }
 
+impl Drop for Block {
+    fn drop(&mut self) {
+        self.signal();
+    }
+}
+
 #[cfg(test)]
 mod test {
-    use std::{sync::mpsc::channel, thread::sleep, time::Duration};
+    use std::sync::mpsc::channel;
 
     use super::{
-        super::{QuoteResponse, Worker, INTRADAY_QUOTE},
+        super::{testing, QuoteResponse},
         *,
     };
 
     #[test]
     fn test_block_worker_stop() {
-        let (tx, _) = channel::<QuoteResponse>();
-        let done = Arc::new(AtomicBool::new(false));
-        let mut worker = Block::new(
-            &format!("{}?symbolId=2884&apiToken=demo", INTRADAY_QUOTE),
-            tx,
-            done.clone(),
-        )
-        .unwrap();
+        let (uri, closed) = testing::silent_server();
+        let (tx, _rx) = channel::<QuoteResponse>();
+        let mut worker = testing::worker(&uri, Arc::new(tx));
+
+        worker.signal();
+        let exit = worker.join(Instant::now() + Duration::from_secs(3));
+
+        assert_eq!(exit, WorkerExit::Clean);
+        assert!(closed.recv_timeout(Duration::from_secs(3)).unwrap());
+    }
 
-        done.store(true, Ordering::SeqCst);
-        sleep(Duration::from_millis(3));
+    #[test]
+    fn test_block_worker_broken() {
+        let (uri, closed) = testing::server(1, |_| vec![testing::broken()], false);
+        let (tx, _rx) = channel::<QuoteResponse>();
+        let mut worker = testing::worker(&uri, Arc::new(tx));
+
+        // exits by itself instead of reading the broken socket forever.
+        let exit = worker.join(Instant::now() + Duration::from_secs(3));
+        assert_eq!(exit, WorkerExit::Clean);
+        assert!(closed.recv_timeout(Duration::from_secs(3)).is_ok());
+    }
+
+    #[test]
+    fn test_block_worker_join_timeout() {
+        let (uri, _closed) = testing::silent_server();
+        let (tx, _rx) = channel::<QuoteResponse>();
+        let mut worker = testing::worker(&uri, Arc::new(tx));
 
-        worker.stop();
+        let exit = worker.join(Instant::now() + Duration::from_millis(10));
+        assert_eq!(exit, WorkerExit::TimedOut);
     }
 }
@@ -0,0 +1,239 @@
+use std::{
+    collections::BTreeMap,
+    sync::{Arc, Condvar, Mutex, MutexGuard},
+    time::{Duration, Instant},
+};
+
+#[cfg(feature = "async-websocket")]
+use crate::websocket::runtime::timeout;
+use crate::{errors::FugleError, schema::Result};
+
+// the budgets of every token used in the process,
+// so the Intraday instances sharing a token share its connections.
+static BUDGETS: Mutex<BTreeMap<String, Arc<Budget>>> = Mutex::new(BTreeMap::new());
+
+/// How many websocket connections of a token are open and how many are allowed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ConnectionUsage {
+    pub in_use: usize,
+    /// None means the connections are not limited.
+    pub limit: Option<usize>,
+}
+
+impl ConnectionUsage {
+    /// Returns how many more connections can be opened, None if not limited.
+    pub fn available(&self) -> Option<usize> {
+        self.limit.map(|limit| limit.saturating_sub(self.in_use))
+    }
+}
+
+#[derive(Debug, Default)]
+struct State {
+    limit: Option<usize>,
+    in_use: usize,
+}
+
+// counts the open connections of a token against its limit.
+#[derive(Debug, Default)]
+pub(crate) struct Budget {
+    state: Mutex<State>,
+    freed: Condvar,
+    #[cfg(feature = "async-websocket")]
+    async_freed: tokio::sync::Notify,
+}
+
+impl Budget {
+    /// Returns the budget shared by every Intraday of the token.
+    pub(crate) fn of(token: &str) -> Arc<Budget> {
+        let mut budgets = BUDGETS.lock().unwrap_or_else(|e| e.into_inner());
+        budgets.entry(token.to_string()).or_default().clone()
+    }
+
+    fn lock(&self) -> MutexGuard<'_, State> {
+        self.state.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    pub(crate) fn set_limit(&self, limit: usize) {
+        self.lock().limit = Some(limit);
+        // a raised limit lets the queued connections go.
+        self.notify();
+    }
+
+    pub(crate) fn usage(&self) -> ConnectionUsage {
+        let state = self.lock();
+        ConnectionUsage {
+            in_use: state.in_use,
+            limit: state.limit,
+        }
+    }
+
+    fn notify(&self) {
+        self.freed.notify_all();
+        #[cfg(feature = "async-websocket")]
+        self.async_freed.notify_waiters();
+    }
+
+    fn take(self: &Arc<Self>, state: &mut State) -> Option<Permit> {
+        if matches!(state.limit, Some(limit) if state.in_use >= limit) {
+            return None;
+        }
+        state.in_use += 1;
+        Some(Permit(self.clone()))
+    }
+
+    fn exceeded(state: &State) -> FugleError {
+        FugleError::ConnectionLimitExceeded(state.limit.unwrap_or_default())
+    }
+
+    /// Takes one connection of the budget,
+    /// waits no longer than the queue timeout for one to be freed if all are in use.
+    #[cfg(feature = "websocket")]
+    pub(crate) fn acquire(self: &Arc<Self>, queue: Option<Duration>) -> Result<Permit> {
+        let deadline = Instant::now() + queue.unwrap_or_default();
+        let mut state = self.lock();
+        loop {
+            if let Some(permit) = self.take(&mut state) {
+                return Ok(permit);
+            }
+            let remaining = deadline.saturating_duration_since(Instant::now());
+            if remaining.is_zero() {
+                return Err(Budget::exceeded(&state));
+            }
+            state = self
+                .freed
+                .wait_timeout(state, remaining)
+                .unwrap_or_else(|e| e.into_inner())
+                .0;
+        }
+    }
+
+    #[cfg(feature = "async-websocket")]
+    pub(crate) async fn async_acquire(self: &Arc<Self>, queue: Option<Duration>) -> Result<Permit> {
+        let deadline = Instant::now() + queue.unwrap_or_default();
+        loop {
+            // registers the waiting before checking, not to miss a connection freed in between.
+            let freed = self.async_freed.notified();
+            tokio::pin!(freed);
+            freed.as_mut().enable();
+
+            if let Some(permit) = self.take(&mut self.lock()) {
+                return Ok(permit);
+            }
+            let remaining = deadline.saturating_duration_since(Instant::now());
+            if remaining.is_zero() || timeout(remaining, freed).await.is_none() {
+                return Err(Budget::exceeded(&self.lock()));
+            }
+        }
+    }
+}
+
+// one open connection of a budget, given back on drop.
+#[derive(Debug)]
+pub(crate) struct Permit(Arc<Budget>);
+
+impl Drop for Permit {
+    fn drop(&mut self) {
+        self.0.lock().in_use -= 1;
+        self.0.notify();
+    }
+}
+
+#[cfg(test)]
+mod test {
+    #[cfg(feature = "websocket")]
+    use std::thread;
+
+    use super::*;
+
+    fn limited(limit: usize) -> Arc<Budget> {
+        let budget = Arc::new(Budget::default());
+        budget.set_limit(limit);
+        budget
+    }
+
+    #[test]
+    fn test_budget_shared_by_token() {
+        let a = Budget::of("test_budget_shared_by_token");
+        let b = Budget::of("test_budget_shared_by_token");
+        assert!(Arc::ptr_eq(&a, &b));
+        assert!(!Arc::ptr_eq(&a, &Budget::of("another token")));
+    }
+
+    #[cfg(feature = "websocket")]
+    #[test]
+    fn test_budget_reject() {
+        let budget = limited(2);
+        let first = budget.acquire(None).unwrap();
+        let _second = budget.acquire(None).unwrap();
+        assert_eq!(
+            budget.usage(),
+            ConnectionUsage {
+                in_use: 2,
+                limit: Some(2)
+            }
+        );
+        assert_eq!(budget.usage().available(), Some(0));
+        assert!(matches!(
+            budget.acquire(None),
+            Err(FugleError::ConnectionLimitExceeded(2))
+        ));
+
+        drop(first);
+        assert_eq!(budget.usage().in_use, 1);
+        assert!(budget.acquire(None).is_ok());
+    }
+
+    #[cfg(feature = "websocket")]
+    #[test]
+    fn test_budget_unlimited() {
+        let budget = Arc::new(Budget::default());
+        let permits: Vec<Permit> = (0..100).map(|_| budget.acquire(None).unwrap()).collect();
+        assert_eq!(budget.usage().in_use, 100);
+        assert_eq!(budget.usage().available(), None);
+        drop(permits);
+        assert_eq!(budget.usage().in_use, 0);
+    }
+
+    #[cfg(feature = "websocket")]
+    #[test]
+    fn test_budget_queue() {
+        let budget = limited(1);
+        let permit = budget.acquire(None).unwrap();
+
+        let releaser = thread::spawn(move || {
+            thread::sleep(Duration::from_millis(50));
+            drop(permit);
+        });
+        assert!(budget.acquire(Some(Duration::from_secs(3))).is_ok());
+        releaser.join().unwrap();
+
+        let _permit = budget.acquire(None).unwrap();
+        assert!(matches!(
+            budget.acquire(Some(Duration::from_millis(20))),
+            Err(FugleError::ConnectionLimitExceeded(1))
+        ));
+    }
+
+    #[cfg(feature = "async-websocket")]
+    #[tokio::test]
+    async fn test_budget_async_queue() {
+        let budget = limited(1);
+        let permit = budget.async_acquire(None).await.unwrap();
+
+        tokio::spawn(async move {
+            tokio::time::sleep(Duration::from_millis(50)).await;
+            drop(permit);
+        });
+        let permit = budget
+            .async_acquire(Some(Duration::from_secs(3)))
+            .await
+            .unwrap();
+
+        assert!(matches!(
+            budget.async_acquire(Some(Duration::from_millis(20))).await,
+            Err(FugleError::ConnectionLimitExceeded(1))
+        ));
+        drop(permit);
+        assert_eq!(budget.usage().in_use, 0);
+    }
+}
@@ -0,0 +1,336 @@
+use std::{
+    io::{self, ErrorKind},
+    sync::Arc,
+    time::Duration,
+};
+
+use rustls::{ClientConfig, OwnedTrustAnchor, RootCertStore};
+use tungstenite::{
+    client::IntoClientRequest,
+    handshake::client::Request,
+    http::{HeaderName, HeaderValue},
+};
+
+use super::budget::{Budget, Permit};
+use crate::schema::Result;
+
+// the longest response head a proxy may answer the CONNECT request with.
+const MAX_PROXY_RESPONSE: usize = 8 * 1024;
+
+/// How the workers connect to the fugle websocket endpoints.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct Connector {
+    /// The HTTP CONNECT proxy in "host:port".
+    pub(crate) proxy: Option<String>,
+    /// The PEM encoded root certificates trusted besides the webpki ones.
+    pub(crate) root_certificates: Vec<Vec<u8>>,
+    pub(crate) connect_timeout: Option<Duration>,
+    pub(crate) headers: Vec<(String, String)>,
+    /// The connections of the token, unlimited unless the Intraday sets it up.
+    pub(crate) budget: Arc<Budget>,
+    /// How long to wait for a free connection of the budget, None rejects at once.
+    pub(crate) queue: Option<Duration>,
+    /// What the async workers are spawned by.
+    #[cfg(feature = "async-websocket")]
+    pub(crate) spawner: crate::websocket::runtime::Spawner,
+}
+
+impl Connector {
+    fn request(&self, uri: &str) -> Result<Request> {
+        let mut request = uri.into_client_request()?;
+        for (name, value) in self.headers.iter() {
+            let name = HeaderName::from_bytes(name.as_bytes()).map_err(tungstenite::Error::from)?;
+            let value = HeaderValue::from_str(value).map_err(tungstenite::Error::from)?;
+            request.headers_mut().append(name, value);
+        }
+        Ok(request)
+    }
+
+    // None lets tungstenite build its default TLS config.
+    fn tls(&self) -> Result<Option<Arc<ClientConfig>>> {
+        if self.root_certificates.is_empty() {
+            return Ok(None);
+        }
+
+        let mut roots = RootCertStore::empty();
+        roots.add_server_trust_anchors(webpki_roots::TLS_SERVER_ROOTS.0.iter().map(|ta| {
+            OwnedTrustAnchor::from_subject_spki_name_constraints(
+                ta.subject,
+                ta.spki,
+                ta.name_constraints,
+            )
+        }));
+        for pem in self.root_certificates.iter() {
+            let certs = rustls_pemfile::certs(&mut pem.as_slice())?;
+            if certs.is_empty() {
+                return Err(invalid_data("no certificate found in the PEM").into());
+            }
+            for der in certs {
+                roots
+                    .add(&rustls::Certificate(der))
+                    .map_err(|e| invalid_data(&e.to_string()))?;
+            }
+        }
+
+        Ok(Some(Arc::new(
+            ClientConfig::builder()
+                .with_safe_defaults()
+                .with_root_certificates(roots)
+                .with_no_client_auth(),
+        )))
+    }
+
+    // where the TCP connection goes, the proxy or the endpoint itself.
+    fn address(&self, request: &Request) -> Result<String> {
+        match self.proxy {
+            Some(ref proxy) => Ok(proxy.clone()),
+            None => target(request),
+        }
+    }
+}
+
+fn invalid_data(msg: &str) -> io::Error {
+    io::Error::new(ErrorKind::InvalidData, msg.to_string())
+}
+
+fn timed_out() -> io::Error {
+    io::Error::new(ErrorKind::TimedOut, "connecting timed out")
+}
+
+// returns the "host:port" of the endpoint.
+fn target(request: &Request) -> Result<String> {
+    let uri = request.uri();
+    let host = uri.host().ok_or(tungstenite::Error::Url(
+        tungstenite::error::UrlError::NoHostName,
+    ))?;
+    let port = match uri.port_u16() {
+        Some(port) => port,
+        None if uri.scheme_str() == Some("wss") => 443,
+        None => 80,
+    };
+    Ok(format!("{}:{}", host, port))
+}
+
+fn connect_request(target: &str) -> String {
+    format!(
+        "CONNECT {} HTTP/1.1\r\nHost: {}\r\nProxy-Connection: Keep-Alive\r\n\r\n",
+        target, target
+    )
+}
+
+// checks the response head of a CONNECT request, returns whether it has ended.
+fn proxy_response(head: &[u8]) -> Result<bool> {
+    if !head.ends_with(b"\r\n\r\n") {
+        if head.len() >= MAX_PROXY_RESPONSE {
+            return Err(invalid_data("proxy response is too long").into());
+        }
+        return Ok(false);
+    }
+
+    let status = head.split(|&b| b == b' ').nth(1).unwrap_or_default();
+    if status != b"200" {
+        let line = head.split(|&b| b == b'\r').next().unwrap_or_default();
+        return Err(io::Error::new(
+            ErrorKind::ConnectionRefused,
+            format!("proxy refused: {}", String::from_utf8_lossy(line)),
+        )
+        .into());
+    }
+    Ok(true)
+}
+
+#[cfg(feature = "websocket")]
+mod block {
+    use std::{
+        io::{ErrorKind, Read, Write},
+        net::{TcpStream, ToSocketAddrs},
+    };
+
+    use tungstenite::{
+        client_tls_with_config, handshake::HandshakeError, stream::MaybeTlsStream, WebSocket,
+    };
+
+    use super::*;
+    use crate::errors::FugleError;
+
+    impl Connector {
+        pub(crate) fn acquire(&self) -> Result<Permit> {
+            self.budget.acquire(self.queue)
+        }
+
+        pub(crate) fn connect(&self, uri: &str) -> Result<WebSocket<MaybeTlsStream<TcpStream>>> {
+            let request = self.request(uri)?;
+            let mut stream = self.tcp(&self.address(&request)?)?;
+            stream.set_read_timeout(self.connect_timeout)?;
+            stream.set_write_timeout(self.connect_timeout)?;
+
+            if self.proxy.is_some() {
+                tunnel(&mut stream, &target(&request)?)?;
+            }
+
+            let connector = self.tls()?.map(tungstenite::Connector::Rustls);
+            let (socket, _) =
+                client_tls_with_config(request, stream, None, connector).map_err(|e| match e {
+                    HandshakeError::Failure(e) => FugleError::from(e),
+                    HandshakeError::Interrupted(_) => timed_out().into(),
+                })?;
+
+            match socket.get_ref() {
+                MaybeTlsStream::Plain(s) => s.set_write_timeout(None)?,
+                MaybeTlsStream::Rustls(s) => s.sock.set_write_timeout(None)?,
+                _ => {}
+            }
+            Ok(socket)
+        }
+
+        fn tcp(&self, address: &str) -> Result<TcpStream> {
+            let timeout = match self.connect_timeout {
+                Some(timeout) => timeout,
+                None => return Ok(TcpStream::connect(address)?),
+            };
+
+            let mut last = io::Error::new(ErrorKind::NotFound, "no address resolved");
+            for addr in address.to_socket_addrs()? {
+                match TcpStream::connect_timeout(&addr, timeout) {
+                    Ok(stream) => return Ok(stream),
+                    Err(e) => last = e,
+                }
+            }
+            Err(last.into())
+        }
+    }
+
+    fn tunnel(stream: &mut TcpStream, target: &str) -> Result<()> {
+        stream.write_all(connect_request(target).as_bytes())?;
+
+        // reads byte by byte to leave the tunneled bytes in the stream.
+        let mut head = Vec::with_capacity(128);
+        let mut byte = [0u8; 1];
+        loop {
+            if stream.read(&mut byte)? == 0 {
+                return Err(io::Error::from(ErrorKind::UnexpectedEof).into());
+            }
+            head.push(byte[0]);
+            if proxy_response(&head)? {
+                return Ok(());
+            }
+        }
+    }
+}
+
+#[cfg(feature = "async-websocket")]
+pub(crate) use r#async::Stream;
+
+#[cfg(feature = "async-websocket")]
+mod r#async {
+    use async_net::TcpStream;
+    use futures_util::{AsyncReadExt, AsyncWriteExt};
+    use tokio_tungstenite::{client_async_tls_with_config, MaybeTlsStream, WebSocketStream};
+    use tokio_util::compat::{Compat, FuturesAsyncReadCompatExt};
+
+    use super::*;
+    use crate::websocket::runtime::timeout;
+
+    // the IO is driven by async-io instead of the runtime the worker is spawned on.
+    pub(crate) type Stream = WebSocketStream<MaybeTlsStream<Compat<TcpStream>>>;
+
+    impl Connector {
+        pub(crate) async fn async_acquire(&self) -> Result<Permit> {
+            self.budget.async_acquire(self.queue).await
+        }
+
+        pub(crate) async fn async_connect(&self, uri: &str) -> Result<Stream> {
+            match self.connect_timeout {
+                Some(duration) => timeout(duration, self.handshake(uri))
+                    .await
+                    .ok_or_else(timed_out)?,
+                None => self.handshake(uri).await,
+            }
+        }
+
+        async fn handshake(&self, uri: &str) -> Result<Stream> {
+            let request = self.request(uri)?;
+            let mut stream = TcpStream::connect(self.address(&request)?).await?;
+
+            if self.proxy.is_some() {
+                tunnel(&mut stream, &target(&request)?).await?;
+            }
+
+            let connector = self.tls()?.map(tokio_tungstenite::Connector::Rustls);
+            let (socket, _) =
+                client_async_tls_with_config(request, stream.compat(), None, connector).await?;
+            Ok(socket)
+        }
+    }
+
+    async fn tunnel(stream: &mut TcpStream, target: &str) -> Result<()> {
+        stream.write_all(connect_request(target).as_bytes()).await?;
+
+        let mut head = Vec::with_capacity(128);
+        let mut byte = [0u8; 1];
+        loop {
+            stream.read_exact(&mut byte).await?;
+            head.push(byte[0]);
+            if proxy_response(&head)? {
+                return Ok(());
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_connector_target() {
+        let connector = Connector::default();
+        let request = connector.request("wss://api.fugle.tw/realtime").unwrap();
+        assert_eq!(target(&request).unwrap(), "api.fugle.tw:443");
+        assert_eq!(connector.address(&request).unwrap(), "api.fugle.tw:443");
+
+        let request = connector.request("ws://127.0.0.1:8080/quote").unwrap();
+        assert_eq!(target(&request).unwrap(), "127.0.0.1:8080");
+
+        let connector = Connector {
+            proxy: Some("10.0.0.1:3128".to_string()),
+            ..Connector::default()
+        };
+        assert_eq!(connector.address(&request).unwrap(), "10.0.0.1:3128");
+    }
+
+    #[test]
+    fn test_connector_headers() {
+        let connector = Connector {
+            headers: vec![("X-Trace-Id".to_string(), "abc".to_string())],
+            ..Connector::default()
+        };
+        let request = connector.request("ws://127.0.0.1/quote").unwrap();
+        assert_eq!(request.headers()["x-trace-id"], "abc");
+
+        let connector = Connector {
+            headers: vec![("bad header".to_string(), "abc".to_string())],
+            ..Connector::default()
+        };
+        assert!(connector.request("ws://127.0.0.1/quote").is_err());
+    }
+
+    #[test]
+    fn test_connector_tls() {
+        assert!(Connector::default().tls().unwrap().is_none());
+
+        let connector = Connector {
+            root_certificates: vec![b"not a pem".to_vec()],
+            ..Connector::default()
+        };
+        assert!(connector.tls().is_err());
+    }
+
+    #[test]
+    fn test_proxy_response() {
+        assert!(!proxy_response(b"HTTP/1.1 200 Connection established\r\n").unwrap());
+        assert!(proxy_response(b"HTTP/1.1 200 Connection established\r\n\r\n").unwrap());
+        assert!(proxy_response(b"HTTP/1.1 407 Proxy Authentication Required\r\n\r\n").is_err());
+        assert!(proxy_response(&[b'a'; MAX_PROXY_RESPONSE]).is_err());
+    }
+}
@@ -0,0 +1,439 @@
+use std::{collections::HashMap, sync::Mutex};
+
+#[cfg(feature = "async-websocket")]
+use futures_util::future::BoxFuture;
+use time::Date;
+
+#[cfg(feature = "async-websocket")]
+use super::AsyncSink;
+#[cfg(feature = "websocket")]
+use super::BlockSink;
+use super::{ConnectionEvent, Symbol};
+use crate::{
+    errors::FugleError,
+    schema::{
+        quote::{Quote, QuoteBidAsk, QuotePrice, QuoteTrade},
+        QuoteResponse,
+    },
+};
+
+/// What has changed between two successive quotes of a symbol.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum QuoteDelta {
+    /// The trading status flags have changed.
+    Status(StatusChange),
+    /// A new trade has been made, told by an advanced trade serial.
+    Trade(QuoteTrade),
+    /// The highest price of the day has been raised.
+    NewHigh(QuotePrice),
+    /// The lowest price of the day has been lowered.
+    NewLow(QuotePrice),
+    /// The best bid or ask has changed in price or volume, None if that side is empty.
+    BestBidAsk {
+        bid: Option<QuoteBidAsk>,
+        ask: Option<QuoteBidAsk>,
+    },
+    /// Any level of the order book has changed, carrying the whole book.
+    Depth {
+        bids: Vec<QuoteBidAsk>,
+        asks: Vec<QuoteBidAsk>,
+    },
+}
+
+/// A transition of the trading status of a symbol.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StatusChange {
+    /// The trial matching has started.
+    TrialStarted,
+    /// The trial matching has ended, the regular trading goes on.
+    TrialEnded,
+    /// The price is being curbed, or the curbing direction has changed.
+    Curbing { rise: bool, fall: bool },
+    /// The price is no longer curbed.
+    CurbingEnded,
+    /// The opening or closing has been delayed, or the delay has been lifted.
+    Delayed { open: bool, close: bool },
+    /// The trading has been halted.
+    Halted,
+    /// The halted trading has been resumed.
+    Resumed,
+    /// The trading of the day has closed.
+    Closed,
+}
+
+#[derive(Debug, Clone)]
+struct Last {
+    date: Date,
+    quote: Quote,
+}
+
+/// Turns the full quote snapshots pushed by fugle into what has changed between them,
+/// comparing every quote with the previous one of the same symbol.
+///
+/// The first quote of a symbol, and the first one of a new trading day,
+/// only sets the baseline and yields no delta.
+///
+/// # Example:
+///
+/// ```
+/// # use fugle::{schema::QuoteResponse, websocket::{QuoteDelta, QuoteDiff}};
+/// let mut diff = QuoteDiff::new();
+///
+/// // every response received from the quote endpoint.
+/// let response = QuoteResponse::default();
+/// for delta in diff.diff(&response) {
+///     if let QuoteDelta::Trade(trade) = delta {
+///         println!("{} x {}", trade.price, trade.volume);
+///     }
+/// }
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct QuoteDiff {
+    last: HashMap<Symbol, Last>,
+}
+
+impl QuoteDiff {
+    pub fn new() -> QuoteDiff {
+        QuoteDiff::default()
+    }
+
+    /// Compares the quote of the response with the previous one of its symbol,
+    /// returns the deltas in the order of status, trade, high, low, best bid ask and depth.
+    pub fn diff(&mut self, response: &QuoteResponse) -> Vec<QuoteDelta> {
+        self.diff_of(Symbol::from(&response.data.info), response)
+    }
+
+    fn diff_of(&mut self, symbol: Symbol, response: &QuoteResponse) -> Vec<QuoteDelta> {
+        let date = response.data.info.date;
+        let quote = &response.data.quote;
+        let next = Last {
+            date,
+            quote: quote.clone(),
+        };
+
+        let last = match self.last.insert(symbol, next) {
+            Some(last) => last,
+            None => return vec![],
+        };
+        // the default date means the response does not tell its day.
+        if last.date != date && last.date != Date::MIN && date != Date::MIN {
+            return vec![];
+        }
+
+        let prev = &last.quote;
+        let mut deltas: Vec<QuoteDelta> = status(prev, quote)
+            .into_iter()
+            .map(QuoteDelta::Status)
+            .collect();
+
+        if quote.trade.serial > prev.trade.serial {
+            deltas.push(QuoteDelta::Trade(quote.trade.clone()));
+        }
+
+        let (high, prev_high) = (quote.price_high.price, prev.price_high.price);
+        if !high.is_zero() && (prev_high.is_zero() || high > prev_high) {
+            deltas.push(QuoteDelta::NewHigh(quote.price_high.clone()));
+        }
+        let (low, prev_low) = (quote.price_low.price, prev.price_low.price);
+        if !low.is_zero() && (prev_low.is_zero() || low < prev_low) {
+            deltas.push(QuoteDelta::NewLow(quote.price_low.clone()));
+        }
+
+        let (bids, asks) = (&quote.order.bids, &quote.order.asks);
+        if bids.first() != prev.order.bids.first() || asks.first() != prev.order.asks.first() {
+            deltas.push(QuoteDelta::BestBidAsk {
+                bid: bids.first().cloned(),
+                ask: asks.first().cloned(),
+            });
+        }
+        if *bids != prev.order.bids || *asks != prev.order.asks {
+            deltas.push(QuoteDelta::Depth {
+                bids: bids.clone(),
+                asks: asks.clone(),
+            });
+        }
+
+        deltas
+    }
+
+    /// Returns the last quote of the symbol, None if no response of it has been compared.
+    pub fn last(&self, symbol: &Symbol) -> Option<&Quote> {
+        self.last.get(symbol).map(|last| &last.quote)
+    }
+
+    /// Drops the last quote of the symbol, its next quote sets the baseline again.
+    pub fn remove(&mut self, symbol: &Symbol) {
+        self.last.remove(symbol);
+    }
+}
+
+fn status(prev: &Quote, quote: &Quote) -> Vec<StatusChange> {
+    let mut changes = vec![];
+
+    if prev.is_trial != quote.is_trial {
+        changes.push(if quote.is_trial {
+            StatusChange::TrialStarted
+        } else {
+            StatusChange::TrialEnded
+        });
+    }
+
+    if quote.is_curbing {
+        if !prev.is_curbing
+            || prev.is_curbing_rise != quote.is_curbing_rise
+            || prev.is_curbing_fall != quote.is_curbing_fall
+        {
+            changes.push(StatusChange::Curbing {
+                rise: quote.is_curbing_rise,
+                fall: quote.is_curbing_fall,
+            });
+        }
+    } else if prev.is_curbing {
+        changes.push(StatusChange::CurbingEnded);
+    }
+
+    if prev.is_open_delayed != quote.is_open_delayed
+        || prev.is_close_delayed != quote.is_close_delayed
+    {
+        changes.push(StatusChange::Delayed {
+            open: quote.is_open_delayed,
+            close: quote.is_close_delayed,
+        });
+    }
+
+    if prev.is_halting != quote.is_halting {
+        changes.push(if quote.is_halting {
+            StatusChange::Halted
+        } else {
+            StatusChange::Resumed
+        });
+    }
+
+    // the close is only left by a new trading day, which starts the baseline over.
+    if !prev.is_closed && quote.is_closed {
+        changes.push(StatusChange::Closed);
+    }
+
+    changes
+}
+
+// compares the quotes of every symbol before handing the deltas to the inner sink.
+pub(crate) struct DeltaSink<S> {
+    inner: S,
+    diff: Mutex<QuoteDiff>,
+}
+
+impl<S> DeltaSink<S> {
+    pub(crate) fn new(inner: S) -> Self {
+        DeltaSink {
+            inner,
+            diff: Mutex::new(QuoteDiff::new()),
+        }
+    }
+
+    fn deltas(&self, symbol: &Symbol, quote: &QuoteResponse) -> Vec<QuoteDelta> {
+        let mut diff = self.diff.lock().unwrap_or_else(|e| e.into_inner());
+        diff.diff_of(symbol.clone(), quote)
+    }
+}
+
+#[cfg(feature = "websocket")]
+impl<S: BlockSink<(Symbol, QuoteDelta)>> BlockSink<QuoteResponse> for DeltaSink<S> {
+    fn send(&self, symbol: &Symbol, msg: QuoteResponse) -> bool {
+        self.deltas(symbol, &msg)
+            .into_iter()
+            .all(|delta| self.inner.send(symbol, (symbol.clone(), delta)))
+    }
+
+    fn fail(&self, symbol: &Symbol, err: FugleError) {
+        self.inner.fail(symbol, err)
+    }
+
+    fn connection(&self, symbol: &Symbol, event: ConnectionEvent) {
+        self.inner.connection(symbol, event)
+    }
+
+    fn may_block(&self) -> bool {
+        self.inner.may_block()
+    }
+}
+
+#[cfg(feature = "async-websocket")]
+impl<S: AsyncSink<(Symbol, QuoteDelta)>> AsyncSink<QuoteResponse> for DeltaSink<S> {
+    fn send<'a>(&'a self, symbol: &'a Symbol, msg: QuoteResponse) -> BoxFuture<'a, bool> {
+        let deltas = self.deltas(symbol, &msg);
+        Box::pin(async move {
+            for delta in deltas {
+                if !self.inner.send(symbol, (symbol.clone(), delta)).await {
+                    return false;
+                }
+            }
+            true
+        })
+    }
+
+    fn fail<'a>(&'a self, symbol: &'a Symbol, err: FugleError) -> BoxFuture<'a, ()> {
+        self.inner.fail(symbol, err)
+    }
+
+    fn connection<'a>(&'a self, symbol: &'a Symbol, event: ConnectionEvent) -> BoxFuture<'a, ()> {
+        self.inner.connection(symbol, event)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use rust_decimal::Decimal;
+    use time::Month;
+
+    use super::*;
+    use crate::schema::SecurityType;
+
+    fn level(price: i64, volume: u64) -> QuoteBidAsk {
+        QuoteBidAsk {
+            price: Decimal::from(price),
+            volume,
+        }
+    }
+
+    fn response(symbol: &str) -> QuoteResponse {
+        let mut response = QuoteResponse::default();
+        response.data.info.symbol_id = symbol.to_string();
+        response.data.info.typ = SecurityType::Equity;
+        response
+    }
+
+    #[test]
+    fn test_quote_diff_baseline() {
+        let mut diff = QuoteDiff::new();
+        let mut first = response("2884");
+        first.data.quote.trade.serial = 3;
+        assert!(diff.diff(&first).is_empty());
+        assert!(diff.diff(&first).is_empty());
+        assert_eq!(diff.last(&Symbol::new("2884")).unwrap().trade.serial, 3);
+
+        // another symbol has a baseline of its own.
+        assert!(diff.diff(&response("2330")).is_empty());
+
+        diff.remove(&Symbol::new("2884"));
+        assert!(diff.last(&Symbol::new("2884")).is_none());
+    }
+
+    #[test]
+    fn test_quote_diff_trade() {
+        let mut diff = QuoteDiff::new();
+        let mut quote = response("2884");
+        diff.diff(&quote);
+
+        quote.data.quote.trade.serial = 1;
+        quote.data.quote.trade.price = Decimal::from(10);
+        quote.data.quote.price_high.price = Decimal::from(10);
+        quote.data.quote.price_low.price = Decimal::from(10);
+        let deltas = diff.diff(&quote);
+        assert_eq!(deltas.len(), 3);
+        assert!(matches!(&deltas[0], QuoteDelta::Trade(trade) if trade.serial == 1));
+        assert!(matches!(deltas[1], QuoteDelta::NewHigh(_)));
+        assert!(matches!(deltas[2], QuoteDelta::NewLow(_)));
+
+        quote.data.quote.trade.serial = 2;
+        quote.data.quote.trade.price = Decimal::from(11);
+        quote.data.quote.price_high.price = Decimal::from(11);
+        let deltas = diff.diff(&quote);
+        assert_eq!(deltas.len(), 2);
+        assert!(matches!(&deltas[1], QuoteDelta::NewHigh(high) if high.price == Decimal::from(11)));
+    }
+
+    #[test]
+    fn test_quote_diff_order() {
+        let mut diff = QuoteDiff::new();
+        let mut quote = response("2884");
+        quote.data.quote.order.bids = vec![level(10, 5), level(9, 3)];
+        quote.data.quote.order.asks = vec![level(11, 2)];
+        diff.diff(&quote);
+
+        // a deeper level changes the depth only.
+        quote.data.quote.order.bids[1].volume = 4;
+        let deltas = diff.diff(&quote);
+        assert_eq!(deltas.len(), 1);
+        assert!(matches!(&deltas[0], QuoteDelta::Depth { bids, .. } if bids[1].volume == 4));
+
+        quote.data.quote.order.asks.clear();
+        let deltas = diff.diff(&quote);
+        assert_eq!(
+            deltas[0],
+            QuoteDelta::BestBidAsk {
+                bid: Some(level(10, 5)),
+                ask: None,
+            }
+        );
+        assert!(matches!(&deltas[1], QuoteDelta::Depth { asks, .. } if asks.is_empty()));
+    }
+
+    #[test]
+    fn test_quote_diff_status() {
+        let mut diff = QuoteDiff::new();
+        let mut quote = response("2884");
+        quote.data.quote.is_trial = true;
+        diff.diff(&quote);
+
+        quote.data.quote.is_trial = false;
+        quote.data.quote.is_curbing = true;
+        quote.data.quote.is_curbing_rise = true;
+        quote.data.quote.is_open_delayed = true;
+        assert_eq!(
+            diff.diff(&quote),
+            vec![
+                QuoteDelta::Status(StatusChange::TrialEnded),
+                QuoteDelta::Status(StatusChange::Curbing {
+                    rise: true,
+                    fall: false
+                }),
+                QuoteDelta::Status(StatusChange::Delayed {
+                    open: true,
+                    close: false
+                }),
+            ]
+        );
+
+        quote.data.quote.is_curbing = false;
+        quote.data.quote.is_curbing_rise = false;
+        quote.data.quote.is_halting = true;
+        assert_eq!(
+            diff.diff(&quote),
+            vec![
+                QuoteDelta::Status(StatusChange::CurbingEnded),
+                QuoteDelta::Status(StatusChange::Halted),
+            ]
+        );
+
+        quote.data.quote.is_halting = false;
+        quote.data.quote.is_closed = true;
+        assert_eq!(
+            diff.diff(&quote),
+            vec![
+                QuoteDelta::Status(StatusChange::Resumed),
+                QuoteDelta::Status(StatusChange::Closed),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_quote_diff_new_day() {
+        let day = |d| Date::from_calendar_date(2022, Month::August, d).unwrap();
+        let mut diff = QuoteDiff::new();
+        let mut quote = response("2884");
+        quote.data.info.date = day(1);
+        quote.data.quote.is_closed = true;
+        quote.data.quote.trade.serial = 100;
+        diff.diff(&quote);
+
+        // the serials start over on a new day.
+        quote.data.info.date = day(2);
+        quote.data.quote.is_closed = false;
+        quote.data.quote.trade.serial = 1;
+        assert!(diff.diff(&quote).is_empty());
+
+        quote.data.quote.trade.serial = 2;
+        assert_eq!(diff.diff(&quote).len(), 1);
+    }
+}
@@ -0,0 +1,244 @@
+use std::{
+    collections::HashMap,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Mutex,
+    },
+    time::Instant,
+};
+
+#[cfg(feature = "async-websocket")]
+use futures_util::future::BoxFuture;
+use time::OffsetDateTime;
+
+#[cfg(feature = "async-websocket")]
+use super::AsyncSink;
+#[cfg(feature = "websocket")]
+use super::BlockSink;
+use super::{ConnectionEvent, Event, Symbol};
+use crate::{
+    errors::FugleError,
+    schema::{ChartResponse, Info, MetaResponse, QuoteResponse, MIN_DATE_TIME},
+};
+
+// every websocket connection of the process gets a distinct id.
+static NEXT_CONNECTION_ID: AtomicU64 = AtomicU64::new(0);
+
+/// A response together with when and where it has been received.
+#[derive(Debug, Clone)]
+pub struct Envelope<T> {
+    /// The monotonic time the response was received.
+    pub received_at: Instant,
+    /// The wall clock time in UTC the response was received.
+    pub received_at_wall: OffsetDateTime,
+    /// The websocket connection the response came from,
+    /// a reconnected symbol gets a new id.
+    pub connection_id: u64,
+    /// The order of the response within its connection, starting from 0.
+    pub sequence: u64,
+    pub data: T,
+}
+
+impl<T: AsRef<Info>> Envelope<T> {
+    /// Returns how long it took from the server updating the response until it was received,
+    /// which is negative if the local clock is behind the server.
+    ///
+    /// Returns None if the response does not carry its last updated time.
+    pub fn latency(&self) -> Option<time::Duration> {
+        let updated_at = self.data.as_ref().last_updated_at;
+        if updated_at == MIN_DATE_TIME {
+            return None;
+        }
+        Some(self.received_at_wall - updated_at)
+    }
+}
+
+impl AsRef<Info> for ChartResponse {
+    fn as_ref(&self) -> &Info {
+        &self.data.info
+    }
+}
+
+impl AsRef<Info> for QuoteResponse {
+    fn as_ref(&self) -> &Info {
+        &self.data.info
+    }
+}
+
+impl AsRef<Info> for MetaResponse {
+    fn as_ref(&self) -> &Info {
+        &self.data.info
+    }
+}
+
+impl AsRef<Info> for Event {
+    fn as_ref(&self) -> &Info {
+        self.info()
+    }
+}
+
+#[derive(Default)]
+struct Connection {
+    id: u64,
+    sequence: u64,
+}
+
+// stamps the responses before handing them to the inner sink.
+pub(crate) struct EnvelopeSink<S> {
+    inner: S,
+    connections: Mutex<HashMap<Symbol, Connection>>,
+}
+
+impl<S> EnvelopeSink<S> {
+    pub(crate) fn new(inner: S) -> Self {
+        EnvelopeSink {
+            inner,
+            connections: Mutex::new(HashMap::new()),
+        }
+    }
+
+    fn wrap<T>(&self, symbol: &Symbol, data: T) -> Envelope<T> {
+        let received_at = Instant::now();
+        let received_at_wall = OffsetDateTime::now_utc();
+
+        let mut connections = self.connections.lock().unwrap_or_else(|e| e.into_inner());
+        let conn = connections
+            .entry(symbol.clone())
+            .or_insert_with(|| Connection {
+                id: NEXT_CONNECTION_ID.fetch_add(1, Ordering::Relaxed),
+                sequence: 0,
+            });
+        let sequence = conn.sequence;
+        conn.sequence += 1;
+
+        Envelope {
+            received_at,
+            received_at_wall,
+            connection_id: conn.id,
+            sequence,
+            data,
+        }
+    }
+
+    fn track(&self, symbol: &Symbol, event: ConnectionEvent) {
+        let mut connections = self.connections.lock().unwrap_or_else(|e| e.into_inner());
+        match event {
+            ConnectionEvent::Connected => {
+                connections.insert(
+                    symbol.clone(),
+                    Connection {
+                        id: NEXT_CONNECTION_ID.fetch_add(1, Ordering::Relaxed),
+                        sequence: 0,
+                    },
+                );
+            }
+            ConnectionEvent::Disconnected => {
+                connections.remove(symbol);
+            }
+        }
+    }
+}
+
+#[cfg(feature = "websocket")]
+impl<T, S: BlockSink<Envelope<T>>> BlockSink<T> for EnvelopeSink<S> {
+    fn send(&self, symbol: &Symbol, msg: T) -> bool {
+        self.inner.send(symbol, self.wrap(symbol, msg))
+    }
+
+    fn fail(&self, symbol: &Symbol, err: FugleError) {
+        self.inner.fail(symbol, err)
+    }
+
+    fn connection(&self, symbol: &Symbol, event: ConnectionEvent) {
+        self.track(symbol, event);
+        self.inner.connection(symbol, event)
+    }
+
+    fn may_block(&self) -> bool {
+        self.inner.may_block()
+    }
+}
+
+#[cfg(feature = "async-websocket")]
+impl<T, S: AsyncSink<Envelope<T>>> AsyncSink<T> for EnvelopeSink<S> {
+    fn send<'a>(&'a self, symbol: &'a Symbol, msg: T) -> BoxFuture<'a, bool> {
+        self.inner.send(symbol, self.wrap(symbol, msg))
+    }
+
+    fn fail<'a>(&'a self, symbol: &'a Symbol, err: FugleError) -> BoxFuture<'a, ()> {
+        self.inner.fail(symbol, err)
+    }
+
+    fn connection<'a>(&'a self, symbol: &'a Symbol, event: ConnectionEvent) -> BoxFuture<'a, ()> {
+        self.track(symbol, event);
+        self.inner.connection(symbol, event)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use time::{Date, Month, PrimitiveDateTime, Time};
+
+    use super::*;
+    use crate::schema::TAIPEI;
+
+    #[test]
+    fn test_envelope_sequence() {
+        let sink = EnvelopeSink::new(());
+        let (a, b) = (Symbol::new("2884"), Symbol::new("2330"));
+
+        sink.track(&a, ConnectionEvent::Connected);
+        sink.track(&b, ConnectionEvent::Connected);
+        let first = sink.wrap(&a, 1);
+        let second = sink.wrap(&a, 2);
+        let other = sink.wrap(&b, 3);
+        assert_eq!((first.sequence, second.sequence), (0, 1));
+        assert_eq!(first.connection_id, second.connection_id);
+        assert_ne!(first.connection_id, other.connection_id);
+        assert!(second.received_at >= first.received_at);
+
+        // a reconnection starts a new sequence.
+        sink.track(&a, ConnectionEvent::Disconnected);
+        sink.track(&a, ConnectionEvent::Connected);
+        let reconnected = sink.wrap(&a, 4);
+        assert_eq!(reconnected.sequence, 0);
+        assert_ne!(reconnected.connection_id, first.connection_id);
+    }
+
+    #[test]
+    fn test_envelope_latency() {
+        let sink = EnvelopeSink::new(());
+        let symbol = Symbol::new("2884");
+
+        let mut quote = sink.wrap(&symbol, QuoteResponse::default());
+        assert_eq!(quote.latency(), None);
+
+        let at = |hour| {
+            PrimitiveDateTime::new(
+                Date::from_calendar_date(2022, Month::May, 20).unwrap(),
+                Time::from_hms(hour, 30, 0).unwrap(),
+            )
+        };
+        // fugle sends the last updated time in Asia/Taipei.
+        quote.data.data.info.last_updated_at = at(9).assume_offset(TAIPEI);
+        quote.received_at_wall = at(1).assume_utc() + time::Duration::milliseconds(250);
+        assert_eq!(quote.latency(), Some(time::Duration::milliseconds(250)));
+    }
+
+    #[test]
+    fn test_envelope_latency_payload() {
+        let sink = EnvelopeSink::new(());
+        let data: QuoteResponse =
+            serde_json::from_str(include_str!("../../../tests/testdata/quote_response.json"))
+                .unwrap();
+
+        // updated at 13:30:00 +08:00, which is 05:30:00 in UTC.
+        let mut quote = sink.wrap(&Symbol::new("2884"), data);
+        quote.received_at_wall = PrimitiveDateTime::new(
+            Date::from_calendar_date(2021, Month::October, 22).unwrap(),
+            Time::from_hms_milli(5, 30, 0, 250).unwrap(),
+        )
+        .assume_utc();
+        assert_eq!(quote.latency(), Some(time::Duration::milliseconds(250)));
+    }
+}
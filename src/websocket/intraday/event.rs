@@ -0,0 +1,102 @@
+#[cfg(feature = "async-websocket")]
+use futures_util::future::BoxFuture;
+
+#[cfg(feature = "async-websocket")]
+use super::AsyncSink;
+#[cfg(feature = "websocket")]
+use super::BlockSink;
+use super::{Channel, Symbol};
+use crate::schema::{ChartResponse, Info, MetaResponse, QuoteResponse};
+
+/// A response from any of the fugle websocket endpoints.
+#[derive(Debug)]
+#[allow(clippy::large_enum_variant)]
+pub enum Event {
+    Chart(ChartResponse),
+    Quote(QuoteResponse),
+    Meta(MetaResponse),
+}
+
+impl Event {
+    /// Returns which endpoint the event comes from.
+    pub fn channel(&self) -> Channel {
+        match *self {
+            Event::Chart(_) => Channel::Chart,
+            Event::Quote(_) => Channel::Quote,
+            Event::Meta(_) => Channel::Meta,
+        }
+    }
+
+    /// Returns the info of the stock the event belongs to.
+    pub fn info(&self) -> &Info {
+        match *self {
+            Event::Chart(ref r) => &r.data.info,
+            Event::Quote(ref r) => &r.data.info,
+            Event::Meta(ref r) => &r.data.info,
+        }
+    }
+
+    /// Returns the stock identification code the event belongs to.
+    pub fn symbol_id(&self) -> &str {
+        &self.info().symbol_id
+    }
+}
+
+impl From<ChartResponse> for Event {
+    fn from(r: ChartResponse) -> Event {
+        Event::Chart(r)
+    }
+}
+
+impl From<QuoteResponse> for Event {
+    fn from(r: QuoteResponse) -> Event {
+        Event::Quote(r)
+    }
+}
+
+impl From<MetaResponse> for Event {
+    fn from(r: MetaResponse) -> Event {
+        Event::Meta(r)
+    }
+}
+
+// wraps the responses of every channel into events of one sender.
+pub(crate) struct EventSink<S>(pub(crate) S);
+
+#[cfg(feature = "websocket")]
+impl<T: Into<Event>> BlockSink<T> for EventSink<std::sync::mpsc::Sender<Event>> {
+    fn send(&self, _: &Symbol, msg: T) -> bool {
+        self.0.send(msg.into()).is_ok()
+    }
+}
+
+#[cfg(feature = "async-websocket")]
+impl<T: Into<Event>> AsyncSink<T> for EventSink<tokio::sync::mpsc::UnboundedSender<Event>> {
+    fn send<'a>(&'a self, _: &'a Symbol, msg: T) -> BoxFuture<'a, bool> {
+        let sent = self.0.send(msg.into()).is_ok();
+        Box::pin(async move { sent })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_event_from_response() {
+        let mut quote = QuoteResponse::default();
+        quote.data.info.symbol_id = "2884".to_string();
+
+        let event = Event::from(quote);
+        assert_eq!(event.channel(), Channel::Quote);
+        assert_eq!(event.symbol_id(), "2884");
+        assert_eq!(
+            Event::from(MetaResponse::default()).channel(),
+            Channel::Meta
+        );
+        assert_eq!(
+            Event::from(ChartResponse::default()).channel(),
+            Channel::Chart
+        );
+    }
+}
@@ -0,0 +1,143 @@
+#[cfg(all(feature = "websocket", feature = "query"))]
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::{Duration, Instant};
+
+use log::error;
+
+#[cfg(all(feature = "async-websocket", feature = "async-query"))]
+use super::AsyncSink;
+#[cfg(all(feature = "websocket", feature = "query"))]
+use super::BlockSink;
+use super::{
+    feed::{Snapshot, Version},
+    Symbol,
+};
+#[cfg(all(feature = "websocket", feature = "query"))]
+use crate::http::BlockRequest;
+#[cfg(all(feature = "async-websocket", feature = "async-query"))]
+use crate::websocket::runtime::Rest;
+use crate::errors::FugleError;
+
+// how long a polling worker sleeps before checking its done flag again.
+#[cfg(all(feature = "websocket", feature = "query"))]
+const SLEEP_SLICE: Duration = Duration::from_millis(100);
+
+/// How a worker polls the REST endpoint of its channel while the websocket cannot connect.
+#[derive(Debug, Clone)]
+pub(crate) struct Fallback {
+    /// How long to wait between two polls.
+    pub(crate) interval: Duration,
+    /// How long to poll before trying the websocket again.
+    pub(crate) reconnect: Duration,
+}
+
+/// Whether a failed connection is left to polling,
+/// a malformed url or a rejected token fails up front instead.
+pub(crate) fn falls_back(err: &FugleError) -> bool {
+    match err {
+        FugleError::Tungstenite(e) => match **e {
+            tungstenite::Error::Url(_) => false,
+            tungstenite::Error::Http(ref res) => res.status() != 401,
+            _ => true,
+        },
+        _ => true,
+    }
+}
+
+// passes on only the polled responses newer than the last one.
+fn fresh<T: Snapshot>(last: &mut Option<Version>, data: &T) -> bool {
+    let next = Version::of(data);
+    if matches!(last, Some(last) if !next.is_newer_than(last)) {
+        return false;
+    }
+    *last = Some(next);
+    true
+}
+
+impl Fallback {
+    /// Polls the REST endpoint until it is time to reconnect,
+    /// returns false if the worker is done meanwhile.
+    #[cfg(all(feature = "websocket", feature = "query"))]
+    pub(crate) fn poll<T: Snapshot>(
+        &self,
+        client: &BlockRequest<'_>,
+        symbol: &Symbol,
+        sink: &dyn BlockSink<T>,
+        done: &AtomicBool,
+        last: &mut Option<Version>,
+    ) -> bool {
+        let until = Instant::now() + self.reconnect;
+        loop {
+            if done.load(Ordering::SeqCst) {
+                return false;
+            }
+
+            match client.call(T::request(symbol)) {
+                Ok(data) => {
+                    if fresh(last, &data) && !sink.send(symbol, data) {
+                        error!("sending on a closed channel");
+                    }
+                }
+                Err(e) => {
+                    error!("polling {} over REST: {}", symbol.id, e);
+                    sink.fail(symbol, e);
+                }
+            }
+
+            let next = Instant::now() + self.interval;
+            loop {
+                if done.load(Ordering::SeqCst) {
+                    return false;
+                }
+                let remain = next.saturating_duration_since(Instant::now());
+                if remain.is_zero() {
+                    break;
+                }
+                std::thread::sleep(remain.min(SLEEP_SLICE));
+            }
+            if Instant::now() >= until {
+                return true;
+            }
+        }
+    }
+
+    /// Polls the REST endpoint until it is time to reconnect,
+    /// returns false if the worker is done meanwhile.
+    #[cfg(all(feature = "async-websocket", feature = "async-query"))]
+    pub(crate) async fn async_poll<T: Snapshot + Send + 'static>(
+        &self,
+        rest: &Rest,
+        symbol: &Symbol,
+        sink: &dyn AsyncSink<T>,
+        done: &mut tokio::sync::watch::Receiver<bool>,
+        last: &mut Option<Version>,
+    ) -> bool {
+        let until = Instant::now() + self.reconnect;
+        loop {
+            if *done.borrow() {
+                return false;
+            }
+
+            match rest.call(T::request(symbol)).await {
+                Ok(data) => {
+                    if fresh(last, &data) && !sink.send(symbol, data).await {
+                        error!("sending on a closed channel");
+                    }
+                }
+                Err(e) => {
+                    error!("polling {} over REST: {}", symbol.id, e);
+                    sink.fail(symbol, e).await;
+                }
+            }
+
+            // a changed or dropped done flag both mean the worker is done.
+            let wait = crate::websocket::runtime::timeout(self.interval, done.changed());
+            if wait.await.is_some() {
+                return false;
+            }
+            if Instant::now() >= until {
+                return true;
+            }
+        }
+    }
+}
@@ -0,0 +1,469 @@
+use std::{
+    collections::{hash_map::DefaultHasher, HashMap},
+    hash::Hasher,
+    sync::{Arc, Mutex},
+};
+
+#[cfg(all(feature = "async-websocket", feature = "async-query"))]
+use futures_util::future::BoxFuture;
+use log::error;
+use serde::Serialize;
+use time::OffsetDateTime;
+
+#[cfg(all(feature = "async-websocket", feature = "async-query"))]
+use super::AsyncSink;
+#[cfg(all(feature = "websocket", feature = "query"))]
+use super::BlockSink;
+use super::{ConnectionEvent, Symbol};
+#[cfg(all(feature = "websocket", feature = "query"))]
+use crate::http::BlockRequest;
+#[cfg(all(feature = "async-websocket", feature = "async-query"))]
+use crate::websocket::runtime::{Rest, Spawner};
+use crate::{
+    errors::FugleError,
+    http::intraday::{ChartRequest, MetaRequest, QuoteRequest},
+    schema::{ChartResponse, Info, MetaResponse, QuoteResponse, Result, MIN_DATE_TIME},
+};
+
+/// A state of a symbol from the feed, either the REST snapshot or a websocket push,
+/// each one newer than the previous of its symbol.
+#[derive(Debug, Clone)]
+pub struct Feed<T> {
+    pub symbol: Symbol,
+    pub data: T,
+    /// Whether the state came from the REST snapshot instead of the websocket.
+    pub snapshot: bool,
+}
+
+// a response which can be fetched from the matching REST endpoint as well.
+pub(crate) trait Snapshot: AsRef<Info> + Serialize + Sized {
+    type Request<'a>: crate::http::Request<Response = Self> + Send;
+
+    fn request(symbol: &Symbol) -> Self::Request<'_>;
+
+    // the serial of the updates, 0 if the response carries none.
+    fn serial(&self) -> u64 {
+        0
+    }
+
+    // a fingerprint of the content, telling apart the states nothing else orders.
+    fn digest(&self) -> u64 {
+        let mut hasher = DefaultHasher::new();
+        if let Ok(bytes) = serde_json::to_vec(self) {
+            hasher.write(&bytes);
+        }
+        hasher.finish()
+    }
+}
+
+impl Snapshot for QuoteResponse {
+    type Request<'a> = QuoteRequest<'a>;
+
+    fn request(symbol: &Symbol) -> QuoteRequest<'_> {
+        QuoteRequest::new()
+            .symbol_id(&symbol.id)
+            .odd_lot(symbol.odd_lot)
+    }
+
+    fn serial(&self) -> u64 {
+        self.data.quote.total.serial
+    }
+}
+
+impl Snapshot for ChartResponse {
+    type Request<'a> = ChartRequest<'a>;
+
+    fn request(symbol: &Symbol) -> ChartRequest<'_> {
+        ChartRequest::new()
+            .symbol_id(&symbol.id)
+            .odd_lot(symbol.odd_lot)
+    }
+}
+
+impl Snapshot for MetaResponse {
+    type Request<'a> = MetaRequest<'a>;
+
+    fn request(symbol: &Symbol) -> MetaRequest<'_> {
+        MetaRequest::new()
+            .symbol_id(&symbol.id)
+            .odd_lot(symbol.odd_lot)
+    }
+}
+
+// what a state is ordered by.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct Version {
+    at: OffsetDateTime,
+    serial: u64,
+    // only taken from the responses telling neither their time nor a serial.
+    digest: Option<u64>,
+}
+
+impl Version {
+    pub(crate) fn of<T: Snapshot>(data: &T) -> Version {
+        let at = data.as_ref().last_updated_at;
+        let serial = data.serial();
+        Version {
+            at,
+            serial,
+            digest: (at == MIN_DATE_TIME && serial == 0).then(|| data.digest()),
+        }
+    }
+
+    // whether the times or the serials tell which one is newer,
+    // an unknown time cannot and neither can a missing serial.
+    pub(crate) fn is_ordered_with(&self, last: &Version) -> bool {
+        (self.at != MIN_DATE_TIME && last.at != MIN_DATE_TIME)
+            || (self.serial != 0 && last.serial != 0)
+    }
+
+    pub(crate) fn is_newer_than(&self, last: &Version) -> bool {
+        // without an order only a changed state is newer.
+        if !self.is_ordered_with(last) {
+            return !matches!((self.digest, last.digest), (Some(a), Some(b)) if a == b);
+        }
+        if self.at == MIN_DATE_TIME || last.at == MIN_DATE_TIME {
+            return self.serial > last.serial;
+        }
+        (self.at, self.serial) > (last.at, last.serial)
+    }
+}
+
+// the last state passed on of every symbol.
+#[derive(Default)]
+pub(crate) struct Versions(Mutex<HashMap<Symbol, Version>>);
+
+impl Versions {
+    // returns false if the message is not newer than the last state of the symbol.
+    pub(crate) fn advance<T: Snapshot>(&self, symbol: &Symbol, data: &T) -> bool {
+        self.replace(symbol, Version::of(data), |next, last| {
+            next.is_newer_than(last)
+        })
+    }
+
+    // like advance, but a snapshot may have been taken before the last message,
+    // so it only replaces a state it is ordered with.
+    pub(crate) fn advance_snapshot<T: Snapshot>(&self, symbol: &Symbol, data: &T) -> bool {
+        self.replace(symbol, Version::of(data), |next, last| {
+            next.is_ordered_with(last) && next.is_newer_than(last)
+        })
+    }
+
+    fn replace<F>(&self, symbol: &Symbol, next: Version, newer: F) -> bool
+    where
+        F: FnOnce(&Version, &Version) -> bool,
+    {
+        let mut versions = self.0.lock().unwrap_or_else(|e| e.into_inner());
+        if matches!(versions.get(symbol), Some(last) if !newer(&next, last)) {
+            return false;
+        }
+        versions.insert(symbol.clone(), next);
+        true
+    }
+}
+
+// passes on a fetched snapshot newer than the last state,
+// returns false once the receiving side has gone.
+fn deliver<T, F>(versions: &Versions, symbol: Symbol, snapshot: Result<T>, send: F) -> bool
+where
+    T: Snapshot,
+    F: FnOnce(Result<Feed<T>>) -> bool,
+{
+    match snapshot {
+        Ok(data) if !versions.advance_snapshot(&symbol, &data) => true,
+        Ok(data) => send(Ok(Feed {
+            symbol,
+            data,
+            snapshot: true,
+        })),
+        Err(e) => {
+            error!("fetching the snapshot of {}: {}", symbol.id, e);
+            send(Err(e))
+        }
+    }
+}
+
+// fetches a snapshot in the background on every connection of a symbol,
+// and passes on only the states newer than the last one passed,
+// whichever of the snapshot and the websocket messages comes first.
+pub(crate) struct FeedSink<S, R> {
+    // the symbols to fetch a snapshot of.
+    snapshots: R,
+    versions: Arc<Versions>,
+    tx: S,
+}
+
+impl<S, R> FeedSink<S, R> {
+    fn live<T: Snapshot>(&self, symbol: &Symbol, msg: T) -> Option<Feed<T>> {
+        if !self.versions.advance(symbol, &msg) {
+            return None;
+        }
+        Some(Feed {
+            symbol: symbol.clone(),
+            data: msg,
+            snapshot: false,
+        })
+    }
+}
+
+#[cfg(all(feature = "websocket", feature = "query"))]
+impl<T: Snapshot + Send + 'static>
+    FeedSink<std::sync::mpsc::Sender<Result<Feed<T>>>, std::sync::mpsc::Sender<Symbol>>
+{
+    /// Starts the snapshot thread, which exits once the receiver or the sink is dropped.
+    pub(crate) fn new(
+        client: BlockRequest<'static>,
+        tx: std::sync::mpsc::Sender<Result<Feed<T>>>,
+    ) -> Self {
+        let (snapshots, rx) = std::sync::mpsc::channel::<Symbol>();
+        let versions = Arc::<Versions>::default();
+
+        let (fetched, sent) = (versions.clone(), tx.clone());
+        std::thread::spawn(move || {
+            for symbol in rx {
+                let snapshot = client.call(T::request(&symbol));
+                if !deliver(&fetched, symbol, snapshot, |feed| sent.send(feed).is_ok()) {
+                    return;
+                }
+            }
+        });
+
+        FeedSink {
+            snapshots,
+            versions,
+            tx,
+        }
+    }
+}
+
+#[cfg(all(feature = "websocket", feature = "query"))]
+impl<T: Snapshot + Send> BlockSink<T>
+    for FeedSink<std::sync::mpsc::Sender<Result<Feed<T>>>, std::sync::mpsc::Sender<Symbol>>
+{
+    fn send(&self, symbol: &Symbol, msg: T) -> bool {
+        match self.live(symbol, msg) {
+            Some(feed) => self.tx.send(Ok(feed)).is_ok(),
+            None => true,
+        }
+    }
+
+    fn fail(&self, _: &Symbol, err: FugleError) {
+        let _ = self.tx.send(Err(err));
+    }
+
+    fn connection(&self, symbol: &Symbol, event: ConnectionEvent) {
+        if event == ConnectionEvent::Connected {
+            let _ = self.snapshots.send(symbol.clone());
+        }
+    }
+}
+
+#[cfg(all(feature = "async-websocket", feature = "async-query"))]
+impl<T: Snapshot + Send + 'static>
+    FeedSink<
+        tokio::sync::mpsc::UnboundedSender<Result<Feed<T>>>,
+        tokio::sync::mpsc::UnboundedSender<Symbol>,
+    >
+{
+    /// Spawns the snapshot task, which exits once the receiver or the sink is dropped.
+    pub(crate) fn async_new(
+        rest: Rest,
+        tx: tokio::sync::mpsc::UnboundedSender<Result<Feed<T>>>,
+        spawner: &Spawner,
+    ) -> Self {
+        let (snapshots, mut rx) = tokio::sync::mpsc::unbounded_channel::<Symbol>();
+        let versions = Arc::<Versions>::default();
+
+        let (fetched, sent) = (versions.clone(), tx.clone());
+        spawner.spawn(Box::pin(async move {
+            while let Some(symbol) = rx.recv().await {
+                let snapshot = rest.call(T::request(&symbol)).await;
+                if !deliver(&fetched, symbol, snapshot, |feed| sent.send(feed).is_ok()) {
+                    return;
+                }
+            }
+        }));
+
+        FeedSink {
+            snapshots,
+            versions,
+            tx,
+        }
+    }
+}
+
+#[cfg(all(feature = "async-websocket", feature = "async-query"))]
+impl<T: Snapshot + Send + 'static> AsyncSink<T>
+    for FeedSink<
+        tokio::sync::mpsc::UnboundedSender<Result<Feed<T>>>,
+        tokio::sync::mpsc::UnboundedSender<Symbol>,
+    >
+{
+    fn send<'a>(&'a self, symbol: &'a Symbol, msg: T) -> BoxFuture<'a, bool> {
+        let sent = match self.live(symbol, msg) {
+            Some(feed) => self.tx.send(Ok(feed)).is_ok(),
+            None => true,
+        };
+        Box::pin(async move { sent })
+    }
+
+    fn fail<'a>(&'a self, _: &'a Symbol, err: FugleError) -> BoxFuture<'a, ()> {
+        let _ = self.tx.send(Err(err));
+        Box::pin(async {})
+    }
+
+    fn connection<'a>(&'a self, symbol: &'a Symbol, event: ConnectionEvent) -> BoxFuture<'a, ()> {
+        if event == ConnectionEvent::Connected {
+            let _ = self.snapshots.send(symbol.clone());
+        }
+        Box::pin(async {})
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use time::{Date, Month, PrimitiveDateTime, Time};
+
+    use super::*;
+    use crate::schema::TAIPEI;
+
+    fn at(second: u8) -> OffsetDateTime {
+        PrimitiveDateTime::new(
+            Date::from_calendar_date(2022, Month::August, 1).unwrap(),
+            Time::from_hms(9, 0, second).unwrap(),
+        )
+        .assume_offset(TAIPEI)
+    }
+
+    fn quote(updated_at: OffsetDateTime, serial: u64) -> QuoteResponse {
+        let mut quote = QuoteResponse::default();
+        quote.data.info.last_updated_at = updated_at;
+        quote.data.quote.total.serial = serial;
+        quote
+    }
+
+    #[test]
+    fn test_feed_version() {
+        let version = |at, serial| Version {
+            at,
+            serial,
+            digest: None,
+        };
+        let last = version(at(1), 5);
+        assert!(version(at(2), 5).is_newer_than(&last));
+        assert!(version(at(1), 6).is_newer_than(&last));
+        assert!(!version(at(1), 5).is_newer_than(&last));
+        assert!(!version(at(0), 9).is_newer_than(&last));
+
+        let unknown = MIN_DATE_TIME;
+        assert!(version(unknown, 6).is_newer_than(&last));
+        assert!(!version(unknown, 4).is_newer_than(&last));
+        assert!(!version(unknown, 0).is_ordered_with(&last));
+        let changed = Version {
+            digest: Some(1),
+            ..version(unknown, 0)
+        };
+        assert!(!changed.is_ordered_with(&last));
+        assert!(changed.is_newer_than(&last));
+        assert!(!changed.is_newer_than(&changed));
+
+        // the digest is taken only when nothing else orders the states.
+        assert_eq!(Version::of(&quote(at(1), 0)).digest, None);
+        assert_eq!(Version::of(&quote(unknown, 5)).digest, None);
+        assert!(Version::of(&quote(unknown, 0)).digest.is_some());
+    }
+
+    #[test]
+    fn test_feed_meta_unknown_time() {
+        let versions = Versions::default();
+        let symbol = Symbol::new("2884");
+
+        // the same meta is passed on once, a changed one again.
+        let mut meta = MetaResponse::default();
+        assert!(versions.advance(&symbol, &meta));
+        assert!(!versions.advance(&symbol, &meta.clone()));
+        meta.data.meta.is_suspended = true;
+        assert!(versions.advance(&symbol, &meta));
+        assert!(!versions.advance(&symbol, &meta));
+    }
+
+    #[test]
+    fn test_feed_reconcile() {
+        let versions = Versions::default();
+        let (a, b) = (Symbol::new("2884"), Symbol::new("2330"));
+
+        // the snapshot is taken after some of the buffered messages.
+        assert!(versions.advance(&a, &quote(at(2), 10)));
+        assert!(!versions.advance(&a, &quote(at(1), 8)));
+        assert!(!versions.advance(&a, &quote(at(2), 10)));
+        assert!(versions.advance(&a, &quote(at(2), 11)));
+        assert!(versions.advance(&a, &quote(at(3), 12)));
+
+        // every symbol is ordered on its own.
+        assert!(versions.advance(&b, &quote(at(1), 1)));
+
+        let mut chart = ChartResponse::default();
+        chart.data.info.last_updated_at = at(4);
+        assert!(versions.advance(&Symbol::new("0050"), &chart));
+        assert!(!versions.advance(&Symbol::new("0050"), &chart));
+    }
+
+    #[test]
+    fn test_feed_deliver() {
+        let versions = Versions::default();
+        let symbol = Symbol::new("2884");
+        let mut sent = vec![];
+
+        // a snapshot older than the websocket messages passed is dropped.
+        assert!(versions.advance(&symbol, &quote(at(2), 10)));
+        let stale = Ok(quote(at(1), 8));
+        assert!(deliver(&versions, symbol.clone(), stale, |feed| {
+            sent.push(feed);
+            true
+        }));
+        assert!(sent.is_empty());
+
+        let fresh = Ok(quote(at(3), 12));
+        assert!(deliver(&versions, symbol.clone(), fresh, |feed| {
+            sent.push(feed);
+            true
+        }));
+        let failed = Err(FugleError::ResourceNotFound);
+        assert!(!deliver(&versions, symbol, failed, |feed| {
+            sent.push(feed);
+            false
+        }));
+        let feed = sent[0].as_ref().unwrap();
+        assert!(feed.snapshot);
+        assert_eq!(feed.data.data.quote.total.serial, 12);
+        assert!(matches!(sent[1], Err(FugleError::ResourceNotFound)));
+    }
+
+    #[test]
+    fn test_feed_deliver_unordered() {
+        let versions = Versions::default();
+        let symbol = Symbol::new("2884");
+        let mut sent = vec![];
+
+        // a snapshot telling no time passes on while nothing else has.
+        let meta = MetaResponse::default();
+        assert!(deliver(&versions, symbol.clone(), Ok(meta.clone()), |feed| {
+            sent.push(feed);
+            true
+        }));
+        assert_eq!(sent.len(), 1);
+
+        // a changed snapshot may be older than the message passed, it is dropped.
+        let mut live = meta.clone();
+        live.data.meta.is_suspended = true;
+        assert!(versions.advance(&symbol, &live));
+        assert!(deliver(&versions, symbol.clone(), Ok(meta.clone()), |feed| {
+            sent.push(feed);
+            true
+        }));
+        assert_eq!(sent.len(), 1);
+
+        // while a changed message is still newer.
+        assert!(versions.advance(&symbol, &meta));
+    }
+}
@@ -0,0 +1,490 @@
+use std::{collections::HashMap, sync::Mutex};
+
+#[cfg(all(feature = "async-websocket", feature = "async-query"))]
+use futures_util::future::BoxFuture;
+use log::error;
+use time::Date;
+
+#[cfg(all(feature = "async-websocket", feature = "async-query"))]
+use super::AsyncSink;
+#[cfg(all(feature = "websocket", feature = "query"))]
+use super::BlockSink;
+use super::Symbol;
+#[cfg(all(feature = "websocket", feature = "query"))]
+use crate::http::BlockRequest;
+#[cfg(all(feature = "async-websocket", feature = "async-query"))]
+use crate::websocket::runtime::{Rest, Spawner};
+use crate::{
+    errors::FugleError,
+    http::intraday::DealtsRequest,
+    schema::{dealts::Dealt, QuoteResponse, Result},
+};
+
+// the most dealts fetched by one backfill request.
+const MAX_PAGE_LIMIT: u64 = 500;
+
+/// A trade of the quote stream in serial order,
+/// the trades skipped by the stream are backfilled from the dealts endpoint.
+#[derive(Debug, Clone)]
+pub struct Trade {
+    pub symbol: Symbol,
+    pub dealt: Dealt,
+    /// Whether the trade came from the dealts endpoint instead of the quote stream.
+    pub backfilled: bool,
+}
+
+// where the trades of a symbol stand, the serials are increasing but not contiguous,
+// so the trades skipped are counted by the transactions instead.
+// both start over every trading day.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct Mark {
+    date: Date,
+    transaction: u64,
+    serial: u64,
+}
+
+// the trades a new quote moves its symbol from and to.
+#[derive(Debug, PartialEq, Eq)]
+pub(crate) struct Step {
+    last: Option<Mark>,
+    mark: Mark,
+}
+
+impl Step {
+    // how many trades are skipped between the last trade and the new one.
+    fn missing(&self) -> u64 {
+        match self.last {
+            Some(last) => self
+                .mark
+                .transaction
+                .saturating_sub(last.transaction)
+                .saturating_sub(1),
+            None => 0,
+        }
+    }
+}
+
+// remembers the last trade of every symbol, across reconnections.
+#[derive(Default)]
+struct Tracker {
+    marks: Mutex<HashMap<Symbol, Mark>>,
+}
+
+impl Tracker {
+    // returns None if the quote carries no new trade.
+    fn advance(&self, symbol: &Symbol, quote: &QuoteResponse) -> Option<Step> {
+        let mark = Mark {
+            date: quote.data.info.date,
+            transaction: quote.data.quote.total.transaction,
+            serial: quote.data.quote.trade.serial,
+        };
+        if mark.serial == 0 {
+            return None;
+        }
+
+        let mut marks = self.marks.lock().unwrap_or_else(|e| e.into_inner());
+        let last = match marks.get(symbol).copied() {
+            // a late quote of the day before.
+            Some(last) if mark.date < last.date => return None,
+            // a new trading day, nothing to backfill from the day before.
+            Some(last) if mark.date > last.date => None,
+            Some(last) if mark.serial <= last.serial => return None,
+            last => last,
+        };
+        marks.insert(symbol.clone(), mark);
+        Some(Step { last, mark })
+    }
+}
+
+// a backfill walks the dealts from the latest one backwards page by page,
+// until every skipped trade is found or the last trade is reached.
+struct Backfill {
+    after: u64,
+    before: u64,
+    missing: u64,
+    offset: u64,
+    limit: u64,
+    dealts: Vec<Dealt>,
+}
+
+impl Backfill {
+    fn new(step: &Step) -> Self {
+        Backfill {
+            after: step.last.map(|last| last.serial).unwrap_or_default(),
+            before: step.mark.serial,
+            missing: step.missing(),
+            offset: 0,
+            limit: step.missing().clamp(1, MAX_PAGE_LIMIT),
+            dealts: vec![],
+        }
+    }
+
+    fn request<'a>(&self, symbol: &'a Symbol) -> DealtsRequest<'a> {
+        DealtsRequest::new()
+            .symbol_id(&symbol.id)
+            .odd_lot(symbol.odd_lot)
+            .limit(self.limit as usize)
+            .offset(self.offset as usize)
+    }
+
+    // keeps the missing dealts of the page, returns true once there is nothing left to fetch.
+    fn take(&mut self, page: Vec<Dealt>) -> bool {
+        // fugle returns the latest dealts first.
+        let reached = page.is_empty() || page.iter().any(|d| d.serial <= self.after);
+        self.offset += page.len() as u64;
+        self.dealts.extend(
+            page.into_iter()
+                .filter(|d| d.serial > self.after && d.serial < self.before),
+        );
+        self.dealts.sort_by_key(|d| d.serial);
+        self.dealts.dedup_by_key(|d| d.serial);
+        reached || self.dealts.len() as u64 >= self.missing
+    }
+
+    fn finish(self) -> Vec<Dealt> {
+        self.dealts
+    }
+}
+
+// what the quote stream hands over to the backfilling, in the order of the stream.
+pub(crate) enum Job {
+    Trade { symbol: Symbol, step: Step, dealt: Dealt },
+    Fail(FugleError),
+}
+
+impl Job {
+    // delivers the trades of the job, the backfilled ones first,
+    // returns false once the receiver has been dropped.
+    fn deliver<F>(self, backfilled: Option<Result<Vec<Dealt>>>, send: F) -> bool
+    where
+        F: Fn(Result<Trade>) -> bool,
+    {
+        let (symbol, dealt) = match self {
+            Job::Trade { symbol, dealt, .. } => (symbol, dealt),
+            Job::Fail(e) => return send(Err(e)),
+        };
+
+        match backfilled {
+            Some(Ok(dealts)) => {
+                for dealt in dealts {
+                    let trade = Trade {
+                        symbol: symbol.clone(),
+                        dealt,
+                        backfilled: true,
+                    };
+                    if !send(Ok(trade)) {
+                        return false;
+                    }
+                }
+            }
+            Some(Err(e)) => {
+                error!("backfilling trades of {}: {}", symbol.id, e);
+                if !send(Err(e)) {
+                    return false;
+                }
+            }
+            None => {}
+        }
+
+        send(Ok(Trade {
+            symbol,
+            dealt,
+            backfilled: false,
+        }))
+    }
+}
+
+// orders the trades of the quote stream, and hands them over to a background backfilling
+// which fetches the skipped ones without holding the socket reads.
+pub(crate) struct GapSink<S> {
+    tracker: Tracker,
+    jobs: S,
+}
+
+impl<S> GapSink<S> {
+    fn job(&self, symbol: &Symbol, msg: &QuoteResponse) -> Option<Job> {
+        let step = self.tracker.advance(symbol, msg)?;
+        Some(Job::Trade {
+            symbol: symbol.clone(),
+            step,
+            dealt: Dealt::from(&msg.data.quote.trade),
+        })
+    }
+}
+
+#[cfg(all(feature = "websocket", feature = "query"))]
+impl GapSink<std::sync::mpsc::Sender<Job>> {
+    /// Starts the backfilling thread, which exits once the receiver or the sink is dropped.
+    pub(crate) fn new(
+        client: BlockRequest<'static>,
+        tx: std::sync::mpsc::Sender<Result<Trade>>,
+    ) -> Self {
+        let (jobs, rx) = std::sync::mpsc::channel::<Job>();
+
+        std::thread::spawn(move || {
+            for job in rx {
+                let backfilled = match job {
+                    Job::Trade {
+                        ref symbol,
+                        ref step,
+                        ..
+                    } if step.missing() > 0 => Some(backfill(&client, symbol, step)),
+                    _ => None,
+                };
+                if !job.deliver(backfilled, |trade| tx.send(trade).is_ok()) {
+                    return;
+                }
+            }
+        });
+
+        GapSink {
+            tracker: Tracker::default(),
+            jobs,
+        }
+    }
+}
+
+#[cfg(all(feature = "websocket", feature = "query"))]
+fn backfill(client: &BlockRequest<'_>, symbol: &Symbol, step: &Step) -> Result<Vec<Dealt>> {
+    let mut backfill = Backfill::new(step);
+    loop {
+        let page = client.call(backfill.request(symbol))?.data.dealts;
+        if backfill.take(page) {
+            return Ok(backfill.finish());
+        }
+    }
+}
+
+#[cfg(all(feature = "websocket", feature = "query"))]
+impl BlockSink<QuoteResponse> for GapSink<std::sync::mpsc::Sender<Job>> {
+    fn send(&self, symbol: &Symbol, msg: QuoteResponse) -> bool {
+        match self.job(symbol, &msg) {
+            Some(job) => self.jobs.send(job).is_ok(),
+            None => true,
+        }
+    }
+
+    fn fail(&self, _: &Symbol, err: FugleError) {
+        let _ = self.jobs.send(Job::Fail(err));
+    }
+}
+
+#[cfg(all(feature = "async-websocket", feature = "async-query"))]
+impl GapSink<tokio::sync::mpsc::UnboundedSender<Job>> {
+    /// Spawns the backfilling task, which exits once the receiver or the sink is dropped.
+    pub(crate) fn async_new(
+        rest: Rest,
+        tx: tokio::sync::mpsc::UnboundedSender<Result<Trade>>,
+        spawner: &Spawner,
+    ) -> Self {
+        let (jobs, mut rx) = tokio::sync::mpsc::unbounded_channel::<Job>();
+
+        spawner.spawn(Box::pin(async move {
+            while let Some(job) = rx.recv().await {
+                let backfilled = match job {
+                    Job::Trade {
+                        ref symbol,
+                        ref step,
+                        ..
+                    } if step.missing() > 0 => Some(async_backfill(&rest, symbol, step).await),
+                    _ => None,
+                };
+                if !job.deliver(backfilled, |trade| tx.send(trade).is_ok()) {
+                    return;
+                }
+            }
+        }));
+
+        GapSink {
+            tracker: Tracker::default(),
+            jobs,
+        }
+    }
+}
+
+#[cfg(all(feature = "async-websocket", feature = "async-query"))]
+async fn async_backfill(rest: &Rest, symbol: &Symbol, step: &Step) -> Result<Vec<Dealt>> {
+    let mut backfill = Backfill::new(step);
+    loop {
+        let page = rest.call(backfill.request(symbol)).await?.data.dealts;
+        if backfill.take(page) {
+            return Ok(backfill.finish());
+        }
+    }
+}
+
+#[cfg(all(feature = "async-websocket", feature = "async-query"))]
+impl AsyncSink<QuoteResponse> for GapSink<tokio::sync::mpsc::UnboundedSender<Job>> {
+    fn send<'a>(&'a self, symbol: &'a Symbol, msg: QuoteResponse) -> BoxFuture<'a, bool> {
+        let sent = match self.job(symbol, &msg) {
+            Some(job) => self.jobs.send(job).is_ok(),
+            None => true,
+        };
+        Box::pin(async move { sent })
+    }
+
+    fn fail<'a>(&'a self, _: &'a Symbol, err: FugleError) -> BoxFuture<'a, ()> {
+        let _ = self.jobs.send(Job::Fail(err));
+        Box::pin(async {})
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::schema::DealtsResponse;
+
+    fn quote(transaction: u64, serial: u64) -> QuoteResponse {
+        let mut quote = QuoteResponse::default();
+        quote.data.quote.total.transaction = transaction;
+        quote.data.quote.trade.serial = serial;
+        quote
+    }
+
+    fn fixture() -> Vec<Dealt> {
+        let dealts: DealtsResponse =
+            serde_json::from_str(include_str!("../../../tests/testdata/dealts_response.json"))
+                .unwrap();
+        dealts.data.dealts
+    }
+
+    #[test]
+    fn test_tracker_advance() {
+        let tracker = Tracker::default();
+        let (a, b) = (Symbol::new("2884"), Symbol::new("2330"));
+
+        assert_eq!(tracker.advance(&a, &quote(0, 0)), None);
+        let first = tracker.advance(&a, &quote(1, 5961786)).unwrap();
+        assert_eq!(first.missing(), 0);
+        // the same trade repeated by an order book update.
+        assert_eq!(tracker.advance(&a, &quote(1, 5961786)), None);
+        // the serials of successive trades are not contiguous.
+        assert_eq!(tracker.advance(&a, &quote(2, 5965356)).unwrap().missing(), 0);
+        let gap = tracker.advance(&a, &quote(4, 6009860)).unwrap();
+        assert_eq!(
+            gap.last,
+            Some(Mark {
+                date: Date::MIN,
+                transaction: 2,
+                serial: 5965356
+            })
+        );
+        assert_eq!(gap.missing(), 1);
+        assert_eq!(tracker.advance(&b, &quote(7, 1)).unwrap().missing(), 0);
+    }
+
+    #[test]
+    fn test_tracker_new_day() {
+        let tracker = Tracker::default();
+        let symbol = Symbol::new("2884");
+        let (today, tomorrow) = (
+            Date::from_calendar_date(2022, time::Month::August, 1).unwrap(),
+            Date::from_calendar_date(2022, time::Month::August, 2).unwrap(),
+        );
+        let on = |date, transaction, serial| {
+            let mut quote = quote(transaction, serial);
+            quote.data.info.date = date;
+            quote
+        };
+
+        tracker.advance(&symbol, &on(today, 1190, 5961786)).unwrap();
+        // the serials and transactions start over, the first trade is not a repeat.
+        let step = tracker.advance(&symbol, &on(tomorrow, 3, 30)).unwrap();
+        assert_eq!(step.last, None);
+        assert_eq!(step.missing(), 0);
+        // a late quote of the day before is dropped.
+        assert_eq!(tracker.advance(&symbol, &on(today, 1191, 5961790)), None);
+        assert_eq!(tracker.advance(&symbol, &on(tomorrow, 5, 50)).unwrap().missing(), 1);
+    }
+
+    #[test]
+    fn test_backfill_fixture() {
+        // the fixture lists the trades 6009860, 5965884, 5965356, 5961786 and 5959512,
+        // the stream went from 5961786 to 6009860 with two trades skipped.
+        let dealts = fixture();
+        let tracker = Tracker::default();
+        let symbol = Symbol::new("2884");
+        tracker.advance(&symbol, &quote(1190, 5961786)).unwrap();
+        let step = tracker.advance(&symbol, &quote(1193, 6009860)).unwrap();
+        assert_eq!(step.missing(), 2);
+
+        let mut backfill = Backfill::new(&step);
+        assert_eq!(backfill.limit, 2);
+        assert!(!backfill.take(dealts[..1].to_vec()));
+        assert!(backfill.take(dealts[1..3].to_vec()));
+        assert_eq!(backfill.offset, 3);
+
+        let serials: Vec<u64> = backfill.finish().iter().map(|d| d.serial).collect();
+        assert_eq!(serials, vec![5965356, 5965884]);
+    }
+
+    #[test]
+    fn test_backfill_reached_last() {
+        // fewer dealts than counted, the walk stops at the last trade.
+        let dealts = fixture();
+        let mut backfill = Backfill::new(&Step {
+            last: Some(Mark {
+                date: Date::MIN,
+                transaction: 10,
+                serial: 5961786,
+            }),
+            mark: Mark {
+                date: Date::MIN,
+                transaction: 20,
+                serial: 6009860,
+            },
+        });
+        assert!(backfill.take(dealts));
+        assert_eq!(backfill.finish().len(), 2);
+    }
+
+    #[test]
+    fn test_backfill_empty_page() {
+        let mut backfill = Backfill::new(&Step {
+            last: Some(Mark {
+                date: Date::MIN,
+                transaction: 1,
+                serial: 1,
+            }),
+            mark: Mark {
+                date: Date::MIN,
+                transaction: 1000,
+                serial: 5000,
+            },
+        });
+        assert_eq!(backfill.limit, MAX_PAGE_LIMIT);
+        assert!(backfill.take(vec![]));
+        assert!(backfill.finish().is_empty());
+    }
+
+    #[test]
+    fn test_job_deliver_order() {
+        let dealts = fixture();
+        let symbol = Symbol::new("2884");
+        let job = Job::Trade {
+            symbol: symbol.clone(),
+            step: Step {
+                last: None,
+                mark: Mark {
+                    date: Date::MIN,
+                    transaction: 1,
+                    serial: dealts[0].serial,
+                },
+            },
+            dealt: dealts[0].clone(),
+        };
+
+        let delivered = std::cell::RefCell::new(vec![]);
+        assert!(job.deliver(Some(Ok(dealts[1..3].to_vec())), |trade| {
+            let trade = trade.unwrap();
+            delivered
+                .borrow_mut()
+                .push((trade.dealt.serial, trade.backfilled));
+            true
+        }));
+        assert_eq!(
+            delivered.into_inner(),
+            vec![(5965884, true), (5965356, true), (6009860, false)]
+        );
+    }
+}
@@ -0,0 +1,287 @@
+#[cfg(feature = "async-websocket")]
+use std::future::Future;
+use std::sync::Arc;
+
+#[cfg(feature = "async-websocket")]
+use futures_util::future::BoxFuture;
+
+#[cfg(feature = "async-websocket")]
+use super::AsyncSink;
+#[cfg(feature = "websocket")]
+use super::BlockSink;
+use super::{Channel, Symbol};
+use crate::{
+    errors::FugleError,
+    schema::{ChartResponse, MetaResponse, QuoteResponse},
+};
+
+/// A change of the connection of a stock on an endpoint.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ConnectionEvent {
+    Connected,
+    Disconnected,
+}
+
+/// Callbacks driven by the workers of
+/// [`Intraday::run`](super::Intraday::run),
+/// every method does nothing by default.
+///
+/// The callbacks of all the workers are called one at a time.
+#[cfg(feature = "websocket")]
+pub trait Handler: Send + 'static {
+    fn on_chart(&mut self, _symbol: &Symbol, _chart: ChartResponse) {}
+
+    fn on_quote(&mut self, _symbol: &Symbol, _quote: QuoteResponse) {}
+
+    fn on_meta(&mut self, _symbol: &Symbol, _meta: MetaResponse) {}
+
+    fn on_error(&mut self, _symbol: &Symbol, _channel: Channel, _err: FugleError) {}
+
+    fn on_connection_event(
+        &mut self,
+        _symbol: &Symbol,
+        _channel: Channel,
+        _event: ConnectionEvent,
+    ) {
+    }
+}
+
+/// Callbacks driven by the workers of
+/// [`Intraday::async_run`](super::Intraday::async_run),
+/// every method does nothing by default.
+///
+/// The callbacks of all the workers are awaited one at a time,
+/// the methods can be implemented as `async fn`.
+#[cfg(feature = "async-websocket")]
+pub trait AsyncHandler: Send + 'static {
+    fn on_chart(
+        &mut self,
+        _symbol: &Symbol,
+        _chart: ChartResponse,
+    ) -> impl Future<Output = ()> + Send {
+        async {}
+    }
+
+    fn on_quote(
+        &mut self,
+        _symbol: &Symbol,
+        _quote: QuoteResponse,
+    ) -> impl Future<Output = ()> + Send {
+        async {}
+    }
+
+    fn on_meta(
+        &mut self,
+        _symbol: &Symbol,
+        _meta: MetaResponse,
+    ) -> impl Future<Output = ()> + Send {
+        async {}
+    }
+
+    fn on_error(
+        &mut self,
+        _symbol: &Symbol,
+        _channel: Channel,
+        _err: FugleError,
+    ) -> impl Future<Output = ()> + Send {
+        async {}
+    }
+
+    fn on_connection_event(
+        &mut self,
+        _symbol: &Symbol,
+        _channel: Channel,
+        _event: ConnectionEvent,
+    ) -> impl Future<Output = ()> + Send {
+        async {}
+    }
+}
+
+// picks the callback of the handler for each kind of response.
+pub(crate) trait Dispatch: Send + 'static {
+    #[cfg(feature = "websocket")]
+    fn dispatch<H: Handler>(self, handler: &mut H, symbol: &Symbol);
+
+    #[cfg(feature = "async-websocket")]
+    fn async_dispatch<'a, H: AsyncHandler>(
+        self,
+        handler: &'a mut H,
+        symbol: &'a Symbol,
+    ) -> BoxFuture<'a, ()>;
+}
+
+macro_rules! impl_dispatch {
+    ($response:ty, $callback:ident) => {
+        impl Dispatch for $response {
+            #[cfg(feature = "websocket")]
+            fn dispatch<H: Handler>(self, handler: &mut H, symbol: &Symbol) {
+                handler.$callback(symbol, self)
+            }
+
+            #[cfg(feature = "async-websocket")]
+            fn async_dispatch<'a, H: AsyncHandler>(
+                self,
+                handler: &'a mut H,
+                symbol: &'a Symbol,
+            ) -> BoxFuture<'a, ()> {
+                Box::pin(handler.$callback(symbol, self))
+            }
+        }
+    };
+}
+
+impl_dispatch!(ChartResponse, on_chart);
+impl_dispatch!(QuoteResponse, on_quote);
+impl_dispatch!(MetaResponse, on_meta);
+
+// one sink per channel, all of them share the same handler.
+pub(crate) struct HandlerSink<M> {
+    handler: Arc<M>,
+    channel: Channel,
+}
+
+impl<M> HandlerSink<M> {
+    pub(crate) fn new(handler: Arc<M>, channel: Channel) -> Self {
+        HandlerSink { handler, channel }
+    }
+}
+
+#[cfg(feature = "websocket")]
+impl<H: Handler, T: Dispatch> BlockSink<T> for HandlerSink<std::sync::Mutex<H>> {
+    fn send(&self, symbol: &Symbol, msg: T) -> bool {
+        match self.handler.lock() {
+            Ok(mut handler) => {
+                msg.dispatch(&mut *handler, symbol);
+                true
+            }
+            // a callback has panicked, the handler is not usable anymore.
+            Err(_) => false,
+        }
+    }
+
+    fn fail(&self, symbol: &Symbol, err: FugleError) {
+        if let Ok(mut handler) = self.handler.lock() {
+            handler.on_error(symbol, self.channel, err);
+        }
+    }
+
+    fn connection(&self, symbol: &Symbol, event: ConnectionEvent) {
+        if let Ok(mut handler) = self.handler.lock() {
+            handler.on_connection_event(symbol, self.channel, event);
+        }
+    }
+
+    fn may_block(&self) -> bool {
+        true
+    }
+}
+
+#[cfg(feature = "async-websocket")]
+impl<H: AsyncHandler, T: Dispatch> AsyncSink<T> for HandlerSink<tokio::sync::Mutex<H>> {
+    fn send<'a>(&'a self, symbol: &'a Symbol, msg: T) -> BoxFuture<'a, bool> {
+        Box::pin(async move {
+            let mut handler = self.handler.lock().await;
+            msg.async_dispatch(&mut *handler, symbol).await;
+            true
+        })
+    }
+
+    fn fail<'a>(&'a self, symbol: &'a Symbol, err: FugleError) -> BoxFuture<'a, ()> {
+        Box::pin(async move {
+            let mut handler = self.handler.lock().await;
+            handler.on_error(symbol, self.channel, err).await;
+        })
+    }
+
+    fn connection<'a>(&'a self, symbol: &'a Symbol, event: ConnectionEvent) -> BoxFuture<'a, ()> {
+        Box::pin(async move {
+            let mut handler = self.handler.lock().await;
+            handler
+                .on_connection_event(symbol, self.channel, event)
+                .await;
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{super::testing, *};
+
+    #[derive(Default)]
+    struct Recorder(Vec<String>);
+
+    #[cfg(feature = "websocket")]
+    impl Handler for Recorder {
+        fn on_quote(&mut self, symbol: &Symbol, quote: QuoteResponse) {
+            self.0
+                .push(format!("{} {}", symbol.id, quote.data.info.symbol_id));
+        }
+
+        fn on_error(&mut self, _: &Symbol, channel: Channel, _: FugleError) {
+            self.0.push(format!("{:?} error", channel));
+        }
+
+        fn on_connection_event(&mut self, _: &Symbol, _: Channel, event: ConnectionEvent) {
+            self.0.push(format!("{:?}", event));
+        }
+    }
+
+    #[cfg(feature = "async-websocket")]
+    impl AsyncHandler for Recorder {
+        async fn on_quote(&mut self, symbol: &Symbol, quote: QuoteResponse) {
+            self.0
+                .push(format!("{} {}", symbol.id, quote.data.info.symbol_id));
+        }
+
+        async fn on_error(&mut self, _: &Symbol, channel: Channel, _: FugleError) {
+            self.0.push(format!("{:?} error", channel));
+        }
+
+        async fn on_connection_event(&mut self, _: &Symbol, _: Channel, event: ConnectionEvent) {
+            self.0.push(format!("{:?}", event));
+        }
+    }
+
+    fn expected() -> Vec<String> {
+        vec![
+            "Connected".to_string(),
+            "2884 2884".to_string(),
+            "Quote error".to_string(),
+            "Disconnected".to_string(),
+        ]
+    }
+
+    fn frames() -> Vec<testing::Frame> {
+        vec![testing::quote("2884"), testing::text("not a json")]
+    }
+
+    #[cfg(feature = "websocket")]
+    #[test]
+    fn test_handler_callbacks() {
+        use std::sync::Mutex;
+
+        let (uri, _closed) = testing::server(1, |_| frames(), true);
+        let handler = Arc::new(Mutex::new(Recorder::default()));
+        let sink: Arc<dyn BlockSink<QuoteResponse>> =
+            Arc::new(HandlerSink::new(handler.clone(), Channel::Quote));
+        let mut worker = testing::worker(&uri, sink);
+
+        assert!(worker.wait().is_clean());
+        assert_eq!(handler.lock().unwrap().0, expected());
+    }
+
+    #[cfg(feature = "async-websocket")]
+    #[tokio::test]
+    async fn test_async_handler_callbacks() {
+        use tokio::sync::Mutex;
+
+        let (uri, _closed) = testing::async_server(frames(), true).await;
+        let handler = Arc::new(Mutex::new(Recorder::default()));
+        let sink: Arc<dyn AsyncSink<QuoteResponse>> =
+            Arc::new(HandlerSink::new(handler.clone(), Channel::Quote));
+        let mut worker = testing::async_worker(&uri, sink).await;
+
+        assert!(worker.wait().await.is_clean());
+        assert_eq!(handler.lock().await.0, expected());
+    }
+}
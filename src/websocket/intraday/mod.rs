@@ -8,27 +8,218 @@ mod r#async;
 #[cfg(feature = "async-websocket")]
 use r#async::Async as AsyncWorker;
 
+mod delta;
+use delta::DeltaSink;
+pub use delta::{QuoteDelta, QuoteDiff, StatusChange};
+
+mod envelope;
+pub use envelope::Envelope;
+use envelope::EnvelopeSink;
+
+mod event;
+pub use event::Event;
+use event::EventSink;
+
+// the items built on the REST client of the websocket flavour,
+// or the ones standing in for them without it when led by `not`.
+macro_rules! cfg_rest {
+    (not $($item:item)*) => {
+        $(
+            #[cfg(not(any(
+                all(feature = "websocket", feature = "query"),
+                all(feature = "async-websocket", feature = "async-query")
+            )))]
+            $item
+        )*
+    };
+    ($($item:item)*) => {
+        $(
+            #[cfg(any(
+                all(feature = "websocket", feature = "query"),
+                all(feature = "async-websocket", feature = "async-query")
+            ))]
+            $item
+        )*
+    };
+}
+
+cfg_rest! {
+    mod gap;
+    use gap::GapSink;
+    pub use gap::Trade;
+
+    mod feed;
+    pub use feed::Feed;
+    use feed::FeedSink;
+
+    mod fallback;
+    use fallback::Fallback;
+
+    // the responses the fallback workers poll.
+    use feed::Snapshot as Polled;
+}
+
+cfg_rest! {
+    not
+    trait Polled {}
+    impl<T> Polled for T {}
+}
+
+mod handler;
+#[cfg(feature = "async-websocket")]
+pub use handler::AsyncHandler;
+pub use handler::ConnectionEvent;
+#[cfg(feature = "websocket")]
+pub use handler::Handler;
+use handler::HandlerSink;
+
+mod budget;
+use budget::Budget;
+pub use budget::ConnectionUsage;
+
+mod connect;
+use connect::Connector;
+
+mod record;
+use record::{Recorder, Tap};
+
+#[cfg(feature = "websocket")]
+mod mux;
+#[cfg(feature = "websocket")]
+use mux::Mux;
+
+mod replay;
+pub use replay::{Pace, Replay};
+
+#[cfg(test)]
+mod testing;
+
+mod sink;
+#[cfg(feature = "async-websocket")]
+mod stream;
+#[cfg(feature = "async-websocket")]
+use sink::AsyncSink;
+#[cfg(feature = "websocket")]
+use sink::BlockSink;
+#[cfg(feature = "async-websocket")]
+use stream::StreamSink;
+#[cfg(feature = "async-websocket")]
+pub use stream::Subscription;
+
 #[cfg(feature = "websocket")]
 use std::sync::mpsc::{channel, Receiver};
 #[cfg(feature = "async-websocket")]
-use tokio::sync::mpsc::{unbounded_channel, UnboundedReceiver};
+use tokio::sync::{
+    broadcast,
+    mpsc::{unbounded_channel, UnboundedReceiver},
+};
+
+#[cfg(feature = "websocket")]
+use std::sync::Mutex;
+use std::{
+    path::Path,
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+use serde::{Deserialize, Serialize};
 
-use std::sync::{
-    atomic::{AtomicBool, Ordering},
-    Arc,
+#[cfg(all(feature = "websocket", feature = "query"))]
+use crate::http::{BlockRequest, RestfulBuilder};
+#[cfg(feature = "websocket")]
+use crate::websocket::broadcast::Broadcast;
+#[cfg(all(feature = "async-websocket", feature = "async-query"))]
+use crate::websocket::runtime::Rest;
+#[cfg(feature = "async-websocket")]
+use crate::websocket::runtime::{Executor, Spawner};
+use crate::{
+    errors::FugleError,
+    schema::{ChartResponse, Info, MetaResponse, QuoteResponse, Result, SecurityType},
+    websocket::bounded::{self, Overflow},
 };
 
-use crate::schema::{ChartResponse, MetaResponse, QuoteResponse, Result};
+const INTRADAY_URL: &str = "wss://api.fugle.tw/realtime/v0.3/intraday";
+
+// how long the workers replaced or dropped may take to stop.
+const DROP_TIMEOUT: Duration = Duration::from_secs(3);
+
+/// A stock to listen on, fugle serves the odd lot trading on its own streams.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Symbol {
+    pub id: String,
+    pub odd_lot: bool,
+}
+
+impl Symbol {
+    /// Returns a Symbol of the regular trading.
+    pub fn new(id: &str) -> Symbol {
+        Symbol {
+            id: id.to_string(),
+            odd_lot: false,
+        }
+    }
+
+    /// Returns a Symbol of the odd lot trading.
+    pub fn odd_lot(id: &str) -> Symbol {
+        Symbol {
+            id: id.to_string(),
+            odd_lot: true,
+        }
+    }
+}
+
+impl From<&str> for Symbol {
+    fn from(id: &str) -> Symbol {
+        Symbol::new(id)
+    }
+}
+
+impl From<&Info> for Symbol {
+    /// Returns the Symbol a response belongs to.
+    fn from(info: &Info) -> Symbol {
+        Symbol {
+            id: info.symbol_id.clone(),
+            odd_lot: info.typ == SecurityType::OddLot,
+        }
+    }
+}
+
+/// The fugle websocket endpoints.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Channel {
+    Chart,
+    Quote,
+    Meta,
+}
 
-const INTRADAY_CHART: &str = "wss://api.fugle.tw/realtime/v0.3/intraday/chart";
-const INTRADAY_QUOTE: &str = "wss://api.fugle.tw/realtime/v0.3/intraday/quote";
-const INTRADAY_META: &str = "wss://api.fugle.tw/realtime/v0.3/intraday/meta";
+impl Channel {
+    fn path(&self) -> &'static str {
+        match *self {
+            Channel::Chart => "chart",
+            Channel::Quote => "quote",
+            Channel::Meta => "meta",
+        }
+    }
+}
 
 /// Accumulates options towards building an Intraday instance of WebSocket.
 pub struct IntradayBuilder<'a> {
     token: &'a str,
     symbol_id: &'a str,
     is_odd_lot: bool,
+    symbols: Vec<Symbol>,
+    url: &'a str,
+    connection_limit: Option<usize>,
+    #[cfg(feature = "websocket")]
+    multiplexed: bool,
+    // the polling interval and the reconnect period.
+    #[cfg(any(
+        all(feature = "websocket", feature = "query"),
+        all(feature = "async-websocket", feature = "async-query")
+    ))]
+    fallback: Option<(Duration, Duration)>,
+    connector: Connector,
 }
 
 impl<'a> Default for IntradayBuilder<'a> {
@@ -47,6 +238,17 @@ impl<'a> IntradayBuilder<'a> {
             token: "demo",
             symbol_id: "",
             is_odd_lot: false,
+            symbols: vec![],
+            url: INTRADAY_URL,
+            connection_limit: None,
+            #[cfg(feature = "websocket")]
+            multiplexed: false,
+            #[cfg(any(
+                all(feature = "websocket", feature = "query"),
+                all(feature = "async-websocket", feature = "async-query")
+            ))]
+            fallback: None,
+            connector: Connector::default(),
         }
     }
 
@@ -65,7 +267,7 @@ impl<'a> IntradayBuilder<'a> {
     ///     .token("b52153ae36747b17c8bdee801da19542")
     ///     .build();
     /// ```
-    pub fn token(mut self, token: &'a str) -> IntradayBuilder {
+    pub fn token(mut self, token: &'a str) -> IntradayBuilder<'a> {
         self.token = token;
         self
     }
@@ -80,7 +282,7 @@ impl<'a> IntradayBuilder<'a> {
     ///     .symbol_id("b52153ae36747b17c8bdee801da19542")
     ///     .build();
     /// ```
-    pub fn symbol_id(mut self, symbol_id: &'a str) -> IntradayBuilder {
+    pub fn symbol_id(mut self, symbol_id: &'a str) -> IntradayBuilder<'a> {
         self.symbol_id = symbol_id;
         self
     }
@@ -100,209 +302,1967 @@ impl<'a> IntradayBuilder<'a> {
         self
     }
 
-    /// Returns an Intraday instance.
+    /// Add one more stock to receive WebSocket data,
+    /// each stock comes with its own odd lot option.
     ///
-    /// When listening on each endpoint,
-    /// Intraday will fork a thread to do the listening job,
-    /// so need to use mpsc::channel receiver to receive response data.
+    /// Stocks added here are listened together with the one set by symbol_id.
     ///
-    /// And as a daemon like process, it won't break while any error ocurs,
-    /// instead it will log the error.
+    /// # Example:
     ///
-    /// Please reference to below link to know how to print the log out.
-    /// https://github.com/rust-lang/log
+    /// ```
+    /// # use fugle::websocket::{IntradayBuilder, Symbol};
+    /// let ws = IntradayBuilder::new()
+    ///     .symbol(Symbol::new("2884"))
+    ///     .symbol(Symbol::odd_lot("2330"))
+    ///     .build();
+    /// ```
+    pub fn symbol(mut self, symbol: Symbol) -> IntradayBuilder<'a> {
+        self.symbols.push(symbol);
+        self
+    }
+
+    /// Set the base url of the websocket endpoints,
+    /// the channel path like "/quote" is appended to it.
     ///
-    /// Example:
+    /// By default it is "wss://api.fugle.tw/realtime/v0.3/intraday".
     ///
-    /// ```
-    /// # use fugle::websocket;
+    /// # Example:
     ///
-    /// let mut ws = websocket::IntradayBuilder::new().build();
     /// ```
-    pub fn build(self) -> Intraday {
-        Intraday {
-            uri: format!(
-                "symbolId={}&apiToken={}&oddLot={}",
-                self.symbol_id, self.token, self.is_odd_lot,
-            ),
-            workers: vec![],
-            done: Arc::new(AtomicBool::new(false)),
-        }
+    /// # use fugle::websocket::IntradayBuilder;
+    /// let ws = IntradayBuilder::new()
+    ///     .url("ws://127.0.0.1:8080/realtime/v0.3/intraday")
+    ///     .build();
+    /// ```
+    pub fn url(mut self, url: &'a str) -> IntradayBuilder<'a> {
+        self.url = url;
+        self
     }
-}
-
-/// Intraday is the Websocket listener to fugle wws endpoints.
-pub struct Intraday {
-    uri: String,
-    workers: Vec<Box<dyn Worker>>,
-    done: Arc<AtomicBool>,
-}
 
-impl Intraday {
-    /// Listening fugle Chart endpoint.
+    /// Connect through an HTTP CONNECT proxy in "host:port".
     ///
-    /// Example:
+    /// # Example:
     ///
-    /// ```no_run
-    /// # fn main() -> fugle::schema::Result<()> {
+    /// ```
     /// # use fugle::websocket::IntradayBuilder;
+    /// let ws = IntradayBuilder::new()
+    ///     .proxy("proxy.internal:3128")
+    ///     .build();
+    /// ```
+    pub fn proxy(mut self, proxy: &'a str) -> IntradayBuilder<'a> {
+        let proxy = proxy.trim_start_matches("http://").trim_end_matches('/');
+        self.connector.proxy = Some(proxy.to_string());
+        self
+    }
+
+    /// Trust the PEM encoded root certificates besides the webpki ones,
+    /// e.g. the certificate of a private CA.
     ///
-    /// let mut ws = IntradayBuilder::new().symbol_id("2884").odd_lot().build();
+    /// A PEM without any certificate fails the connecting.
     ///
-    /// let rx = ws.chart()?;
-    /// let response = rx.recv()?;
+    /// # Example:
     ///
-    /// # Ok(())
-    /// # }
+    /// ```no_run
+    /// # use fugle::websocket::IntradayBuilder;
+    /// let pem = std::fs::read("private-ca.pem").unwrap();
+    /// let ws = IntradayBuilder::new()
+    ///     .root_certificate(&pem)
+    ///     .build();
     /// ```
-    #[cfg(feature = "websocket")]
-    pub fn chart(&mut self) -> Result<Receiver<ChartResponse>> {
-        let (tx, rx) = channel();
-        let uri = &format!("{}?{}", INTRADAY_CHART, self.uri);
-        let worker = BlockWorker::new(uri, tx, self.done.clone())?;
-        self.workers.push(Box::new(worker));
-        Ok(rx)
+    pub fn root_certificate(mut self, pem: &[u8]) -> IntradayBuilder<'a> {
+        self.connector.root_certificates.push(pem.to_vec());
+        self
     }
 
-    /// Listening fugle Chart endpoint.
+    /// Setup how long connecting, including the proxy and websocket handshakes, may take.
     ///
-    /// Example:
+    /// # Example:
     ///
-    /// ```no_run
-    /// # #[tokio::main]
-    /// # async fn main() -> fugle::schema::Result<()> {
+    /// ```
+    /// # use std::time::Duration;
     /// # use fugle::websocket::IntradayBuilder;
+    /// let ws = IntradayBuilder::new()
+    ///     .connect_timeout(Duration::from_secs(5))
+    ///     .build();
+    /// ```
+    pub fn connect_timeout(mut self, timeout: Duration) -> IntradayBuilder<'a> {
+        self.connector.connect_timeout = Some(timeout);
+        self
+    }
+
+    /// Add a header to the websocket handshake request.
     ///
-    /// let mut ws = IntradayBuilder::new().symbol_id("2884").odd_lot().build();
-    ///
-    /// let mut rx = ws.async_chart().await?;
-    /// let response = rx.recv().await;
+    /// # Example:
     ///
-    /// # Ok(())
-    /// # }
     /// ```
-    #[cfg(feature = "async-websocket")]
-    pub async fn async_chart(&mut self) -> Result<UnboundedReceiver<ChartResponse>> {
-        let (tx, rx) = unbounded_channel();
-        let uri = &format!("{}?{}", INTRADAY_CHART, self.uri);
-        let worker = AsyncWorker::new(uri, tx, self.done.clone()).await?;
-        self.workers.push(Box::new(worker));
-        Ok(rx)
+    /// # use fugle::websocket::IntradayBuilder;
+    /// let ws = IntradayBuilder::new()
+    ///     .header("User-Agent", "my-bot/1.0")
+    ///     .build();
+    /// ```
+    pub fn header(mut self, name: &str, value: &str) -> IntradayBuilder<'a> {
+        self.connector
+            .headers
+            .push((name.to_string(), value.to_string()));
+        self
     }
 
-    /// Listening fugle Meta endpoint.
+    /// Spawn the async workers by the executor instead of the tokio runtime,
+    /// see [`Executor`](crate::websocket::Executor).
     ///
-    /// Example:
+    /// # Example:
     ///
-    /// ```no_run
-    /// # fn main() -> fugle::schema::Result<()> {
-    /// # use fugle::websocket::IntradayBuilder;
+    /// ```
+    /// # use fugle::websocket::{IntradayBuilder, Tokio};
+    /// let ws = IntradayBuilder::new()
+    ///     .executor(Tokio)
+    ///     .build();
+    /// ```
+    #[cfg(feature = "async-websocket")]
+    pub fn executor<E: Executor>(mut self, executor: E) -> IntradayBuilder<'a> {
+        self.connector.spawner = Spawner(Arc::new(executor));
+        self
+    }
+
+    /// Read the sockets of all the blocking workers on one background thread,
+    /// instead of a thread per stock and endpoint, the receivers stay the same.
     ///
-    /// let mut ws = IntradayBuilder::new().symbol_id("2884").odd_lot().build();
+    /// The responses are delivered on that thread as well, so the listeners which may wait
+    /// while delivering keep a thread per stock and endpoint, that is the handlers of `run`,
+    /// the bounded receivers with `Overflow::Block` and the broadcasts,
+    /// whose consumers may subscribe with `Overflow::Block` later.
     ///
-    /// let rx = ws.meta()?;
-    /// let response = rx.recv()?;
+    /// # Example:
     ///
-    /// # Ok(())
-    /// # }
+    /// ```
+    /// # use fugle::websocket::{IntradayBuilder, Symbol};
+    /// let ws = IntradayBuilder::new()
+    ///     .symbol(Symbol::new("2884"))
+    ///     .symbol(Symbol::new("2330"))
+    ///     .multiplexed()
+    ///     .build();
     /// ```
     #[cfg(feature = "websocket")]
-    pub fn meta(&mut self) -> Result<Receiver<MetaResponse>> {
-        let (tx, rx) = channel();
-        let uri = &format!("{}?{}", INTRADAY_META, self.uri);
-        let worker = BlockWorker::new(uri, tx, self.done.clone())?;
-        self.workers.push(Box::new(worker));
-        Ok(rx)
+    pub fn multiplexed(mut self) -> IntradayBuilder<'a> {
+        self.multiplexed = true;
+        self
     }
 
-    /// Listening fugle Meta endpoint.
+    /// Setup how many websocket connections the token may open at the same time,
+    /// fugle serves one connection per stock and channel.
     ///
-    /// Example:
-    ///
-    /// ```no_run
-    /// # #[tokio::main]
-    /// # async fn main() -> fugle::schema::Result<()> {
-    /// # use fugle::websocket::IntradayBuilder;
+    /// The limit is shared by every Intraday of the same token in the process,
+    /// and the one built latest wins. By default the connections are not limited.
     ///
-    /// let mut ws = IntradayBuilder::new().symbol_id("2884").odd_lot().build();
+    /// Connecting beyond the limit returns ConnectionLimitExceeded error,
+    /// unless the connections are queued by queue_connections.
     ///
-    /// let mut rx = ws.async_meta().await?;
-    /// let response = rx.recv().await;
+    /// # Example:
     ///
-    /// # Ok(())
-    /// # }
     /// ```
-    #[cfg(feature = "async-websocket")]
-    pub async fn async_meta(&mut self) -> Result<UnboundedReceiver<MetaResponse>> {
-        let (tx, rx) = unbounded_channel();
-        let uri = &format!("{}?{}", INTRADAY_META, self.uri);
-        let worker = AsyncWorker::new(uri, tx, self.done.clone()).await?;
-        self.workers.push(Box::new(worker));
-        Ok(rx)
+    /// # use fugle::websocket::IntradayBuilder;
+    /// let ws = IntradayBuilder::new()
+    ///     .connection_limit(5)
+    ///     .build();
+    /// ```
+    pub fn connection_limit(mut self, limit: usize) -> IntradayBuilder<'a> {
+        self.connection_limit = Some(limit);
+        self
     }
 
-    /// Listening fugle Quote endpoint.
+    /// Wait up to the timeout for a connection to be freed when the token has reached its limit,
+    /// instead of returning ConnectionLimitExceeded error at once.
     ///
-    /// Example:
+    /// # Example:
     ///
-    /// ```no_run
-    /// # fn main() -> fugle::schema::Result<()> {
+    /// ```
+    /// # use std::time::Duration;
     /// # use fugle::websocket::IntradayBuilder;
+    /// let ws = IntradayBuilder::new()
+    ///     .connection_limit(5)
+    ///     .queue_connections(Duration::from_secs(10))
+    ///     .build();
+    /// ```
+    pub fn queue_connections(mut self, timeout: Duration) -> IntradayBuilder<'a> {
+        self.connector.queue = Some(timeout);
+        self
+    }
+
+    /// Poll the REST endpoint of a stock and endpoint every interval
+    /// while its websocket cannot connect, e.g. beyond the connection limit or behind a firewall,
+    /// delivering the same responses on the same receivers.
     ///
-    /// let mut ws = IntradayBuilder::new().symbol_id("2884").odd_lot().build();
+    /// The websocket is tried again after every reconnect period of polling,
+    /// and a closed websocket falls back to polling as well.
+    /// Only the polled responses newer than the last polled one are delivered.
     ///
-    /// let rx = ws.quote()?;
-    /// let response = rx.recv()?;
+    /// The blocking workers need the query feature and the async ones the async-query feature,
+    /// listening without it fails with FeatureRequired error,
+    /// and the polling workers are not multiplexed.
+    /// A first connection failing on a malformed url or a rejected token
+    /// fails the listening instead of polling.
+    /// The async workers are started inside a tokio runtime where their polls run,
+    /// see [`Executor`](crate::websocket::Executor).
+    ///
+    /// # Example:
     ///
-    /// # Ok(())
-    /// # }
     /// ```
-    #[cfg(feature = "websocket")]
-    pub fn quote(&mut self) -> Result<Receiver<QuoteResponse>> {
-        let (tx, rx) = channel();
-        let uri = &format!("{}?{}", INTRADAY_QUOTE, self.uri);
-        let worker = BlockWorker::new(uri, tx, self.done.clone())?;
-        self.workers.push(Box::new(worker));
-        Ok(rx)
+    /// # use std::time::Duration;
+    /// # use fugle::websocket::IntradayBuilder;
+    /// let ws = IntradayBuilder::new()
+    ///     .symbol_id("2884")
+    ///     .rest_fallback(Duration::from_secs(5), Duration::from_secs(60))
+    ///     .build();
+    /// ```
+    #[cfg(any(
+        all(feature = "websocket", feature = "query"),
+        all(feature = "async-websocket", feature = "async-query")
+    ))]
+    pub fn rest_fallback(mut self, interval: Duration, reconnect: Duration) -> IntradayBuilder<'a> {
+        self.fallback = Some((interval, reconnect));
+        self
     }
 
-    /// Listening fugle Quote endpoint.
+    /// Returns an Intraday instance.
     ///
-    /// Example:
+    /// When listening on each endpoint,
+    /// Intraday will fork a thread for every stock to do the listening job,
+    /// so need to use mpsc::channel receiver to receive response data.
     ///
-    /// ```no_run
-    /// # #[tokio::main]
-    /// # async fn main() -> fugle::schema::Result<()> {
-    /// # use fugle::websocket::IntradayBuilder;
+    /// And as a daemon like process, it won't break while any error ocurs,
+    /// instead it will log the error.
     ///
-    /// let mut ws = IntradayBuilder::new().symbol_id("2884").odd_lot().build();
+    /// Please reference to below link to know how to print the log out.
+    /// https://github.com/rust-lang/log
     ///
-    /// let mut rx = ws.async_quote().await?;
-    /// let response = rx.recv().await;
+    /// Example:
     ///
-    /// # Ok(())
-    /// # }
     /// ```
-    #[cfg(feature = "async-websocket")]
-    pub async fn async_quote(&mut self) -> Result<UnboundedReceiver<QuoteResponse>> {
-        let (tx, rx) = unbounded_channel();
-        let uri = &format!("{}?{}", INTRADAY_QUOTE, self.uri);
-        let worker = AsyncWorker::new(uri, tx, self.done.clone()).await?;
-        self.workers.push(Box::new(worker));
-        Ok(rx)
-    }
-}
+    /// # use fugle::websocket;
+    ///
+    /// let mut ws = websocket::IntradayBuilder::new().build();
+    /// ```
+    pub fn build(mut self) -> Intraday {
+        self.connector.budget = Budget::of(self.token);
+        if let Some(limit) = self.connection_limit {
+            self.connector.budget.set_limit(limit);
+        }
 
-impl Drop for Intraday {
-    fn drop(&mut self) {
-        self.done.store(true, Ordering::SeqCst);
+        let mut symbols = Vec::with_capacity(self.symbols.len() + 1);
+        if !self.symbol_id.is_empty() {
+            symbols.push(Symbol {
+                id: self.symbol_id.to_string(),
+                odd_lot: self.is_odd_lot,
+            });
+        }
+        for symbol in self.symbols {
+            if !symbols.contains(&symbol) {
+                symbols.push(symbol);
+            }
+        }
+
+        Intraday {
+            token: self.token.to_string(),
+            symbols,
+            url: self.url.to_string(),
+            connector: self.connector,
+            recorder: Arc::default(),
+            #[cfg(feature = "websocket")]
+            multiplexed: self.multiplexed,
+            #[cfg(feature = "websocket")]
+            mux: Mutex::default(),
+            #[cfg(any(
+                all(feature = "websocket", feature = "query"),
+                all(feature = "async-websocket", feature = "async-query")
+            ))]
+            fallback: self.fallback.map(|(interval, reconnect)| Fallback {
+                interval,
+                reconnect,
+            }),
+            #[cfg(feature = "websocket")]
+            block_senders: BlockSenders::default(),
+            #[cfg(feature = "websocket")]
+            block_workers: vec![],
+            #[cfg(feature = "async-websocket")]
+            async_senders: AsyncSenders::default(),
+            #[cfg(feature = "async-websocket")]
+            async_workers: vec![],
+        }
+    }
+}
+
+/// Intraday is the Websocket listener to fugle wws endpoints.
+///
+/// Fugle serves one stock per connection,
+/// so Intraday opens a connection for every stock on each endpoint listened,
+/// and merges them into a single receiver of that endpoint.
+/// Responses are told apart by their `data.info.symbol_id`,
+/// and odd lot ones come with the `ODDLOT` `data.info.typ`.
+///
+/// Listening an endpoint again stops its running connections first,
+/// so the receiver it returned before is closed.
+pub struct Intraday {
+    token: String,
+    symbols: Vec<Symbol>,
+    url: String,
+    connector: Connector,
+    recorder: Arc<Recorder>,
+    #[cfg(feature = "websocket")]
+    multiplexed: bool,
+    // started by the first multiplexed worker.
+    #[cfg(feature = "websocket")]
+    mux: Mutex<Option<Arc<Mux>>>,
+    #[cfg(any(
+        all(feature = "websocket", feature = "query"),
+        all(feature = "async-websocket", feature = "async-query")
+    ))]
+    fallback: Option<Fallback>,
+    #[cfg(feature = "websocket")]
+    block_senders: BlockSenders,
+    #[cfg(feature = "websocket")]
+    block_workers: Vec<Running<BlockWorker>>,
+    #[cfg(feature = "async-websocket")]
+    async_senders: AsyncSenders,
+    #[cfg(feature = "async-websocket")]
+    async_workers: Vec<Running<AsyncWorker>>,
+}
+
+// the senders of the receivers handed out, kept for the workers subscribed later.
+#[cfg(feature = "websocket")]
+#[derive(Default)]
+struct BlockSenders {
+    chart: Option<Arc<dyn BlockSink<ChartResponse>>>,
+    quote: Option<Arc<dyn BlockSink<QuoteResponse>>>,
+    meta: Option<Arc<dyn BlockSink<MetaResponse>>>,
+}
+
+#[cfg(feature = "websocket")]
+impl BlockSenders {
+    fn clear(&mut self, channel: Channel) {
+        match channel {
+            Channel::Chart => self.chart = None,
+            Channel::Quote => self.quote = None,
+            Channel::Meta => self.meta = None,
+        }
+    }
+}
+
+#[cfg(feature = "async-websocket")]
+#[derive(Default)]
+struct AsyncSenders {
+    chart: Option<Arc<dyn AsyncSink<ChartResponse>>>,
+    quote: Option<Arc<dyn AsyncSink<QuoteResponse>>>,
+    meta: Option<Arc<dyn AsyncSink<MetaResponse>>>,
+}
+
+#[cfg(feature = "async-websocket")]
+impl AsyncSenders {
+    fn clear(&mut self, channel: Channel) {
+        match channel {
+            Channel::Chart => self.chart = None,
+            Channel::Quote => self.quote = None,
+            Channel::Meta => self.meta = None,
+        }
+    }
+}
+
+// a worker listening on the channel of the symbol.
+struct Running<W> {
+    symbol: Symbol,
+    channel: Channel,
+    worker: W,
+}
+
+// the response of an endpoint, telling which sender its workers send into.
+trait Endpoint: for<'de> Deserialize<'de> + Polled + Send + Sized + 'static {
+    const CHANNEL: Channel;
+
+    #[cfg(feature = "websocket")]
+    fn block_sender(senders: &mut BlockSenders) -> &mut Option<Arc<dyn BlockSink<Self>>>;
+
+    #[cfg(feature = "async-websocket")]
+    fn async_sender(senders: &mut AsyncSenders) -> &mut Option<Arc<dyn AsyncSink<Self>>>;
+}
+
+macro_rules! impl_endpoint {
+    ($($response:ty => $channel:ident, $field:ident;)+) => {
+        $(
+            impl Endpoint for $response {
+                const CHANNEL: Channel = Channel::$channel;
+
+                #[cfg(feature = "websocket")]
+                fn block_sender(senders: &mut BlockSenders) -> &mut Option<Arc<dyn BlockSink<Self>>> {
+                    &mut senders.$field
+                }
+
+                #[cfg(feature = "async-websocket")]
+                fn async_sender(senders: &mut AsyncSenders) -> &mut Option<Arc<dyn AsyncSink<Self>>> {
+                    &mut senders.$field
+                }
+            }
+        )+
+    };
+}
+
+impl_endpoint! {
+    ChartResponse => Chart, chart;
+    QuoteResponse => Quote, quote;
+    MetaResponse => Meta, meta;
+}
+
+impl Intraday {
+    /// Returns the stocks listened on every endpoint when it is opened.
+    pub fn symbols(&self) -> &[Symbol] {
+        &self.symbols
+    }
+
+    /// Returns how many websocket connections of the token are open,
+    /// counting those of every Intraday sharing the token, and its limit.
+    ///
+    /// Example:
+    ///
+    /// ```
+    /// # use fugle::websocket::IntradayBuilder;
+    ///
+    /// let ws = IntradayBuilder::new()
+    ///     .token("b52153ae36747b17c8bdee801da19542")
+    ///     .connection_limit(5)
+    ///     .build();
+    ///
+    /// let usage = ws.connection_usage();
+    /// assert_eq!(usage.limit, Some(5));
+    /// assert_eq!(usage.available(), Some(5));
+    /// ```
+    pub fn connection_usage(&self) -> ConnectionUsage {
+        self.connector.budget.usage()
+    }
+
+    /// Starts recording the raw frames of every worker into a gzip compressed
+    /// JSON-lines file with their receive time, the file is replayed by [`Replay`].
+    ///
+    /// A running recording is finished before recording into the new file.
+    ///
+    /// Example:
+    ///
+    /// ```no_run
+    /// # fn main() -> fugle::schema::Result<()> {
+    /// # use fugle::websocket::IntradayBuilder;
+    ///
+    /// let mut ws = IntradayBuilder::new().symbol_id("2884").build();
+    ///
+    /// ws.record("2884.jsonl.gz")?;
+    /// let rx = ws.quote()?;
+    ///
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn record<P: AsRef<Path>>(&self, path: P) -> Result<()> {
+        self.recorder.start(path.as_ref())
+    }
+
+    /// Finishes the running recording, the file is also finished
+    /// once the Intraday and all of its workers are gone.
+    pub fn stop_recording(&self) -> Result<()> {
+        self.recorder.stop()
+    }
+
+    fn uri(&self, symbol: &Symbol, channel: Channel) -> String {
+        format!(
+            "{}/{}?symbolId={}&apiToken={}&oddLot={}",
+            self.url.trim_end_matches('/'),
+            channel.path(),
+            symbol.id,
+            self.token,
+            symbol.odd_lot,
+        )
+    }
+
+    // the REST client of the blocking workers, moved onto their threads.
+    #[cfg(all(feature = "websocket", feature = "query"))]
+    fn rest(&self) -> Result<BlockRequest<'static>> {
+        Ok(RestfulBuilder::new()
+            .token(&self.token)
+            .build()?
+            .into_owned())
+    }
+
+    #[cfg(feature = "websocket")]
+    fn listen<T: Endpoint>(&mut self, sink: Arc<dyn BlockSink<T>>) -> Result<()> {
+        *T::block_sender(&mut self.block_senders) = Some(sink);
+        self.open(T::CHANNEL)
+    }
+
+    #[cfg(feature = "async-websocket")]
+    async fn async_listen<T: Endpoint>(&mut self, sink: Arc<dyn AsyncSink<T>>) -> Result<()> {
+        *T::async_sender(&mut self.async_senders) = Some(sink);
+        self.async_open(T::CHANNEL).await
+    }
+
+    #[cfg(feature = "websocket")]
+    fn spawn(&mut self, symbol: &Symbol, channel: Channel) -> Result<Running<BlockWorker>> {
+        let worker = match channel {
+            Channel::Chart => self.block_worker::<ChartResponse>(symbol),
+            Channel::Quote => self.block_worker::<QuoteResponse>(symbol),
+            Channel::Meta => self.block_worker::<MetaResponse>(symbol),
+        };
+
+        Ok(Running {
+            symbol: symbol.clone(),
+            channel,
+            worker: worker?,
+        })
+    }
+
+    #[cfg(feature = "websocket")]
+    fn block_worker<T: Endpoint>(&mut self, symbol: &Symbol) -> Result<BlockWorker> {
+        let tx = T::block_sender(&mut self.block_senders)
+            .clone()
+            .ok_or(FugleError::ChannelNotListened)?;
+        let uri = self.uri(symbol, T::CHANNEL);
+        let tap = Tap::new(self.recorder.clone(), T::CHANNEL);
+
+        #[cfg(feature = "query")]
+        if let Some(ref fallback) = self.fallback {
+            return BlockWorker::fallback(
+                &self.connector,
+                &uri,
+                symbol.clone(),
+                tx,
+                tap,
+                fallback.clone(),
+                self.rest()?,
+            );
+        }
+        #[cfg(all(
+            not(feature = "query"),
+            feature = "async-websocket",
+            feature = "async-query"
+        ))]
+        if self.fallback.is_some() {
+            return Err(FugleError::FeatureRequired("query"));
+        }
+
+        if !self.multiplexed || tx.may_block() {
+            return BlockWorker::new(&self.connector, &uri, symbol.clone(), tx, tap);
+        }
+
+        let mux = {
+            let mut mux = self.mux.lock().unwrap_or_else(|e| e.into_inner());
+            match *mux {
+                Some(ref mux) => mux.clone(),
+                None => mux.insert(Arc::new(Mux::new()?)).clone(),
+            }
+        };
+        BlockWorker::muxed(&mux, &self.connector, &uri, symbol.clone(), tx, tap)
+    }
+
+    #[cfg(feature = "async-websocket")]
+    async fn async_spawn(
+        &mut self,
+        symbol: &Symbol,
+        channel: Channel,
+    ) -> Result<Running<AsyncWorker>> {
+        let worker = match channel {
+            Channel::Chart => self.async_worker::<ChartResponse>(symbol).await,
+            Channel::Quote => self.async_worker::<QuoteResponse>(symbol).await,
+            Channel::Meta => self.async_worker::<MetaResponse>(symbol).await,
+        };
+
+        Ok(Running {
+            symbol: symbol.clone(),
+            channel,
+            worker: worker?,
+        })
+    }
+
+    #[cfg(feature = "async-websocket")]
+    async fn async_worker<T: Endpoint>(&mut self, symbol: &Symbol) -> Result<AsyncWorker> {
+        let tx = T::async_sender(&mut self.async_senders)
+            .clone()
+            .ok_or(FugleError::ChannelNotListened)?;
+        let uri = self.uri(symbol, T::CHANNEL);
+        let tap = Tap::new(self.recorder.clone(), T::CHANNEL);
+
+        #[cfg(feature = "async-query")]
+        if let Some(ref fallback) = self.fallback {
+            let rest = Rest::new(&self.token)?;
+            return AsyncWorker::fallback(
+                &self.connector,
+                &uri,
+                symbol.clone(),
+                tx,
+                tap,
+                fallback.clone(),
+                rest,
+            )
+            .await;
+        }
+        #[cfg(all(not(feature = "async-query"), feature = "websocket", feature = "query"))]
+        if self.fallback.is_some() {
+            return Err(FugleError::FeatureRequired("async-query"));
+        }
+
+        AsyncWorker::new(&self.connector, &uri, symbol.clone(), tx, tap).await
+    }
+
+    // stops the workers matching, waiting all of them no longer than the timeout.
+    #[cfg(feature = "websocket")]
+    fn stop<F>(&mut self, matching: F, timeout: Duration) -> Vec<WorkerExit>
+    where
+        F: Fn(&Running<BlockWorker>) -> bool,
+    {
+        let deadline = Instant::now() + timeout;
+        let (stopped, kept): (Vec<_>, Vec<_>) =
+            self.block_workers.drain(..).partition(|r| matching(r));
+        self.block_workers = kept;
+
+        for running in stopped.iter() {
+            running.worker.signal();
+        }
+        stopped
+            .into_iter()
+            .map(|mut running| running.worker.join(deadline))
+            .collect()
+    }
+
+    #[cfg(feature = "async-websocket")]
+    async fn async_stop<F>(&mut self, matching: F, timeout: Duration) -> Vec<WorkerExit>
+    where
+        F: Fn(&Running<AsyncWorker>) -> bool,
+    {
+        let deadline = Instant::now() + timeout;
+        let (stopped, kept): (Vec<_>, Vec<_>) =
+            self.async_workers.drain(..).partition(|r| matching(r));
+        self.async_workers = kept;
+
+        for running in stopped.iter() {
+            running.worker.signal();
+        }
+        let mut exits = Vec::with_capacity(stopped.len());
+        for mut running in stopped {
+            exits.push(running.worker.join(deadline).await);
+        }
+        exits
+    }
+
+    #[cfg(feature = "websocket")]
+    fn open(&mut self, channel: Channel) -> Result<()> {
+        // listening a channel again replaces its workers,
+        // which would keep a connection each for the receiver returned before.
+        self.stop(|r| r.channel == channel, DROP_TIMEOUT);
+
+        // connects all the symbols before keeping any of them,
+        // the connected workers stop themselves on drop if any symbol failed.
+        let mut workers = Vec::with_capacity(self.symbols.len());
+        for symbol in self.symbols.clone().iter() {
+            match self.spawn(symbol, channel) {
+                Ok(running) => workers.push(running),
+                Err(e) => {
+                    self.block_senders.clear(channel);
+                    return Err(e);
+                }
+            }
+        }
+        self.block_workers.extend(workers);
+        Ok(())
+    }
+
+    #[cfg(feature = "async-websocket")]
+    async fn async_open(&mut self, channel: Channel) -> Result<()> {
+        self.async_stop(|r| r.channel == channel, DROP_TIMEOUT)
+            .await;
+
+        let mut workers = Vec::with_capacity(self.symbols.len());
+        for symbol in self.symbols.clone().iter() {
+            match self.async_spawn(symbol, channel).await {
+                Ok(running) => workers.push(running),
+                Err(e) => {
+                    self.async_senders.clear(channel);
+                    return Err(e);
+                }
+            }
+        }
+        self.async_workers.extend(workers);
+        Ok(())
+    }
+
+    #[cfg(feature = "websocket")]
+    fn open_all(&mut self) -> Result<()> {
+        // stops the channels opened by this call if any of them failed.
+        for channel in [Channel::Chart, Channel::Quote, Channel::Meta] {
+            if let Err(e) = self.open(channel) {
+                self.block_workers.clear();
+                self.block_senders = BlockSenders::default();
+                return Err(e);
+            }
+        }
+        Ok(())
+    }
+
+    #[cfg(feature = "async-websocket")]
+    async fn async_open_all(&mut self) -> Result<()> {
+        for channel in [Channel::Chart, Channel::Quote, Channel::Meta] {
+            if let Err(e) = self.async_open(channel).await {
+                self.async_workers.clear();
+                self.async_senders = AsyncSenders::default();
+                return Err(e);
+            }
+        }
+        Ok(())
+    }
+
+    /// Listening fugle Chart endpoint.
+    ///
+    /// Example:
+    ///
+    /// ```no_run
+    /// # fn main() -> fugle::schema::Result<()> {
+    /// # use fugle::websocket::IntradayBuilder;
+    ///
+    /// let mut ws = IntradayBuilder::new().symbol_id("2884").odd_lot().build();
+    ///
+    /// let rx = ws.chart()?;
+    /// let response = rx.recv()?;
+    ///
+    /// # Ok(())
+    /// # }
+    /// ```
+    #[cfg(feature = "websocket")]
+    pub fn chart(&mut self) -> Result<Receiver<ChartResponse>> {
+        let (tx, rx) = channel();
+        self.listen::<ChartResponse>(Arc::new(tx))?;
+        Ok(rx)
+    }
+
+    /// Listening fugle Chart endpoint through a bounded channel,
+    /// which holds at most `capacity` responses and handles a full channel by the overflow policy.
+    ///
+    /// Example:
+    ///
+    /// ```no_run
+    /// # fn main() -> fugle::schema::Result<()> {
+    /// # use fugle::websocket::{IntradayBuilder, Overflow};
+    ///
+    /// let mut ws = IntradayBuilder::new().symbol_id("2884").build();
+    ///
+    /// let rx = ws.chart_bounded(16, Overflow::DropOldest)?;
+    /// let response = rx.recv()?;
+    ///
+    /// # Ok(())
+    /// # }
+    /// ```
+    #[cfg(feature = "websocket")]
+    pub fn chart_bounded(
+        &mut self,
+        capacity: usize,
+        overflow: Overflow,
+    ) -> Result<bounded::Receiver<ChartResponse>> {
+        let (tx, rx) = bounded::bounded(capacity, overflow);
+        self.listen::<ChartResponse>(Arc::new(tx))?;
+        Ok(rx)
+    }
+
+    /// Listening fugle Chart endpoint.
+    ///
+    /// Example:
+    ///
+    /// ```no_run
+    /// # #[tokio::main]
+    /// # async fn main() -> fugle::schema::Result<()> {
+    /// # use fugle::websocket::IntradayBuilder;
+    ///
+    /// let mut ws = IntradayBuilder::new().symbol_id("2884").odd_lot().build();
+    ///
+    /// let mut rx = ws.async_chart().await?;
+    /// let response = rx.recv().await;
+    ///
+    /// # Ok(())
+    /// # }
+    /// ```
+    #[cfg(feature = "async-websocket")]
+    pub async fn async_chart(&mut self) -> Result<UnboundedReceiver<ChartResponse>> {
+        let (tx, rx) = unbounded_channel();
+        self.async_listen::<ChartResponse>(Arc::new(tx)).await?;
+        Ok(rx)
+    }
+
+    /// Listening fugle Chart endpoint through a bounded channel,
+    /// which holds at most `capacity` responses and handles a full channel by the overflow policy.
+    ///
+    /// Example:
+    ///
+    /// ```no_run
+    /// # #[tokio::main]
+    /// # async fn main() -> fugle::schema::Result<()> {
+    /// # use fugle::websocket::{IntradayBuilder, Overflow};
+    ///
+    /// let mut ws = IntradayBuilder::new().symbol_id("2884").build();
+    ///
+    /// let rx = ws.async_chart_bounded(16, Overflow::Conflate).await?;
+    /// let response = rx.async_recv().await?;
+    ///
+    /// # Ok(())
+    /// # }
+    /// ```
+    #[cfg(feature = "async-websocket")]
+    pub async fn async_chart_bounded(
+        &mut self,
+        capacity: usize,
+        overflow: Overflow,
+    ) -> Result<bounded::Receiver<ChartResponse>> {
+        let (tx, rx) = bounded::bounded(capacity, overflow);
+        self.async_listen::<ChartResponse>(Arc::new(tx)).await?;
+        Ok(rx)
+    }
+
+    /// Listening fugle Meta endpoint.
+    ///
+    /// Example:
+    ///
+    /// ```no_run
+    /// # fn main() -> fugle::schema::Result<()> {
+    /// # use fugle::websocket::IntradayBuilder;
+    ///
+    /// let mut ws = IntradayBuilder::new().symbol_id("2884").odd_lot().build();
+    ///
+    /// let rx = ws.meta()?;
+    /// let response = rx.recv()?;
+    ///
+    /// # Ok(())
+    /// # }
+    /// ```
+    #[cfg(feature = "websocket")]
+    pub fn meta(&mut self) -> Result<Receiver<MetaResponse>> {
+        let (tx, rx) = channel();
+        self.listen::<MetaResponse>(Arc::new(tx))?;
+        Ok(rx)
+    }
+
+    /// Listening fugle Meta endpoint through a bounded channel,
+    /// see [`chart_bounded`](Intraday::chart_bounded).
+    #[cfg(feature = "websocket")]
+    pub fn meta_bounded(
+        &mut self,
+        capacity: usize,
+        overflow: Overflow,
+    ) -> Result<bounded::Receiver<MetaResponse>> {
+        let (tx, rx) = bounded::bounded(capacity, overflow);
+        self.listen::<MetaResponse>(Arc::new(tx))?;
+        Ok(rx)
+    }
+
+    /// Listening fugle Meta endpoint.
+    ///
+    /// Example:
+    ///
+    /// ```no_run
+    /// # #[tokio::main]
+    /// # async fn main() -> fugle::schema::Result<()> {
+    /// # use fugle::websocket::IntradayBuilder;
+    ///
+    /// let mut ws = IntradayBuilder::new().symbol_id("2884").odd_lot().build();
+    ///
+    /// let mut rx = ws.async_meta().await?;
+    /// let response = rx.recv().await;
+    ///
+    /// # Ok(())
+    /// # }
+    /// ```
+    #[cfg(feature = "async-websocket")]
+    pub async fn async_meta(&mut self) -> Result<UnboundedReceiver<MetaResponse>> {
+        let (tx, rx) = unbounded_channel();
+        self.async_listen::<MetaResponse>(Arc::new(tx)).await?;
+        Ok(rx)
+    }
+
+    /// Listening fugle Meta endpoint through a bounded channel,
+    /// see [`async_chart_bounded`](Intraday::async_chart_bounded).
+    #[cfg(feature = "async-websocket")]
+    pub async fn async_meta_bounded(
+        &mut self,
+        capacity: usize,
+        overflow: Overflow,
+    ) -> Result<bounded::Receiver<MetaResponse>> {
+        let (tx, rx) = bounded::bounded(capacity, overflow);
+        self.async_listen::<MetaResponse>(Arc::new(tx)).await?;
+        Ok(rx)
+    }
+
+    /// Listening fugle Quote endpoint.
+    ///
+    /// Example:
+    ///
+    /// ```no_run
+    /// # fn main() -> fugle::schema::Result<()> {
+    /// # use fugle::websocket::IntradayBuilder;
+    ///
+    /// let mut ws = IntradayBuilder::new().symbol_id("2884").odd_lot().build();
+    ///
+    /// let rx = ws.quote()?;
+    /// let response = rx.recv()?;
+    ///
+    /// # Ok(())
+    /// # }
+    /// ```
+    #[cfg(feature = "websocket")]
+    pub fn quote(&mut self) -> Result<Receiver<QuoteResponse>> {
+        let (tx, rx) = channel();
+        self.listen::<QuoteResponse>(Arc::new(tx))?;
+        Ok(rx)
+    }
+
+    /// Listening fugle Quote endpoint through a bounded channel,
+    /// see [`chart_bounded`](Intraday::chart_bounded).
+    #[cfg(feature = "websocket")]
+    pub fn quote_bounded(
+        &mut self,
+        capacity: usize,
+        overflow: Overflow,
+    ) -> Result<bounded::Receiver<QuoteResponse>> {
+        let (tx, rx) = bounded::bounded(capacity, overflow);
+        self.listen::<QuoteResponse>(Arc::new(tx))?;
+        Ok(rx)
+    }
+
+    /// Listening fugle Quote endpoint.
+    ///
+    /// Example:
+    ///
+    /// ```no_run
+    /// # #[tokio::main]
+    /// # async fn main() -> fugle::schema::Result<()> {
+    /// # use fugle::websocket::IntradayBuilder;
+    ///
+    /// let mut ws = IntradayBuilder::new().symbol_id("2884").odd_lot().build();
+    ///
+    /// let mut rx = ws.async_quote().await?;
+    /// let response = rx.recv().await;
+    ///
+    /// # Ok(())
+    /// # }
+    /// ```
+    #[cfg(feature = "async-websocket")]
+    pub async fn async_quote(&mut self) -> Result<UnboundedReceiver<QuoteResponse>> {
+        let (tx, rx) = unbounded_channel();
+        self.async_listen::<QuoteResponse>(Arc::new(tx)).await?;
+        Ok(rx)
+    }
+
+    /// Listening fugle Quote endpoint through a bounded channel,
+    /// see [`async_chart_bounded`](Intraday::async_chart_bounded).
+    #[cfg(feature = "async-websocket")]
+    pub async fn async_quote_bounded(
+        &mut self,
+        capacity: usize,
+        overflow: Overflow,
+    ) -> Result<bounded::Receiver<QuoteResponse>> {
+        let (tx, rx) = bounded::bounded(capacity, overflow);
+        self.async_listen::<QuoteResponse>(Arc::new(tx)).await?;
+        Ok(rx)
+    }
+}
+
+#[cfg(feature = "websocket")]
+impl Intraday {
+    /// Listening fugle Chart endpoint once for many consumers,
+    /// every consumer subscribed on the returned handle receives a copy of each response.
+    ///
+    /// Example:
+    ///
+    /// ```no_run
+    /// # fn main() -> fugle::schema::Result<()> {
+    /// # use fugle::websocket::{IntradayBuilder, Overflow};
+    ///
+    /// let mut ws = IntradayBuilder::new().symbol_id("2884").build();
+    ///
+    /// let broadcast = ws.chart_broadcast()?;
+    /// let rx1 = broadcast.subscribe(16, Overflow::DropOldest);
+    /// let rx2 = broadcast.subscribe(16, Overflow::Block);
+    /// let response = rx1.recv()?;
+    ///
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn chart_broadcast(&mut self) -> Result<Broadcast<ChartResponse>> {
+        let broadcast = Broadcast::new();
+        self.listen::<ChartResponse>(Arc::new(broadcast.clone()))?;
+        Ok(broadcast)
+    }
+
+    /// Listening fugle Meta endpoint once for many consumers,
+    /// see [`chart_broadcast`](Intraday::chart_broadcast).
+    pub fn meta_broadcast(&mut self) -> Result<Broadcast<MetaResponse>> {
+        let broadcast = Broadcast::new();
+        self.listen::<MetaResponse>(Arc::new(broadcast.clone()))?;
+        Ok(broadcast)
+    }
+
+    /// Listening fugle Quote endpoint once for many consumers,
+    /// see [`chart_broadcast`](Intraday::chart_broadcast).
+    pub fn quote_broadcast(&mut self) -> Result<Broadcast<QuoteResponse>> {
+        let broadcast = Broadcast::new();
+        self.listen::<QuoteResponse>(Arc::new(broadcast.clone()))?;
+        Ok(broadcast)
+    }
+}
+
+#[cfg(feature = "async-websocket")]
+impl Intraday {
+    /// Listening fugle Chart endpoint once for many consumers,
+    /// call `subscribe` on the returned sender for every consumer.
+    ///
+    /// Each consumer holds at most `capacity` responses,
+    /// a consumer falling behind gets `RecvError::Lagged` and skips to the held ones.
+    ///
+    /// Example:
+    ///
+    /// ```no_run
+    /// # #[tokio::main]
+    /// # async fn main() -> fugle::schema::Result<()> {
+    /// # use fugle::websocket::IntradayBuilder;
+    ///
+    /// let mut ws = IntradayBuilder::new().symbol_id("2884").build();
+    ///
+    /// let broadcast = ws.async_chart_broadcast(16).await?;
+    /// let mut rx1 = broadcast.subscribe();
+    /// let mut rx2 = broadcast.subscribe();
+    /// let response = rx1.recv().await;
+    ///
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub async fn async_chart_broadcast(
+        &mut self,
+        capacity: usize,
+    ) -> Result<broadcast::Sender<ChartResponse>> {
+        let (tx, _) = broadcast::channel(capacity.max(1));
+        self.async_listen::<ChartResponse>(Arc::new(tx.clone()))
+            .await?;
+        Ok(tx)
+    }
+
+    /// Listening fugle Meta endpoint once for many consumers,
+    /// see [`async_chart_broadcast`](Intraday::async_chart_broadcast).
+    pub async fn async_meta_broadcast(
+        &mut self,
+        capacity: usize,
+    ) -> Result<broadcast::Sender<MetaResponse>> {
+        let (tx, _) = broadcast::channel(capacity.max(1));
+        self.async_listen::<MetaResponse>(Arc::new(tx.clone()))
+            .await?;
+        Ok(tx)
+    }
+
+    /// Listening fugle Quote endpoint once for many consumers,
+    /// see [`async_chart_broadcast`](Intraday::async_chart_broadcast).
+    pub async fn async_quote_broadcast(
+        &mut self,
+        capacity: usize,
+    ) -> Result<broadcast::Sender<QuoteResponse>> {
+        let (tx, _) = broadcast::channel(capacity.max(1));
+        self.async_listen::<QuoteResponse>(Arc::new(tx.clone()))
+            .await?;
+        Ok(tx)
+    }
+}
+
+#[cfg(feature = "websocket")]
+impl Intraday {
+    /// Listening fugle Chart endpoint,
+    /// every response is wrapped into an Envelope stamped on receiving.
+    ///
+    /// Example:
+    ///
+    /// ```no_run
+    /// # fn main() -> fugle::schema::Result<()> {
+    /// # use fugle::websocket::IntradayBuilder;
+    ///
+    /// let mut ws = IntradayBuilder::new().symbol_id("2884").build();
+    ///
+    /// let rx = ws.chart_enveloped()?;
+    /// let envelope = rx.recv()?;
+    /// println!("{} {:?}", envelope.sequence, envelope.latency());
+    ///
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn chart_enveloped(&mut self) -> Result<Receiver<Envelope<ChartResponse>>> {
+        let (tx, rx) = channel();
+        self.listen::<ChartResponse>(Arc::new(EnvelopeSink::new(tx)))?;
+        Ok(rx)
+    }
+
+    /// Listening fugle Meta endpoint with every response wrapped into an Envelope,
+    /// see [`chart_enveloped`](Intraday::chart_enveloped).
+    pub fn meta_enveloped(&mut self) -> Result<Receiver<Envelope<MetaResponse>>> {
+        let (tx, rx) = channel();
+        self.listen::<MetaResponse>(Arc::new(EnvelopeSink::new(tx)))?;
+        Ok(rx)
+    }
+
+    /// Listening fugle Quote endpoint with every response wrapped into an Envelope,
+    /// see [`chart_enveloped`](Intraday::chart_enveloped).
+    pub fn quote_enveloped(&mut self) -> Result<Receiver<Envelope<QuoteResponse>>> {
+        let (tx, rx) = channel();
+        self.listen::<QuoteResponse>(Arc::new(EnvelopeSink::new(tx)))?;
+        Ok(rx)
+    }
+}
+
+#[cfg(feature = "async-websocket")]
+impl Intraday {
+    /// Listening fugle Chart endpoint,
+    /// every response is wrapped into an Envelope stamped on receiving.
+    ///
+    /// Example:
+    ///
+    /// ```no_run
+    /// # #[tokio::main]
+    /// # async fn main() -> fugle::schema::Result<()> {
+    /// # use fugle::websocket::IntradayBuilder;
+    ///
+    /// let mut ws = IntradayBuilder::new().symbol_id("2884").build();
+    ///
+    /// let mut rx = ws.async_chart_enveloped().await?;
+    /// if let Some(envelope) = rx.recv().await {
+    ///     println!("{} {:?}", envelope.sequence, envelope.latency());
+    /// }
+    ///
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub async fn async_chart_enveloped(
+        &mut self,
+    ) -> Result<UnboundedReceiver<Envelope<ChartResponse>>> {
+        let (tx, rx) = unbounded_channel();
+        self.async_listen::<ChartResponse>(Arc::new(EnvelopeSink::new(tx)))
+            .await?;
+        Ok(rx)
+    }
+
+    /// Listening fugle Meta endpoint with every response wrapped into an Envelope,
+    /// see [`async_chart_enveloped`](Intraday::async_chart_enveloped).
+    pub async fn async_meta_enveloped(
+        &mut self,
+    ) -> Result<UnboundedReceiver<Envelope<MetaResponse>>> {
+        let (tx, rx) = unbounded_channel();
+        self.async_listen::<MetaResponse>(Arc::new(EnvelopeSink::new(tx)))
+            .await?;
+        Ok(rx)
+    }
+
+    /// Listening fugle Quote endpoint with every response wrapped into an Envelope,
+    /// see [`async_chart_enveloped`](Intraday::async_chart_enveloped).
+    pub async fn async_quote_enveloped(
+        &mut self,
+    ) -> Result<UnboundedReceiver<Envelope<QuoteResponse>>> {
+        let (tx, rx) = unbounded_channel();
+        self.async_listen::<QuoteResponse>(Arc::new(EnvelopeSink::new(tx)))
+            .await?;
+        Ok(rx)
+    }
+}
+
+#[cfg(feature = "websocket")]
+impl Intraday {
+    /// Listening fugle Quote endpoint for what has changed between the quotes of every stock,
+    /// see QuoteDiff for how the quotes are compared.
+    ///
+    /// Example:
+    ///
+    /// ```no_run
+    /// # fn main() -> fugle::schema::Result<()> {
+    /// # use fugle::websocket::{IntradayBuilder, QuoteDelta};
+    ///
+    /// let mut ws = IntradayBuilder::new().symbol_id("2884").build();
+    ///
+    /// let rx = ws.quote_deltas()?;
+    /// let (symbol, delta) = rx.recv()?;
+    /// if let QuoteDelta::Trade(trade) = delta {
+    ///     println!("{} {}", symbol.id, trade.price);
+    /// }
+    ///
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn quote_deltas(&mut self) -> Result<Receiver<(Symbol, QuoteDelta)>> {
+        let (tx, rx) = channel();
+        self.listen::<QuoteResponse>(Arc::new(DeltaSink::new(tx)))?;
+        Ok(rx)
+    }
+}
+
+#[cfg(feature = "async-websocket")]
+impl Intraday {
+    /// Listening fugle Quote endpoint for what has changed between the quotes of every stock,
+    /// see QuoteDiff for how the quotes are compared.
+    ///
+    /// Example:
+    ///
+    /// ```no_run
+    /// # #[tokio::main]
+    /// # async fn main() -> fugle::schema::Result<()> {
+    /// # use fugle::websocket::{IntradayBuilder, QuoteDelta};
+    ///
+    /// let mut ws = IntradayBuilder::new().symbol_id("2884").build();
+    ///
+    /// let mut rx = ws.async_quote_deltas().await?;
+    /// if let Some((symbol, QuoteDelta::Trade(trade))) = rx.recv().await {
+    ///     println!("{} {}", symbol.id, trade.price);
+    /// }
+    ///
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub async fn async_quote_deltas(&mut self) -> Result<UnboundedReceiver<(Symbol, QuoteDelta)>> {
+        let (tx, rx) = unbounded_channel();
+        self.async_listen::<QuoteResponse>(Arc::new(DeltaSink::new(tx)))
+            .await?;
+        Ok(rx)
+    }
+}
+
+#[cfg(all(feature = "websocket", feature = "query"))]
+impl Intraday {
+    /// Listening fugle Quote endpoint for the trades of every stock in serial order.
+    ///
+    /// A trade skipped by the stream, e.g. while reconnecting, is told by the transaction count
+    /// of the quote and backfilled from the dealts endpoint before the trade which revealed the gap.
+    /// The backfilling runs in the background, it delays the trades but never the socket reads.
+    /// A failed backfill is reported as an error and the stream goes on.
+    ///
+    /// Example:
+    ///
+    /// ```no_run
+    /// # fn main() -> fugle::schema::Result<()> {
+    /// # use fugle::websocket::IntradayBuilder;
+    ///
+    /// let mut ws = IntradayBuilder::new().symbol_id("2884").build();
+    ///
+    /// let rx = ws.quote_trades()?;
+    /// let trade = rx.recv()??;
+    /// println!("{} {}", trade.dealt.serial, trade.backfilled);
+    ///
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn quote_trades(&mut self) -> Result<Receiver<Result<Trade>>> {
+        let (tx, rx) = channel();
+        self.listen::<QuoteResponse>(Arc::new(GapSink::new(self.rest()?, tx)))?;
+        Ok(rx)
+    }
+
+    /// Listening fugle Chart endpoint as one consistent state stream of every stock.
+    ///
+    /// Every connection of a stock fetches a snapshot from the chart REST endpoint
+    /// in the background, and of the snapshot and the websocket responses,
+    /// whichever comes first, the ones not newer than the last state are dropped,
+    /// by the last updated time.
+    /// A snapshot without the updated time never replaces a state passed on already.
+    /// A failed snapshot is reported as an error and the stream goes on.
+    ///
+    /// Example:
+    ///
+    /// ```no_run
+    /// # fn main() -> fugle::schema::Result<()> {
+    /// # use fugle::websocket::IntradayBuilder;
+    ///
+    /// let mut ws = IntradayBuilder::new().symbol_id("2884").build();
+    ///
+    /// let rx = ws.chart_feed()?;
+    /// let feed = rx.recv()??;
+    /// println!("{} {}", feed.snapshot, feed.data.data.chart.close.len());
+    ///
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn chart_feed(&mut self) -> Result<Receiver<Result<Feed<ChartResponse>>>> {
+        let (tx, rx) = channel();
+        self.listen::<ChartResponse>(Arc::new(FeedSink::new(self.rest()?, tx)))?;
+        Ok(rx)
+    }
+
+    /// Listening fugle Quote endpoint as one consistent state stream of every stock.
+    ///
+    /// Every connection of a stock fetches a snapshot from the quote REST endpoint
+    /// in the background, and of the snapshot and the websocket responses,
+    /// whichever comes first, the ones not newer than the last state are dropped,
+    /// by the last updated time and then the serial.
+    /// A failed snapshot is reported as an error and the stream goes on.
+    ///
+    /// Example:
+    ///
+    /// ```no_run
+    /// # fn main() -> fugle::schema::Result<()> {
+    /// # use fugle::websocket::IntradayBuilder;
+    ///
+    /// let mut ws = IntradayBuilder::new().symbol_id("2884").build();
+    ///
+    /// let rx = ws.quote_feed()?;
+    /// let feed = rx.recv()??;
+    /// println!("{} {}", feed.snapshot, feed.data.data.quote.total.serial);
+    ///
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn quote_feed(&mut self) -> Result<Receiver<Result<Feed<QuoteResponse>>>> {
+        let (tx, rx) = channel();
+        self.listen::<QuoteResponse>(Arc::new(FeedSink::new(self.rest()?, tx)))?;
+        Ok(rx)
+    }
+}
+
+#[cfg(all(feature = "async-websocket", feature = "async-query"))]
+impl Intraday {
+    /// Listening fugle Quote endpoint for the trades of every stock in serial order.
+    ///
+    /// A trade skipped by the stream, e.g. while reconnecting, is told by the transaction count
+    /// of the quote and backfilled from the dealts endpoint before the trade which revealed the gap.
+    /// The backfilling runs in the background, it delays the trades but never the socket reads.
+    /// A failed backfill is reported as an error and the stream goes on.
+    /// The backfills need a tokio runtime, see [`Executor`](crate::websocket::Executor).
+    ///
+    /// Example:
+    ///
+    /// ```no_run
+    /// # #[tokio::main]
+    /// # async fn main() -> fugle::schema::Result<()> {
+    /// # use fugle::websocket::IntradayBuilder;
+    ///
+    /// let mut ws = IntradayBuilder::new().symbol_id("2884").build();
+    ///
+    /// let mut rx = ws.async_quote_trades().await?;
+    /// if let Some(trade) = rx.recv().await {
+    ///     println!("{}", trade?.dealt.serial);
+    /// }
+    ///
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub async fn async_quote_trades(&mut self) -> Result<UnboundedReceiver<Result<Trade>>> {
+        let (tx, rx) = unbounded_channel();
+        self.async_listen::<QuoteResponse>(Arc::new(GapSink::async_new(
+            Rest::new(&self.token)?,
+            tx,
+            &self.connector.spawner,
+        )))
+        .await?;
+        Ok(rx)
+    }
+
+    /// Listening fugle Chart endpoint as one consistent state stream of every stock.
+    ///
+    /// Every connection of a stock fetches a snapshot from the chart REST endpoint
+    /// in the background, and of the snapshot and the websocket responses,
+    /// whichever comes first, the ones not newer than the last state are dropped,
+    /// by the last updated time.
+    /// A snapshot without the updated time never replaces a state passed on already.
+    /// A failed snapshot is reported as an error and the stream goes on.
+    /// The snapshots need a tokio runtime, see [`Executor`](crate::websocket::Executor).
+    ///
+    /// Example:
+    ///
+    /// ```no_run
+    /// # #[tokio::main]
+    /// # async fn main() -> fugle::schema::Result<()> {
+    /// # use fugle::websocket::IntradayBuilder;
+    ///
+    /// let mut ws = IntradayBuilder::new().symbol_id("2884").build();
+    ///
+    /// let mut rx = ws.async_chart_feed().await?;
+    /// if let Some(feed) = rx.recv().await {
+    ///     println!("{}", feed?.snapshot);
+    /// }
+    ///
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub async fn async_chart_feed(
+        &mut self,
+    ) -> Result<UnboundedReceiver<Result<Feed<ChartResponse>>>> {
+        let (tx, rx) = unbounded_channel();
+        let sink = FeedSink::async_new(Rest::new(&self.token)?, tx, &self.connector.spawner);
+        self.async_listen::<ChartResponse>(Arc::new(sink)).await?;
+        Ok(rx)
+    }
+
+    /// Listening fugle Quote endpoint as one consistent state stream of every stock.
+    ///
+    /// Every connection of a stock fetches a snapshot from the quote REST endpoint
+    /// in the background, and of the snapshot and the websocket responses,
+    /// whichever comes first, the ones not newer than the last state are dropped,
+    /// by the last updated time and then the serial.
+    /// A failed snapshot is reported as an error and the stream goes on.
+    /// The snapshots need a tokio runtime, see [`Executor`](crate::websocket::Executor).
+    ///
+    /// Example:
+    ///
+    /// ```no_run
+    /// # #[tokio::main]
+    /// # async fn main() -> fugle::schema::Result<()> {
+    /// # use fugle::websocket::IntradayBuilder;
+    ///
+    /// let mut ws = IntradayBuilder::new().symbol_id("2884").build();
+    ///
+    /// let mut rx = ws.async_quote_feed().await?;
+    /// if let Some(feed) = rx.recv().await {
+    ///     println!("{}", feed?.snapshot);
+    /// }
+    ///
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub async fn async_quote_feed(
+        &mut self,
+    ) -> Result<UnboundedReceiver<Result<Feed<QuoteResponse>>>> {
+        let (tx, rx) = unbounded_channel();
+        let sink = FeedSink::async_new(Rest::new(&self.token)?, tx, &self.connector.spawner);
+        self.async_listen::<QuoteResponse>(Arc::new(sink)).await?;
+        Ok(rx)
+    }
+}
+
+impl Intraday {
+    /// Listening all the fugle endpoints of every stock through one receiver,
+    /// the responses are wrapped into Event and come in their arrival order.
+    ///
+    /// Example:
+    ///
+    /// ```no_run
+    /// # fn main() -> fugle::schema::Result<()> {
+    /// # use fugle::websocket::{Event, IntradayBuilder};
+    ///
+    /// let mut ws = IntradayBuilder::new().symbol_id("2884").build();
+    ///
+    /// let rx = ws.events()?;
+    /// match rx.recv()? {
+    ///     Event::Chart(chart) => println!("{:?}", chart),
+    ///     Event::Quote(quote) => println!("{:?}", quote),
+    ///     Event::Meta(meta) => println!("{:?}", meta),
+    /// }
+    ///
+    /// # Ok(())
+    /// # }
+    /// ```
+    #[cfg(feature = "websocket")]
+    pub fn events(&mut self) -> Result<Receiver<Event>> {
+        let (tx, rx) = channel();
+        let sink = Arc::new(EventSink(tx));
+        self.block_senders.chart = Some(sink.clone());
+        self.block_senders.quote = Some(sink.clone());
+        self.block_senders.meta = Some(sink);
+
+        self.open_all()?;
+        Ok(rx)
+    }
+
+    /// Listening all the fugle endpoints of every stock through one receiver,
+    /// the responses are wrapped into Event and come in their arrival order.
+    ///
+    /// Example:
+    ///
+    /// ```no_run
+    /// # #[tokio::main]
+    /// # async fn main() -> fugle::schema::Result<()> {
+    /// # use fugle::websocket::{Event, IntradayBuilder};
+    ///
+    /// let mut ws = IntradayBuilder::new().symbol_id("2884").build();
+    ///
+    /// let mut rx = ws.async_events().await?;
+    /// if let Some(Event::Quote(quote)) = rx.recv().await {
+    ///     println!("{:?}", quote);
+    /// }
+    ///
+    /// # Ok(())
+    /// # }
+    /// ```
+    #[cfg(feature = "async-websocket")]
+    pub async fn async_events(&mut self) -> Result<UnboundedReceiver<Event>> {
+        let (tx, rx) = unbounded_channel();
+        let sink = Arc::new(EventSink(tx));
+        self.async_senders.chart = Some(sink.clone());
+        self.async_senders.quote = Some(sink.clone());
+        self.async_senders.meta = Some(sink);
+
+        self.async_open_all().await?;
+        Ok(rx)
+    }
+}
+
+#[cfg(feature = "websocket")]
+impl Intraday {
+    /// Drives the handler with the responses of all the fugle endpoints of every stock,
+    /// blocks until all the connections are closed by the server.
+    ///
+    /// Returns how every worker exited.
+    ///
+    /// Example:
+    ///
+    /// ```no_run
+    /// # fn main() -> fugle::schema::Result<()> {
+    /// # use fugle::schema::QuoteResponse;
+    /// # use fugle::websocket::{Handler, IntradayBuilder, Symbol};
+    ///
+    /// struct Bot;
+    ///
+    /// impl Handler for Bot {
+    ///     fn on_quote(&mut self, symbol: &Symbol, quote: QuoteResponse) {
+    ///         println!("{}: {:?}", symbol.id, quote);
+    ///     }
+    /// }
+    ///
+    /// let mut ws = IntradayBuilder::new().symbol_id("2884").build();
+    /// ws.run(Bot)?;
+    ///
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn run<H: Handler>(&mut self, handler: H) -> Result<Vec<WorkerExit>> {
+        let handler = Arc::new(std::sync::Mutex::new(handler));
+        self.block_senders.chart =
+            Some(Arc::new(HandlerSink::new(handler.clone(), Channel::Chart)));
+        self.block_senders.quote =
+            Some(Arc::new(HandlerSink::new(handler.clone(), Channel::Quote)));
+        self.block_senders.meta = Some(Arc::new(HandlerSink::new(handler, Channel::Meta)));
+
+        self.open_all()?;
+
+        let exits = self
+            .block_workers
+            .drain(..)
+            .map(|mut running| running.worker.wait())
+            .collect();
+        self.block_senders = BlockSenders::default();
+        Ok(exits)
+    }
+}
+
+#[cfg(feature = "async-websocket")]
+impl Intraday {
+    /// Drives the handler with the responses of all the fugle endpoints of every stock,
+    /// resolves once all the connections are closed by the server.
+    ///
+    /// Returns how every worker exited.
+    ///
+    /// Example:
+    ///
+    /// ```no_run
+    /// # #[tokio::main]
+    /// # async fn main() -> fugle::schema::Result<()> {
+    /// # use fugle::schema::QuoteResponse;
+    /// # use fugle::websocket::{AsyncHandler, IntradayBuilder, Symbol};
+    ///
+    /// struct Bot;
+    ///
+    /// impl AsyncHandler for Bot {
+    ///     async fn on_quote(&mut self, symbol: &Symbol, quote: QuoteResponse) {
+    ///         println!("{}: {:?}", symbol.id, quote);
+    ///     }
+    /// }
+    ///
+    /// let mut ws = IntradayBuilder::new().symbol_id("2884").build();
+    /// ws.async_run(Bot).await?;
+    ///
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub async fn async_run<H: AsyncHandler>(&mut self, handler: H) -> Result<Vec<WorkerExit>> {
+        let handler = Arc::new(tokio::sync::Mutex::new(handler));
+        self.async_senders.chart =
+            Some(Arc::new(HandlerSink::new(handler.clone(), Channel::Chart)));
+        self.async_senders.quote =
+            Some(Arc::new(HandlerSink::new(handler.clone(), Channel::Quote)));
+        self.async_senders.meta = Some(Arc::new(HandlerSink::new(handler, Channel::Meta)));
+
+        self.async_open_all().await?;
+
+        let mut exits = Vec::with_capacity(self.async_workers.len());
+        for mut running in self.async_workers.drain(..) {
+            exits.push(running.worker.wait().await);
+        }
+        self.async_senders = AsyncSenders::default();
+        Ok(exits)
+    }
+}
+
+#[cfg(feature = "async-websocket")]
+impl Intraday {
+    async fn async_stream<T>(&self, channel: Channel) -> Result<Subscription<T>>
+    where
+        T: for<'de> serde::Deserialize<'de> + Send + 'static,
+    {
+        let (tx, rx) = unbounded_channel();
+        let sink: Arc<dyn AsyncSink<T>> = Arc::new(StreamSink(tx));
+
+        let tap = Tap::new(self.recorder.clone(), channel);
+        let mut workers = Vec::with_capacity(self.symbols.len());
+        for symbol in self.symbols.iter() {
+            let uri = self.uri(symbol, channel);
+            workers.push(
+                AsyncWorker::new(
+                    &self.connector,
+                    &uri,
+                    symbol.clone(),
+                    sink.clone(),
+                    tap.clone(),
+                )
+                .await?,
+            );
+        }
+
+        Ok(Subscription::new(rx, workers))
+    }
+
+    /// Listening fugle Chart endpoint as a Stream,
+    /// which owns its connections and closes them on drop.
+    ///
+    /// Example:
+    ///
+    /// ```no_run
+    /// # #[tokio::main]
+    /// # async fn main() -> fugle::schema::Result<()> {
+    /// # use futures_util::StreamExt;
+    /// # use fugle::websocket::IntradayBuilder;
+    ///
+    /// let ws = IntradayBuilder::new().symbol_id("2884").build();
+    ///
+    /// let mut stream = ws.async_chart_stream().await?;
+    /// let response = stream.next().await;
+    ///
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub async fn async_chart_stream(&self) -> Result<Subscription<ChartResponse>> {
+        self.async_stream(Channel::Chart).await
+    }
+
+    /// Listening fugle Meta endpoint as a Stream,
+    /// which owns its connections and closes them on drop.
+    ///
+    /// Example:
+    ///
+    /// ```no_run
+    /// # #[tokio::main]
+    /// # async fn main() -> fugle::schema::Result<()> {
+    /// # use futures_util::StreamExt;
+    /// # use fugle::websocket::IntradayBuilder;
+    ///
+    /// let ws = IntradayBuilder::new().symbol_id("2884").build();
+    ///
+    /// let mut stream = ws.async_meta_stream().await?;
+    /// let response = stream.next().await;
+    ///
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub async fn async_meta_stream(&self) -> Result<Subscription<MetaResponse>> {
+        self.async_stream(Channel::Meta).await
+    }
+
+    /// Listening fugle Quote endpoint as a Stream,
+    /// which owns its connections and closes them on drop.
+    ///
+    /// Example:
+    ///
+    /// ```no_run
+    /// # #[tokio::main]
+    /// # async fn main() -> fugle::schema::Result<()> {
+    /// # use futures_util::StreamExt;
+    /// # use fugle::websocket::IntradayBuilder;
+    ///
+    /// let ws = IntradayBuilder::new().symbol_id("2884").build();
+    ///
+    /// let mut stream = ws.async_quote_stream().await?;
+    /// let response = stream.next().await;
+    ///
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub async fn async_quote_stream(&self) -> Result<Subscription<QuoteResponse>> {
+        self.async_stream(Channel::Quote).await
+    }
+}
+
+impl Intraday {
+    /// Returns the symbol and channel of every running worker.
+    pub fn subscriptions(&self) -> Vec<(Symbol, Channel)> {
+        let mut ret = vec![];
+        #[cfg(feature = "websocket")]
+        ret.extend(
+            self.block_workers
+                .iter()
+                .map(|r| (r.symbol.clone(), r.channel)),
+        );
+        #[cfg(feature = "async-websocket")]
+        ret.extend(
+            self.async_workers
+                .iter()
+                .map(|r| (r.symbol.clone(), r.channel)),
+        );
+        ret
+    }
+
+    /// Starts listening one more stock on a running channel,
+    /// its responses are sent into the receiver that channel returned before.
+    ///
+    /// Other workers are not disturbed,
+    /// and subscribing a stock which is already listened does nothing.
+    ///
+    /// Returns ChannelNotListened error if the channel has not been listened yet.
+    ///
+    /// Example:
+    ///
+    /// ```no_run
+    /// # fn main() -> fugle::schema::Result<()> {
+    /// # use fugle::websocket::{Channel, IntradayBuilder, Symbol};
+    ///
+    /// let mut ws = IntradayBuilder::new().symbol_id("2884").build();
+    ///
+    /// let rx = ws.quote()?;
+    /// ws.subscribe(Symbol::new("2330"), Channel::Quote)?;
+    ///
+    /// # Ok(())
+    /// # }
+    /// ```
+    #[cfg(feature = "websocket")]
+    pub fn subscribe(&mut self, symbol: Symbol, channel: Channel) -> Result<()> {
+        let listened = self
+            .block_workers
+            .iter()
+            .any(|r| r.symbol == symbol && r.channel == channel);
+        if !listened {
+            let running = self.spawn(&symbol, channel)?;
+            self.block_workers.push(running);
+        }
+        Ok(())
+    }
 
-        for worker in self.workers.iter_mut() {
-            worker.stop();
+    /// Starts listening one more stock on a running channel,
+    /// its responses are sent into the receiver that channel returned before.
+    ///
+    /// Other workers are not disturbed,
+    /// and subscribing a stock which is already listened does nothing.
+    ///
+    /// Returns ChannelNotListened error if the channel has not been listened yet.
+    ///
+    /// Example:
+    ///
+    /// ```no_run
+    /// # #[tokio::main]
+    /// # async fn main() -> fugle::schema::Result<()> {
+    /// # use fugle::websocket::{Channel, IntradayBuilder, Symbol};
+    ///
+    /// let mut ws = IntradayBuilder::new().symbol_id("2884").build();
+    ///
+    /// let rx = ws.async_quote().await?;
+    /// ws.async_subscribe(Symbol::new("2330"), Channel::Quote).await?;
+    ///
+    /// # Ok(())
+    /// # }
+    /// ```
+    #[cfg(feature = "async-websocket")]
+    pub async fn async_subscribe(&mut self, symbol: Symbol, channel: Channel) -> Result<()> {
+        let listened = self
+            .async_workers
+            .iter()
+            .any(|r| r.symbol == symbol && r.channel == channel);
+        if !listened {
+            let running = self.async_spawn(&symbol, channel).await?;
+            self.async_workers.push(running);
         }
+        Ok(())
+    }
+
+    /// Stops listening a stock on a channel,
+    /// the other workers and the receiver of that channel keep running.
+    ///
+    /// Returns how the worker exited, or None if the stock was not listened on the channel.
+    ///
+    /// Example:
+    ///
+    /// ```no_run
+    /// # fn main() -> fugle::schema::Result<()> {
+    /// # use std::time::Duration;
+    /// # use fugle::websocket::{Channel, IntradayBuilder, Symbol};
+    ///
+    /// let mut ws = IntradayBuilder::new().symbol_id("2884").build();
+    ///
+    /// let rx = ws.quote()?;
+    /// ws.unsubscribe(Symbol::new("2884"), Channel::Quote, Duration::from_secs(3));
+    ///
+    /// # Ok(())
+    /// # }
+    /// ```
+    #[cfg(feature = "websocket")]
+    pub fn unsubscribe(
+        &mut self,
+        symbol: Symbol,
+        channel: Channel,
+        timeout: Duration,
+    ) -> Option<WorkerExit> {
+        self.stop(|r| r.symbol == symbol && r.channel == channel, timeout)
+            .into_iter()
+            .reduce(|exit, next| if exit.is_clean() { next } else { exit })
+    }
+
+    /// Stops listening a stock on a channel,
+    /// the other workers and the receiver of that channel keep running.
+    ///
+    /// Returns how the worker exited, or None if the stock was not listened on the channel.
+    ///
+    /// Example:
+    ///
+    /// ```no_run
+    /// # #[tokio::main]
+    /// # async fn main() -> fugle::schema::Result<()> {
+    /// # use std::time::Duration;
+    /// # use fugle::websocket::{Channel, IntradayBuilder, Symbol};
+    ///
+    /// let mut ws = IntradayBuilder::new().symbol_id("2884").build();
+    ///
+    /// let rx = ws.async_quote().await?;
+    /// ws.async_unsubscribe(Symbol::new("2884"), Channel::Quote, Duration::from_secs(3))
+    ///     .await;
+    ///
+    /// # Ok(())
+    /// # }
+    /// ```
+    #[cfg(feature = "async-websocket")]
+    pub async fn async_unsubscribe(
+        &mut self,
+        symbol: Symbol,
+        channel: Channel,
+        timeout: Duration,
+    ) -> Option<WorkerExit> {
+        self.async_stop(|r| r.symbol == symbol && r.channel == channel, timeout)
+            .await
+            .into_iter()
+            .reduce(|exit, next| if exit.is_clean() { next } else { exit })
+    }
+
+    /// Gracefully stops all the blocking workers.
+    ///
+    /// Every worker is asked to send a close frame to fugle then exit,
+    /// and the whole shutdown waits no longer than the given timeout.
+    /// A worker thread which is still running after the timeout will be detached.
+    ///
+    /// Returns how each worker exited, in the order they were created.
+    ///
+    /// Example:
+    ///
+    /// ```no_run
+    /// # fn main() -> fugle::schema::Result<()> {
+    /// # use std::time::Duration;
+    /// # use fugle::websocket::IntradayBuilder;
+    ///
+    /// let mut ws = IntradayBuilder::new().symbol_id("2884").build();
+    ///
+    /// let rx = ws.quote()?;
+    /// let exits = ws.shutdown(Duration::from_secs(3));
+    /// assert!(exits.iter().all(|e| e.is_clean()));
+    ///
+    /// # Ok(())
+    /// # }
+    /// ```
+    #[cfg(feature = "websocket")]
+    pub fn shutdown(&mut self, timeout: Duration) -> Vec<WorkerExit> {
+        self.stop(|_| true, timeout)
+    }
+
+    /// Gracefully stops all the async workers.
+    ///
+    /// Every worker is asked to send a close frame to fugle then exit,
+    /// and the whole shutdown waits no longer than the given timeout.
+    /// A worker task which is still running after the timeout will be aborted.
+    ///
+    /// Returns how each worker exited, in the order they were created.
+    ///
+    /// Example:
+    ///
+    /// ```no_run
+    /// # #[tokio::main]
+    /// # async fn main() -> fugle::schema::Result<()> {
+    /// # use std::time::Duration;
+    /// # use fugle::websocket::IntradayBuilder;
+    ///
+    /// let mut ws = IntradayBuilder::new().symbol_id("2884").build();
+    ///
+    /// let rx = ws.async_quote().await?;
+    /// let exits = ws.async_shutdown(Duration::from_secs(3)).await;
+    /// assert!(exits.iter().all(|e| e.is_clean()));
+    ///
+    /// # Ok(())
+    /// # }
+    /// ```
+    #[cfg(feature = "async-websocket")]
+    pub async fn async_shutdown(&mut self, timeout: Duration) -> Vec<WorkerExit> {
+        self.async_stop(|_| true, timeout).await
+    }
+}
+
+impl Drop for Intraday {
+    fn drop(&mut self) {
+        #[cfg(feature = "websocket")]
+        self.shutdown(DROP_TIMEOUT);
+
+        // async workers can not be awaited in here,
+        // they are signaled by their own drop and exit in the background.
+        #[cfg(feature = "async-websocket")]
+        self.async_workers.clear();
+    }
+}
+
+/// How a worker exited while shutting down.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WorkerExit {
+    /// The worker sent the close frame and exited in time.
+    Clean,
+    /// The blocking worker did not exit in time and has been detached.
+    TimedOut,
+    /// The async worker did not exit in time and has been aborted.
+    Aborted,
+    /// The worker panicked.
+    Panicked,
+}
+
+impl WorkerExit {
+    /// Returns true if the worker exited in time.
+    pub fn is_clean(&self) -> bool {
+        *self == WorkerExit::Clean
+    }
+}
+
+// decodes a data frame into a response, control frames are skipped.
+fn decode<T>(msg: &tungstenite::Message) -> Option<Result<T>>
+where
+    T: for<'de> serde::Deserialize<'de>,
+{
+    if !msg.is_text() && !msg.is_binary() {
+        return None;
+    }
+    let decoded = msg
+        .to_text()
+        .map_err(FugleError::from)
+        .and_then(|m| serde_json::from_str(m).map_err(FugleError::from));
+    Some(decoded)
+}
+
+// whether the socket can still be read after the error,
+// an invalid text frame is skipped while a broken stream or protocol ends the connection.
+fn is_transient(e: &tungstenite::Error) -> bool {
+    match e {
+        tungstenite::Error::Io(e) => matches!(
+            e.kind(),
+            std::io::ErrorKind::WouldBlock
+                | std::io::ErrorKind::TimedOut
+                | std::io::ErrorKind::Interrupted
+        ),
+        tungstenite::Error::Utf8 | tungstenite::Error::SendQueueFull(_) => true,
+        _ => false,
     }
 }
 
 pub(crate) trait Worker: Send {
-    fn stop(&mut self);
+    /// Asks the worker to stop without waiting for it.
+    fn signal(&self);
 }
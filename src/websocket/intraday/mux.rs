@@ -0,0 +1,217 @@
+use std::{
+    collections::HashMap,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        mpsc::{self, Receiver, Sender, TryRecvError},
+        Arc,
+    },
+    thread,
+    time::Duration,
+};
+
+use log::error;
+use polling::{Event, Poller};
+
+use super::block::{Connection, Read};
+use crate::{schema::Result, websocket::bounded};
+
+// how long the multiplexer sleeps when nothing happens,
+// it is woken up at once by new connections and done workers anyway.
+const POLL_TIMEOUT: Duration = Duration::from_secs(1);
+
+// a connection handed over to the multiplexer.
+struct Registration {
+    conn: Box<dyn Connection>,
+    done: Arc<AtomicBool>,
+    exited: Sender<()>,
+}
+
+/// One background thread reading the non-blocking sockets of every blocking worker,
+/// the thread exits once the Mux and all of its connections are gone.
+pub(crate) struct Mux {
+    poller: Arc<Poller>,
+    registrations: Sender<Registration>,
+}
+
+impl Mux {
+    pub(crate) fn new() -> Result<Mux> {
+        let poller = Arc::new(Poller::new()?);
+        let (tx, rx) = mpsc::channel();
+
+        let looping = poller.clone();
+        thread::spawn(move || run(looping, rx));
+
+        Ok(Mux {
+            poller,
+            registrations: tx,
+        })
+    }
+
+    /// Starts reading the connection, returns a receiver reporting once it is closed.
+    pub(crate) fn register(
+        &self,
+        conn: Box<dyn Connection>,
+        done: Arc<AtomicBool>,
+    ) -> Result<Receiver<()>> {
+        let (exited, rx) = mpsc::channel();
+        let registration = Registration { conn, done, exited };
+        self.registrations
+            .send(registration)
+            .map_err(|_| std::io::Error::from(std::io::ErrorKind::BrokenPipe))?;
+        self.poller.notify()?;
+        Ok(rx)
+    }
+
+    pub(crate) fn waker(&self) -> Arc<Poller> {
+        self.poller.clone()
+    }
+}
+
+impl Drop for Mux {
+    fn drop(&mut self) {
+        let _ = self.poller.notify();
+    }
+}
+
+fn run(poller: Arc<Poller>, registrations: Receiver<Registration>) {
+    let mut conns: HashMap<usize, Registration> = HashMap::new();
+    let mut next_key = 0;
+    let mut ready = vec![];
+    let mut events = vec![];
+    let mut closing = false;
+
+    loop {
+        loop {
+            match registrations.try_recv() {
+                Ok(registration) => {
+                    let key = next_key;
+                    next_key += 1;
+                    if let Err(e) = poller.add(registration.conn.stream(), Event::readable(key)) {
+                        error!("polling a connection: {}", e);
+                        close(&poller, registration);
+                        continue;
+                    }
+                    bounded::delivering(&registration.done, || registration.conn.connected());
+                    // the handshake may have read some frames already.
+                    ready.push(key);
+                    conns.insert(key, registration);
+                }
+                Err(TryRecvError::Empty) => break,
+                Err(TryRecvError::Disconnected) => {
+                    closing = true;
+                    break;
+                }
+            }
+        }
+
+        let done: Vec<usize> = conns
+            .iter()
+            .filter(|(_, r)| r.done.load(Ordering::SeqCst))
+            .map(|(&key, _)| key)
+            .collect();
+        for key in done {
+            if let Some(registration) = conns.remove(&key) {
+                close(&poller, registration);
+            }
+        }
+
+        for key in ready.drain(..) {
+            let registration = match conns.get_mut(&key) {
+                Some(registration) => registration,
+                None => continue,
+            };
+            let conn = registration.conn.as_mut();
+            if bounded::delivering(&registration.done, || drain(conn)) {
+                if let Some(registration) = conns.remove(&key) {
+                    close(&poller, registration);
+                }
+                continue;
+            }
+            // the poller reports a readiness once, it is armed again for the next one.
+            if let Err(e) = poller.modify(registration.conn.stream(), Event::readable(key)) {
+                error!("polling a connection: {}", e);
+            }
+        }
+
+        if closing && conns.is_empty() {
+            return;
+        }
+
+        events.clear();
+        if let Err(e) = poller.wait(&mut events, Some(POLL_TIMEOUT)) {
+            error!("polling connections: {}", e);
+            continue;
+        }
+        ready.extend(events.iter().map(|ev| ev.key));
+    }
+}
+
+// reads the connection until nothing is left, returns true if it has been closed.
+fn drain(conn: &mut dyn Connection) -> bool {
+    loop {
+        match conn.read() {
+            Read::Message => {}
+            Read::Idle | Read::Failed => return false,
+            Read::Closed => return true,
+        }
+    }
+}
+
+fn close(poller: &Poller, registration: Registration) {
+    let _ = poller.delete(registration.conn.stream());
+    registration.conn.close();
+    let _ = registration.exited.send(());
+}
+
+#[cfg(test)]
+mod test {
+    use std::time::Instant;
+
+    use super::{
+        super::{block::Block, testing, QuoteResponse, Symbol, Worker, WorkerExit},
+        *,
+    };
+
+    #[test]
+    fn test_mux_workers() {
+        // every client gets a quote of the symbol it requested.
+        let (uri, closed) = testing::server(2, |id| vec![testing::quote(id)], false);
+        let mux = Mux::new().unwrap();
+        let (tx, rx) = mpsc::channel::<QuoteResponse>();
+        let tx = Arc::new(tx);
+
+        let mut workers: Vec<Block> = ["2884", "2330"]
+            .iter()
+            .map(|id| {
+                Block::muxed(
+                    &mux,
+                    &Default::default(),
+                    &format!("{}/?{}", uri, id),
+                    Symbol::new(id),
+                    tx.clone(),
+                    testing::tap(),
+                )
+                .unwrap()
+            })
+            .collect();
+
+        let mut ids: Vec<String> = (0..2)
+            .map(|_| {
+                let quote = rx.recv_timeout(Duration::from_secs(3)).unwrap();
+                quote.data.info.symbol_id
+            })
+            .collect();
+        ids.sort();
+        assert_eq!(ids, vec!["2330", "2884"]);
+
+        let deadline = Instant::now() + Duration::from_secs(3);
+        for worker in workers.iter() {
+            worker.signal();
+        }
+        for worker in workers.iter_mut() {
+            assert_eq!(worker.join(deadline), WorkerExit::Clean);
+        }
+        assert!(closed.recv_timeout(Duration::from_secs(3)).unwrap());
+        assert!(closed.recv_timeout(Duration::from_secs(3)).unwrap());
+    }
+}
@@ -0,0 +1,104 @@
+use std::{
+    fs::File,
+    io::{BufWriter, Write},
+    path::Path,
+    sync::{Arc, Mutex, MutexGuard},
+};
+
+use flate2::{write::GzEncoder, Compression};
+use log::error;
+use serde::{Deserialize, Serialize};
+use time::OffsetDateTime;
+
+use super::{Channel, Symbol};
+use crate::schema::Result;
+
+/// One raw websocket frame of a recording, stored as a line of JSON.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct Frame {
+    /// The unix time in nanoseconds the frame was received.
+    pub(crate) received_at: u64,
+    pub(crate) channel: Channel,
+    pub(crate) symbol_id: String,
+    pub(crate) odd_lot: bool,
+    pub(crate) frame: String,
+}
+
+type Writer = GzEncoder<BufWriter<File>>;
+
+// shared by every worker of an Intraday, writes nothing until a recording starts.
+#[derive(Default)]
+pub(crate) struct Recorder {
+    writer: Mutex<Option<Writer>>,
+}
+
+impl Recorder {
+    fn lock(&self) -> MutexGuard<'_, Option<Writer>> {
+        self.writer.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Starts writing into a new gzip file, the running recording is finished first.
+    pub(crate) fn start(&self, path: &Path) -> Result<()> {
+        let file = File::create(path)?;
+        let writer = GzEncoder::new(BufWriter::new(file), Compression::default());
+        if let Some(previous) = self.lock().replace(writer) {
+            finish(previous)?;
+        }
+        Ok(())
+    }
+
+    /// Finishes the running recording, does nothing if there is none.
+    pub(crate) fn stop(&self) -> Result<()> {
+        match self.lock().take() {
+            Some(writer) => finish(writer),
+            None => Ok(()),
+        }
+    }
+
+    fn write(&self, channel: Channel, symbol: &Symbol, text: &str) {
+        let mut guard = self.lock();
+        let writer = match guard.as_mut() {
+            Some(writer) => writer,
+            None => return,
+        };
+
+        let frame = Frame {
+            received_at: OffsetDateTime::now_utc().unix_timestamp_nanos() as u64,
+            channel,
+            symbol_id: symbol.id.clone(),
+            odd_lot: symbol.odd_lot,
+            frame: text.to_string(),
+        };
+        let written = serde_json::to_writer(&mut *writer, &frame)
+            .map_err(std::io::Error::from)
+            .and_then(|_| writer.write_all(b"\n"));
+        if let Err(e) = written {
+            error!("recording a frame: {}", e);
+        }
+    }
+}
+
+fn finish(writer: Writer) -> Result<()> {
+    writer.finish()?.flush()?;
+    Ok(())
+}
+
+// what a worker hands its raw frames to.
+#[derive(Clone)]
+pub(crate) struct Tap {
+    recorder: Arc<Recorder>,
+    channel: Channel,
+}
+
+impl Tap {
+    pub(crate) fn new(recorder: Arc<Recorder>, channel: Channel) -> Self {
+        Tap { recorder, channel }
+    }
+
+    pub(crate) fn frame(&self, symbol: &Symbol, msg: &tungstenite::Message) {
+        if let tungstenite::Message::Text(ref text) = *msg {
+            self.recorder.write(self.channel, symbol, text);
+        }
+    }
+}
@@ -0,0 +1,416 @@
+use std::{
+    fs::File,
+    io::{BufRead, BufReader},
+    path::{Path, PathBuf},
+    thread,
+    time::{Duration, Instant},
+};
+
+use flate2::read::GzDecoder;
+use log::error;
+
+use super::{record::Frame, Channel, Event};
+use crate::{
+    errors::FugleError,
+    schema::{ChartResponse, MetaResponse, QuoteResponse, Result},
+};
+
+/// How fast a recording is replayed.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Pace {
+    /// The frames keep the intervals they were received with.
+    Original,
+    /// The intervals are shortened by the factor, e.g. 2.0 replays twice as fast.
+    Accelerated(f64),
+    /// The frames are delivered without waiting.
+    AsFastAsPossible,
+}
+
+impl Pace {
+    // how long after the first frame a frame received `elapsed` later is delivered,
+    // a tiny factor stretching it beyond a Duration waits as long as one can.
+    fn scale(&self, elapsed: Duration) -> Option<Duration> {
+        match *self {
+            Pace::Original => Some(elapsed),
+            Pace::Accelerated(factor) if factor > 0.0 => Some(
+                Duration::try_from_secs_f64(elapsed.as_secs_f64() / factor)
+                    .unwrap_or(Duration::MAX),
+            ),
+            Pace::Accelerated(_) | Pace::AsFastAsPossible => None,
+        }
+    }
+}
+
+/// Replays a recording made by
+/// [`Intraday::record`](super::Intraday::record)
+/// through the same receivers as listening the fugle endpoints.
+///
+/// Every call reads the recording on its own thread from the beginning,
+/// and the receiver is disconnected at the end of the recording.
+pub struct Replay {
+    path: PathBuf,
+    pace: Pace,
+}
+
+impl Replay {
+    /// Returns a Replay of the recording file at the original pace.
+    pub fn new<P: AsRef<Path>>(path: P) -> Replay {
+        Replay {
+            path: path.as_ref().to_path_buf(),
+            pace: Pace::Original,
+        }
+    }
+
+    /// Setup how fast the recording is replayed.
+    pub fn pace(mut self, pace: Pace) -> Replay {
+        self.pace = pace;
+        self
+    }
+
+    // reads the frames of the channel, or of all the channels on None,
+    // until the deliver returns false.
+    fn play<F>(&self, channel: Option<Channel>, mut deliver: F) -> Result<()>
+    where
+        F: FnMut(Frame) -> bool + Send + 'static,
+    {
+        let lines = BufReader::new(GzDecoder::new(File::open(&self.path)?)).lines();
+        let pace = self.pace;
+
+        thread::spawn(move || {
+            let mut first: Option<(u64, Instant)> = None;
+            for line in lines {
+                let frame: Frame = match line
+                    .map_err(FugleError::from)
+                    .and_then(|l| serde_json::from_str(&l).map_err(FugleError::from))
+                {
+                    Ok(frame) => frame,
+                    Err(e) => {
+                        error!("replaying a recording: {}", e);
+                        break;
+                    }
+                };
+                if matches!(channel, Some(c) if c != frame.channel) {
+                    continue;
+                }
+
+                let (base, start) = *first.get_or_insert((frame.received_at, Instant::now()));
+                let elapsed = Duration::from_nanos(frame.received_at.saturating_sub(base));
+                if let Some(due) = pace.scale(elapsed) {
+                    thread::sleep(due.saturating_sub(start.elapsed()));
+                }
+
+                if !deliver(frame) {
+                    break;
+                }
+            }
+        });
+        Ok(())
+    }
+}
+
+impl Frame {
+    fn decode<T>(&self) -> Option<T>
+    where
+        T: for<'de> serde::Deserialize<'de>,
+    {
+        match serde_json::from_str(&self.frame) {
+            Ok(m) => Some(m),
+            Err(e) => {
+                error!("replaying a frame of {}: {}", self.symbol_id, e);
+                None
+            }
+        }
+    }
+
+    fn event(&self) -> Option<Event> {
+        match self.channel {
+            Channel::Chart => self.decode::<ChartResponse>().map(Event::from),
+            Channel::Quote => self.decode::<QuoteResponse>().map(Event::from),
+            Channel::Meta => self.decode::<MetaResponse>().map(Event::from),
+        }
+    }
+}
+
+#[cfg(feature = "websocket")]
+impl Replay {
+    fn replay<T>(&self, channel: Channel) -> Result<std::sync::mpsc::Receiver<T>>
+    where
+        T: for<'de> serde::Deserialize<'de> + Send + 'static,
+    {
+        let (tx, rx) = std::sync::mpsc::channel();
+        self.play(Some(channel), move |frame| match frame.decode() {
+            Some(m) => tx.send(m).is_ok(),
+            None => true,
+        })?;
+        Ok(rx)
+    }
+
+    /// Replays the recorded Chart responses.
+    ///
+    /// Example:
+    ///
+    /// ```no_run
+    /// # fn main() -> fugle::schema::Result<()> {
+    /// # use fugle::websocket::{Pace, Replay};
+    ///
+    /// let rx = Replay::new("2884.jsonl.gz").pace(Pace::Accelerated(10.0)).chart()?;
+    /// let response = rx.recv()?;
+    ///
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn chart(&self) -> Result<std::sync::mpsc::Receiver<ChartResponse>> {
+        self.replay(Channel::Chart)
+    }
+
+    /// Replays the recorded Meta responses.
+    ///
+    /// Example:
+    ///
+    /// ```no_run
+    /// # fn main() -> fugle::schema::Result<()> {
+    /// # use fugle::websocket::{Pace, Replay};
+    ///
+    /// let rx = Replay::new("2884.jsonl.gz").pace(Pace::Original).meta()?;
+    /// let response = rx.recv()?;
+    ///
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn meta(&self) -> Result<std::sync::mpsc::Receiver<MetaResponse>> {
+        self.replay(Channel::Meta)
+    }
+
+    /// Replays the recorded Quote responses.
+    ///
+    /// Example:
+    ///
+    /// ```no_run
+    /// # fn main() -> fugle::schema::Result<()> {
+    /// # use fugle::websocket::{Pace, Replay};
+    ///
+    /// let rx = Replay::new("2884.jsonl.gz").pace(Pace::AsFastAsPossible).quote()?;
+    /// let response = rx.recv()?;
+    ///
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn quote(&self) -> Result<std::sync::mpsc::Receiver<QuoteResponse>> {
+        self.replay(Channel::Quote)
+    }
+
+    /// Replays the recorded responses of all the channels in their recorded order.
+    ///
+    /// Example:
+    ///
+    /// ```no_run
+    /// # fn main() -> fugle::schema::Result<()> {
+    /// # use fugle::websocket::Replay;
+    ///
+    /// let rx = Replay::new("2884.jsonl.gz").events()?;
+    /// let event = rx.recv()?;
+    ///
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn events(&self) -> Result<std::sync::mpsc::Receiver<Event>> {
+        let (tx, rx) = std::sync::mpsc::channel();
+        self.play(None, move |frame| match frame.event() {
+            Some(e) => tx.send(e).is_ok(),
+            None => true,
+        })?;
+        Ok(rx)
+    }
+}
+
+#[cfg(feature = "async-websocket")]
+impl Replay {
+    fn async_replay<T>(&self, channel: Channel) -> Result<tokio::sync::mpsc::UnboundedReceiver<T>>
+    where
+        T: for<'de> serde::Deserialize<'de> + Send + 'static,
+    {
+        let (tx, rx) = tokio::sync::mpsc::unbounded_channel();
+        self.play(Some(channel), move |frame| match frame.decode() {
+            Some(m) => tx.send(m).is_ok(),
+            None => true,
+        })?;
+        Ok(rx)
+    }
+
+    /// Replays the recorded Chart responses.
+    ///
+    /// Example:
+    ///
+    /// ```no_run
+    /// # #[tokio::main]
+    /// # async fn main() -> fugle::schema::Result<()> {
+    /// # use fugle::websocket::{Pace, Replay};
+    ///
+    /// let mut rx = Replay::new("2884.jsonl.gz").pace(Pace::Accelerated(10.0)).async_chart()?;
+    /// let response = rx.recv().await;
+    ///
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn async_chart(&self) -> Result<tokio::sync::mpsc::UnboundedReceiver<ChartResponse>> {
+        self.async_replay(Channel::Chart)
+    }
+
+    /// Replays the recorded Meta responses.
+    ///
+    /// Example:
+    ///
+    /// ```no_run
+    /// # #[tokio::main]
+    /// # async fn main() -> fugle::schema::Result<()> {
+    /// # use fugle::websocket::{Pace, Replay};
+    ///
+    /// let mut rx = Replay::new("2884.jsonl.gz").pace(Pace::Original).async_meta()?;
+    /// let response = rx.recv().await;
+    ///
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn async_meta(&self) -> Result<tokio::sync::mpsc::UnboundedReceiver<MetaResponse>> {
+        self.async_replay(Channel::Meta)
+    }
+
+    /// Replays the recorded Quote responses.
+    ///
+    /// Example:
+    ///
+    /// ```no_run
+    /// # #[tokio::main]
+    /// # async fn main() -> fugle::schema::Result<()> {
+    /// # use fugle::websocket::{Pace, Replay};
+    ///
+    /// let mut rx = Replay::new("2884.jsonl.gz").pace(Pace::AsFastAsPossible).async_quote()?;
+    /// let response = rx.recv().await;
+    ///
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn async_quote(&self) -> Result<tokio::sync::mpsc::UnboundedReceiver<QuoteResponse>> {
+        self.async_replay(Channel::Quote)
+    }
+
+    /// Replays the recorded responses of all the channels in their recorded order.
+    ///
+    /// Example:
+    ///
+    /// ```no_run
+    /// # #[tokio::main]
+    /// # async fn main() -> fugle::schema::Result<()> {
+    /// # use fugle::websocket::Replay;
+    ///
+    /// let mut rx = Replay::new("2884.jsonl.gz").async_events()?;
+    /// let event = rx.recv().await;
+    ///
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn async_events(&self) -> Result<tokio::sync::mpsc::UnboundedReceiver<Event>> {
+        let (tx, rx) = tokio::sync::mpsc::unbounded_channel();
+        self.play(None, move |frame| match frame.event() {
+            Some(e) => tx.send(e).is_ok(),
+            None => true,
+        })?;
+        Ok(rx)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::sync::Arc;
+
+    use super::{
+        super::{
+            record::{Recorder, Tap},
+            Symbol,
+        },
+        *,
+    };
+
+    const QUOTE: &str = r#"{"apiVersion":"0.3.0","data":{"info":{"symbolId":"2884"}}}"#;
+    const META: &str = r#"{"apiVersion":"0.3.0","data":{"info":{"symbolId":"2330"}}}"#;
+
+    fn record(path: &Path) {
+        let recorder = Arc::new(Recorder::default());
+        let quote = Tap::new(recorder.clone(), Channel::Quote);
+        let meta = Tap::new(recorder.clone(), Channel::Meta);
+        let text = |s: &str| tungstenite::Message::Text(s.to_string());
+
+        // frames before the recording starts are not written.
+        quote.frame(&Symbol::new("2884"), &text(QUOTE));
+        recorder.start(path).unwrap();
+        quote.frame(&Symbol::new("2884"), &text(QUOTE));
+        meta.frame(&Symbol::odd_lot("2330"), &text(META));
+        quote.frame(&Symbol::new("2884"), &tungstenite::Message::Ping(vec![]));
+        quote.frame(&Symbol::new("2884"), &text("not a json"));
+        quote.frame(&Symbol::new("2884"), &text(QUOTE));
+        recorder.stop().unwrap();
+        meta.frame(&Symbol::odd_lot("2330"), &text(META));
+    }
+
+    #[test]
+    fn test_pace_scale() {
+        let elapsed = Duration::from_secs(10);
+        assert_eq!(Pace::Original.scale(elapsed), Some(elapsed));
+        assert_eq!(
+            Pace::Accelerated(4.0).scale(elapsed),
+            Some(Duration::from_millis(2500))
+        );
+        assert_eq!(Pace::Accelerated(1e-20).scale(elapsed), Some(Duration::MAX));
+        assert_eq!(Pace::Accelerated(0.0).scale(elapsed), None);
+        assert_eq!(Pace::AsFastAsPossible.scale(elapsed), None);
+    }
+
+    #[cfg(feature = "websocket")]
+    #[test]
+    fn test_record_replay() {
+        let path =
+            std::env::temp_dir().join(format!("fugle-replay-{}.jsonl.gz", std::process::id()));
+        record(&path);
+
+        let replay = Replay::new(&path).pace(Pace::AsFastAsPossible);
+        let quotes: Vec<QuoteResponse> = replay.quote().unwrap().iter().collect();
+        assert_eq!(quotes.len(), 2);
+        assert_eq!(quotes[0].data.info.symbol_id, "2884");
+
+        let events: Vec<Channel> = replay
+            .events()
+            .unwrap()
+            .iter()
+            .map(|e| e.channel())
+            .collect();
+        assert_eq!(events, vec![Channel::Quote, Channel::Meta, Channel::Quote]);
+
+        std::fs::remove_file(&path).unwrap();
+        assert!(Replay::new(&path).quote().is_err());
+    }
+
+    #[cfg(feature = "async-websocket")]
+    #[tokio::test]
+    async fn test_async_record_replay() {
+        let path = std::env::temp_dir().join(format!(
+            "fugle-async-replay-{}.jsonl.gz",
+            std::process::id()
+        ));
+        record(&path);
+
+        let replay = Replay::new(&path).pace(Pace::Accelerated(1000.0));
+        let mut rx = replay.async_meta().unwrap();
+        let meta = rx.recv().await.unwrap();
+        assert_eq!(meta.data.info.symbol_id, "2330");
+        assert!(rx.recv().await.is_none());
+
+        let mut rx = replay.async_events().unwrap();
+        let mut events = vec![];
+        while let Some(event) = rx.recv().await {
+            events.push(event.channel());
+        }
+        assert_eq!(events, vec![Channel::Quote, Channel::Meta, Channel::Quote]);
+
+        std::fs::remove_file(&path).unwrap();
+    }
+}
@@ -0,0 +1,99 @@
+#[cfg(feature = "async-websocket")]
+use futures_util::future::BoxFuture;
+
+use super::{ConnectionEvent, Symbol};
+#[cfg(feature = "websocket")]
+use crate::websocket::broadcast::Broadcast;
+use crate::{errors::FugleError, websocket::bounded};
+
+/// Where a blocking worker delivers the responses of its symbol to.
+#[cfg(feature = "websocket")]
+pub(crate) trait BlockSink<T>: Send + Sync {
+    /// Returns false once the receiving side has gone.
+    fn send(&self, symbol: &Symbol, msg: T) -> bool;
+
+    /// Reports an error of the symbol, which has been logged already.
+    fn fail(&self, _symbol: &Symbol, _err: FugleError) {}
+
+    /// Reports the connection of the symbol has been opened or closed.
+    fn connection(&self, _symbol: &Symbol, _event: ConnectionEvent) {}
+
+    /// Whether a call may wait, e.g. on a user callback or a full receiver,
+    /// such a sink gets a worker thread of its own instead of the multiplexer.
+    fn may_block(&self) -> bool {
+        false
+    }
+}
+
+/// Where an async worker delivers the responses of its symbol to.
+#[cfg(feature = "async-websocket")]
+pub(crate) trait AsyncSink<T>: Send + Sync {
+    /// Returns false once the receiving side has gone.
+    fn send<'a>(&'a self, symbol: &'a Symbol, msg: T) -> BoxFuture<'a, bool>;
+
+    /// Reports an error of the symbol, which has been logged already.
+    fn fail<'a>(&'a self, _symbol: &'a Symbol, _err: FugleError) -> BoxFuture<'a, ()> {
+        Box::pin(async {})
+    }
+
+    /// Reports the connection of the symbol has been opened or closed.
+    fn connection<'a>(&'a self, _symbol: &'a Symbol, _event: ConnectionEvent) -> BoxFuture<'a, ()> {
+        Box::pin(async {})
+    }
+}
+
+#[cfg(feature = "websocket")]
+impl<T: Send> BlockSink<T> for std::sync::mpsc::Sender<T> {
+    fn send(&self, _: &Symbol, msg: T) -> bool {
+        std::sync::mpsc::Sender::send(self, msg).is_ok()
+    }
+}
+
+#[cfg(feature = "websocket")]
+impl<T: Send> BlockSink<T> for bounded::Sender<T> {
+    fn send(&self, symbol: &Symbol, msg: T) -> bool {
+        bounded::Sender::send(self, symbol, msg)
+    }
+
+    fn may_block(&self) -> bool {
+        self.overflow() == bounded::Overflow::Block
+    }
+}
+
+#[cfg(feature = "websocket")]
+impl<T: Clone + Send> BlockSink<T> for Broadcast<T> {
+    // keeps listening without consumers, they may subscribe later.
+    fn send(&self, symbol: &Symbol, msg: T) -> bool {
+        Broadcast::send(self, symbol, msg);
+        true
+    }
+
+    // consumers may subscribe with the Block policy after the worker has been placed.
+    fn may_block(&self) -> bool {
+        true
+    }
+}
+
+#[cfg(feature = "async-websocket")]
+impl<T: Send + 'static> AsyncSink<T> for tokio::sync::mpsc::UnboundedSender<T> {
+    fn send<'a>(&'a self, _: &'a Symbol, msg: T) -> BoxFuture<'a, bool> {
+        let sent = tokio::sync::mpsc::UnboundedSender::send(self, msg).is_ok();
+        Box::pin(async move { sent })
+    }
+}
+
+#[cfg(feature = "async-websocket")]
+impl<T: Send + 'static> AsyncSink<T> for bounded::Sender<T> {
+    fn send<'a>(&'a self, symbol: &'a Symbol, msg: T) -> BoxFuture<'a, bool> {
+        Box::pin(self.async_send(symbol, msg))
+    }
+}
+
+#[cfg(feature = "async-websocket")]
+impl<T: Send + 'static> AsyncSink<T> for tokio::sync::broadcast::Sender<T> {
+    // keeps listening without consumers, they may subscribe later.
+    fn send<'a>(&'a self, _: &'a Symbol, msg: T) -> BoxFuture<'a, bool> {
+        let _ = tokio::sync::broadcast::Sender::send(self, msg);
+        Box::pin(async { true })
+    }
+}
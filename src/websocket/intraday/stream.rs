@@ -0,0 +1,100 @@
+use std::{
+    pin::Pin,
+    task::{Context, Poll},
+    time::{Duration, Instant},
+};
+
+use futures_util::{future::BoxFuture, Stream};
+use tokio::sync::mpsc::{UnboundedReceiver, UnboundedSender};
+
+use super::{AsyncSink, AsyncWorker, Symbol, Worker, WorkerExit};
+use crate::{errors::FugleError, schema::Result};
+
+// delivers both the responses and the errors into the stream.
+pub(crate) struct StreamSink<T>(pub(crate) UnboundedSender<Result<T>>);
+
+impl<T: Send + 'static> AsyncSink<T> for StreamSink<T> {
+    fn send<'a>(&'a self, _: &'a Symbol, msg: T) -> BoxFuture<'a, bool> {
+        let sent = self.0.send(Ok(msg)).is_ok();
+        Box::pin(async move { sent })
+    }
+
+    fn fail<'a>(&'a self, _: &'a Symbol, err: FugleError) -> BoxFuture<'a, ()> {
+        let _ = self.0.send(Err(err));
+        Box::pin(async {})
+    }
+}
+
+/// A stream of responses from one fugle endpoint of all the symbols,
+/// it owns its workers so dropping the stream closes the connections.
+///
+/// Besides the responses, the errors of the workers are also yielded,
+/// and the stream ends once all of its workers have stopped.
+pub struct Subscription<T> {
+    rx: UnboundedReceiver<Result<T>>,
+    workers: Vec<AsyncWorker>,
+}
+
+impl<T> Subscription<T> {
+    pub(crate) fn new(rx: UnboundedReceiver<Result<T>>, workers: Vec<AsyncWorker>) -> Self {
+        Subscription { rx, workers }
+    }
+
+    /// Gracefully stops the workers of this stream,
+    /// see [`Intraday::async_shutdown`](super::Intraday::async_shutdown).
+    pub async fn shutdown(mut self, timeout: Duration) -> Vec<WorkerExit> {
+        let deadline = Instant::now() + timeout;
+
+        for worker in self.workers.iter() {
+            worker.signal();
+        }
+
+        let mut exits = Vec::with_capacity(self.workers.len());
+        for mut worker in self.workers.drain(..) {
+            exits.push(worker.join(deadline).await);
+        }
+        exits
+    }
+}
+
+impl<T> Stream for Subscription<T> {
+    type Item = Result<T>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        self.rx.poll_recv(cx)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::sync::Arc;
+
+    use futures_util::StreamExt;
+    use tokio::sync::mpsc::unbounded_channel;
+
+    use super::{
+        super::{testing, QuoteResponse},
+        *,
+    };
+
+    #[tokio::test]
+    async fn test_subscription_stream() {
+        let frames = vec![testing::quote("2884"), testing::text("not a json")];
+        let (uri, closed) = testing::async_server(frames, false).await;
+
+        let (tx, rx) = unbounded_channel();
+        let sink: Arc<dyn AsyncSink<QuoteResponse>> = Arc::new(StreamSink(tx));
+        let worker = testing::async_worker(&uri, sink).await;
+        let mut stream = Subscription::new(rx, vec![worker]);
+
+        let quote = stream.next().await.unwrap().unwrap();
+        assert_eq!(quote.data.info.symbol_id, "2884");
+        assert!(matches!(
+            stream.next().await,
+            Some(Err(FugleError::SerdeJson(_)))
+        ));
+
+        drop(stream);
+        assert!(closed.await.unwrap());
+    }
+}
@@ -0,0 +1,209 @@
+// the local websocket servers and the workers the unit tests of the intraday modules share.
+
+use std::sync::Arc;
+
+use super::{record::Tap, Channel};
+
+/// What a test server sends each client after the handshake.
+#[derive(Clone)]
+pub(crate) enum Frame {
+    Text(String),
+    // written to the socket as is, e.g. to break the protocol.
+    Raw(&'static [u8]),
+}
+
+pub(crate) fn text(frame: &str) -> Frame {
+    Frame::Text(frame.to_string())
+}
+
+/// A quote frame of the symbol.
+pub(crate) fn quote(symbol_id: &str) -> Frame {
+    Frame::Text(format!(
+        r#"{{"apiVersion":"0.3.0","data":{{"info":{{"symbolId":"{}"}}}}}}"#,
+        symbol_id
+    ))
+}
+
+/// A frame of an opcode the protocol does not define.
+pub(crate) fn broken() -> Frame {
+    Frame::Raw(&[0x83, 0x00])
+}
+
+pub(crate) fn tap() -> Tap {
+    Tap::new(Arc::default(), Channel::Quote)
+}
+
+#[cfg(feature = "websocket")]
+pub(crate) use block::*;
+
+#[cfg(feature = "websocket")]
+mod block {
+    use std::{
+        io::Write,
+        net::TcpListener,
+        sync::{
+            mpsc::{channel, Receiver},
+            Arc,
+        },
+        thread,
+    };
+
+    use tungstenite::{handshake::server::Request, Message};
+
+    use super::{
+        super::{connect::Connector, BlockSink, BlockWorker, Symbol},
+        Frame,
+    };
+
+    /// A local websocket server accepting the clients, which sends each client the frames
+    /// made from the query of its request, closing the connection afterwards if asked.
+    /// Reports for every client whether it sent a close frame.
+    // the handshake callback returns the error response tungstenite defines.
+    #[allow(clippy::result_large_err)]
+    pub(crate) fn server<F>(clients: usize, frames: F, close: bool) -> (String, Receiver<bool>)
+    where
+        F: Fn(&str) -> Vec<Frame> + Send + Sync + 'static,
+    {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let uri = format!("ws://{}", listener.local_addr().unwrap());
+        let (closed_tx, closed_rx) = channel();
+        let frames = Arc::new(frames);
+
+        thread::spawn(move || {
+            for _ in 0..clients {
+                let (stream, _) = listener.accept().unwrap();
+                let (closed_tx, frames) = (closed_tx.clone(), frames.clone());
+                thread::spawn(move || {
+                    let mut query = String::new();
+                    let mut ws = tungstenite::accept_hdr(stream, |req: &Request, res| {
+                        query = req.uri().query().unwrap_or_default().to_string();
+                        Ok(res)
+                    })
+                    .unwrap();
+                    for frame in frames(&query) {
+                        match frame {
+                            Frame::Text(text) => ws.write_message(Message::Text(text)).unwrap(),
+                            Frame::Raw(bytes) => ws.get_mut().write_all(bytes).unwrap(),
+                        }
+                    }
+                    if close {
+                        let _ = ws.close(None);
+                    }
+                    loop {
+                        match ws.read_message() {
+                            Ok(Message::Close(_)) => {
+                                let _ = closed_tx.send(true);
+                                break;
+                            }
+                            Ok(_) => {}
+                            Err(_) => {
+                                let _ = closed_tx.send(false);
+                                break;
+                            }
+                        }
+                    }
+                });
+            }
+        });
+
+        (uri, closed_rx)
+    }
+
+    /// A local websocket server which accepts one client and never sends anything.
+    pub(crate) fn silent_server() -> (String, Receiver<bool>) {
+        server(1, |_| vec![], false)
+    }
+
+    /// A blocking worker listening on the quotes of 2884.
+    pub(crate) fn worker<T>(uri: &str, sink: Arc<dyn BlockSink<T>>) -> BlockWorker
+    where
+        T: for<'de> serde::Deserialize<'de> + Send + 'static,
+    {
+        BlockWorker::new(
+            &Connector::default(),
+            uri,
+            Symbol::new("2884"),
+            sink,
+            super::tap(),
+        )
+        .unwrap()
+    }
+}
+
+#[cfg(feature = "async-websocket")]
+pub(crate) use r#async::*;
+
+#[cfg(feature = "async-websocket")]
+mod r#async {
+    use std::sync::Arc;
+
+    use futures_util::{SinkExt, StreamExt};
+    use tokio::{net::TcpListener, sync::oneshot};
+    use tokio_tungstenite::tungstenite::Message;
+
+    use super::{
+        super::{connect::Connector, AsyncSink, AsyncWorker, Symbol},
+        Frame,
+    };
+
+    /// A local websocket server which accepts one client and sends it the frames,
+    /// closing the connection afterwards if asked.
+    /// Reports whether the client sent a close frame.
+    pub(crate) async fn async_server(
+        frames: Vec<Frame>,
+        close: bool,
+    ) -> (String, oneshot::Receiver<bool>) {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let uri = format!("ws://{}", listener.local_addr().unwrap());
+        let (closed_tx, closed_rx) = oneshot::channel();
+
+        tokio::spawn(async move {
+            let (stream, _) = listener.accept().await.unwrap();
+            let mut ws = tokio_tungstenite::accept_async(stream).await.unwrap();
+            for frame in frames {
+                match frame {
+                    Frame::Text(text) => ws.send(Message::Text(text)).await.unwrap(),
+                    Frame::Raw(bytes) => {
+                        let stream = ws.get_mut();
+                        stream.writable().await.unwrap();
+                        stream.try_write(bytes).unwrap();
+                    }
+                }
+            }
+            if close {
+                let _ = ws.close(None).await;
+            }
+            let closed = loop {
+                match ws.next().await {
+                    Some(Ok(Message::Close(_))) => break true,
+                    Some(Ok(_)) => {}
+                    _ => break false,
+                }
+            };
+            let _ = closed_tx.send(closed);
+        });
+
+        (uri, closed_rx)
+    }
+
+    /// A local websocket server which accepts one client and never sends anything.
+    pub(crate) async fn async_silent_server() -> (String, oneshot::Receiver<bool>) {
+        async_server(vec![], false).await
+    }
+
+    /// An async worker listening on the quotes of 2884.
+    pub(crate) async fn async_worker<T>(uri: &str, sink: Arc<dyn AsyncSink<T>>) -> AsyncWorker
+    where
+        T: for<'de> serde::Deserialize<'de> + Send + 'static,
+    {
+        AsyncWorker::new(
+            &Connector::default(),
+            uri,
+            Symbol::new("2884"),
+            sink,
+            super::tap(),
+        )
+        .await
+        .unwrap()
+    }
+}
@@ -1,2 +1,26 @@
+pub mod book;
+pub mod bounded;
+#[cfg(feature = "websocket")]
+pub mod broadcast;
+pub use book::ChartBook;
+pub use bounded::Overflow;
+#[cfg(feature = "websocket")]
+pub use broadcast::Broadcast;
 pub mod intraday;
-pub use intraday::IntradayBuilder;
+#[cfg(feature = "async-websocket")]
+pub mod runtime;
+#[cfg(feature = "websocket")]
+pub use intraday::Handler;
+#[cfg(feature = "async-websocket")]
+pub use intraday::{AsyncHandler, Subscription};
+pub use intraday::{
+    Channel, ConnectionEvent, ConnectionUsage, Envelope, Event, IntradayBuilder, Pace, QuoteDelta,
+    QuoteDiff, Replay, StatusChange, Symbol, WorkerExit,
+};
+#[cfg(any(
+    all(feature = "websocket", feature = "query"),
+    all(feature = "async-websocket", feature = "async-query")
+))]
+pub use intraday::{Feed, Trade};
+#[cfg(feature = "async-websocket")]
+pub use runtime::{Executor, Tokio};
@@ -0,0 +1,158 @@
+use std::{fmt, future::Future, sync::Arc, time::Duration};
+
+use futures_util::future::{self, BoxFuture, Either};
+#[cfg(feature = "async-query")]
+use serde::de::DeserializeOwned;
+
+#[cfg(feature = "async-query")]
+use crate::{
+    errors::FugleError,
+    http::{AsyncRequest, Query, Request, RestfulBuilder},
+    schema::Result,
+};
+
+/// Spawns the background tasks of the async websocket workers.
+///
+/// The workers do their IO and timing without any runtime,
+/// so implementing it lets them run on the async runtime of your choice,
+/// a closure taking the future works as well.
+///
+/// By default the workers are spawned on the tokio runtime by [`Tokio`].
+///
+/// The listeners fetching from the REST endpoints as well, i.e. the async feeds, trades
+/// and the REST fallback, still need a tokio runtime with the `async-query` feature,
+/// as its reqwest client runs on tokio only.
+/// They must be started inside one, where their requests run whatever the executor is,
+/// or they fail with TokioRuntimeRequired error.
+///
+/// # Example:
+///
+/// ```no_run
+/// # use futures_util::future::BoxFuture;
+/// # use fugle::websocket::IntradayBuilder;
+/// let ws = IntradayBuilder::new()
+///     // e.g. `async_std::task::spawn(task);` on async-std.
+///     .executor(|task: BoxFuture<'static, ()>| {
+///         std::thread::spawn(move || async_io::block_on(task));
+///     })
+///     .build();
+/// ```
+pub trait Executor: Send + Sync + 'static {
+    fn spawn(&self, task: BoxFuture<'static, ()>);
+}
+
+impl<F> Executor for F
+where
+    F: Fn(BoxFuture<'static, ()>) + Send + Sync + 'static,
+{
+    fn spawn(&self, task: BoxFuture<'static, ()>) {
+        self(task)
+    }
+}
+
+/// Spawns on the tokio runtime of the caller, which must be inside one.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct Tokio;
+
+impl Executor for Tokio {
+    fn spawn(&self, task: BoxFuture<'static, ()>) {
+        tokio::spawn(task);
+    }
+}
+
+// the executor the workers of an Intraday are spawned by.
+#[derive(Clone)]
+pub(crate) struct Spawner(pub(crate) Arc<dyn Executor>);
+
+impl Default for Spawner {
+    fn default() -> Self {
+        Spawner(Arc::new(Tokio))
+    }
+}
+
+impl fmt::Debug for Spawner {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("Spawner")
+    }
+}
+
+impl Spawner {
+    pub(crate) fn spawn(&self, task: BoxFuture<'static, ()>) {
+        self.0.spawn(task)
+    }
+}
+
+// the REST client of the async workers, its requests are spawned on the tokio runtime
+// it was created in, to be awaited on any executor.
+#[cfg(feature = "async-query")]
+#[derive(Clone)]
+pub(crate) struct Rest {
+    handle: tokio::runtime::Handle,
+    client: Arc<AsyncRequest<'static>>,
+}
+
+#[cfg(feature = "async-query")]
+impl Rest {
+    // fails outside of a tokio runtime.
+    pub(crate) fn new(token: &str) -> Result<Rest> {
+        let handle =
+            tokio::runtime::Handle::try_current().map_err(|_| FugleError::TokioRuntimeRequired)?;
+        let client = RestfulBuilder::new().token(token).build_async()?;
+        Ok(Rest {
+            handle,
+            client: Arc::new(client.into_owned()),
+        })
+    }
+
+    pub(crate) async fn call<R>(&self, request: R) -> Result<R::Response>
+    where
+        R: Request,
+        R::Response: Send + 'static,
+    {
+        let task = spawn_get(
+            &self.handle,
+            self.client.clone(),
+            R::REQUEST_URL,
+            request.queries(),
+        );
+        task.await.map_err(std::io::Error::other)?
+    }
+}
+
+// kept apart from the call to spawn nothing borrowed by the request.
+#[cfg(feature = "async-query")]
+fn spawn_get<T>(
+    handle: &tokio::runtime::Handle,
+    client: Arc<AsyncRequest<'static>>,
+    url: &'static str,
+    queries: Vec<Query>,
+) -> tokio::task::JoinHandle<Result<T>>
+where
+    T: DeserializeOwned + Send + 'static,
+{
+    handle.spawn(async move { client.get(url, queries).await })
+}
+
+// awaits the future no longer than the duration, returns None once it is elapsed.
+pub(crate) async fn timeout<F: Future>(duration: Duration, fut: F) -> Option<F::Output> {
+    futures_util::pin_mut!(fut);
+    match future::select(fut, async_io::Timer::after(duration)).await {
+        Either::Left((output, _)) => Some(output),
+        Either::Right(_) => None,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_timeout_without_runtime() {
+        let ready = async_io::block_on(timeout(Duration::from_secs(3), async { 1 }));
+        assert_eq!(ready, Some(1));
+
+        let pending = future::pending::<()>();
+        let elapsed = async_io::block_on(timeout(Duration::from_millis(10), pending));
+        assert_eq!(elapsed, None);
+    }
+}
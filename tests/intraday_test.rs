@@ -2,6 +2,7 @@ use fugle::{
     errors::FugleError,
     http::intraday::{ChartRequest, DealtsRequest, MetaRequest, QuoteRequest, VolumesRequest},
     http::RestfulBuilder,
+    schema::SecurityType,
 };
 mod util;
 
@@ -10,11 +11,11 @@ fn test_intraday_chart_pass() {
     let it = RestfulBuilder::new().build().unwrap();
     let chart = it.call(ChartRequest::new()).unwrap();
     assert_eq!(chart.data.info.symbol_id, "2884");
-    assert_eq!(chart.data.info.typ, "EQUITY");
+    assert_eq!(chart.data.info.typ, SecurityType::Equity);
 
     let chart = it.call(ChartRequest::default().odd_lot(true)).unwrap();
     assert_eq!(chart.data.info.symbol_id, "2884");
-    assert_eq!(chart.data.info.typ, "ODDLOT");
+    assert_eq!(chart.data.info.typ, SecurityType::OddLot);
 }
 
 #[tokio::test]
@@ -22,14 +23,23 @@ async fn test_intraday_async_chart_pass() {
     let it = RestfulBuilder::new().build_async().unwrap();
     let chart = it.call(ChartRequest::new()).await.unwrap();
     assert_eq!(chart.data.info.symbol_id, "2884");
-    assert_eq!(chart.data.info.typ, "EQUITY");
+    assert_eq!(chart.data.info.typ, SecurityType::Equity);
 
     let chart = it
         .call(ChartRequest::default().odd_lot(true))
         .await
         .unwrap();
     assert_eq!(chart.data.info.symbol_id, "2884");
-    assert_eq!(chart.data.info.typ, "ODDLOT");
+    assert_eq!(chart.data.info.typ, SecurityType::OddLot);
+}
+
+#[tokio::test]
+#[cfg(feature = "agnostic-query")]
+async fn test_intraday_agnostic_chart_pass() {
+    let it = RestfulBuilder::new().build_agnostic().unwrap();
+    let chart = it.call(ChartRequest::new()).await.unwrap();
+    assert_eq!(chart.data.info.symbol_id, "2884");
+    assert_eq!(chart.data.info.typ, SecurityType::Equity);
 }
 
 #[test]
@@ -50,6 +60,16 @@ async fn test_intraday_async_chart_400_failed() {
     )
 }
 
+#[tokio::test]
+#[cfg(feature = "agnostic-query")]
+async fn test_intraday_agnostic_chart_401_failed() {
+    let it = RestfulBuilder::new().token("").build_agnostic().unwrap();
+    assert_err!(
+        it.call(ChartRequest::new()).await,
+        Err(FugleError::Unauthorized)
+    )
+}
+
 #[test]
 fn test_intraday_chart_401_failed() {
     let it = RestfulBuilder::new().token("").build().unwrap();
@@ -70,11 +90,11 @@ fn test_intraday_quote_pass() {
     let it = RestfulBuilder::new().build().unwrap();
     let quote = it.call(QuoteRequest::new()).unwrap();
     assert_eq!(quote.data.info.symbol_id, "2884");
-    assert_eq!(quote.data.info.typ, "EQUITY");
+    assert_eq!(quote.data.info.typ, SecurityType::Equity);
 
     let quote = it.call(QuoteRequest::default().odd_lot(true)).unwrap();
     assert_eq!(quote.data.info.symbol_id, "2884");
-    assert_eq!(quote.data.info.typ, "ODDLOT");
+    assert_eq!(quote.data.info.typ, SecurityType::OddLot);
 }
 
 #[tokio::test]
@@ -82,14 +102,14 @@ async fn test_intraday_async_quote_pass() {
     let it = RestfulBuilder::new().build_async().unwrap();
     let quote = it.call(QuoteRequest::new()).await.unwrap();
     assert_eq!(quote.data.info.symbol_id, "2884");
-    assert_eq!(quote.data.info.typ, "EQUITY");
+    assert_eq!(quote.data.info.typ, SecurityType::Equity);
 
     let quote = it
         .call(QuoteRequest::default().odd_lot(true))
         .await
         .unwrap();
     assert_eq!(quote.data.info.symbol_id, "2884");
-    assert_eq!(quote.data.info.typ, "ODDLOT");
+    assert_eq!(quote.data.info.typ, SecurityType::OddLot);
 }
 
 #[test]
@@ -130,11 +150,11 @@ fn test_intraday_meta_pass() {
     let it = RestfulBuilder::new().build().unwrap();
     let meta = it.call(MetaRequest::new()).unwrap();
     assert_eq!(meta.data.info.symbol_id, "2884");
-    assert_eq!(meta.data.info.typ, "EQUITY");
+    assert_eq!(meta.data.info.typ, SecurityType::Equity);
 
     let meta = it.call(MetaRequest::default().odd_lot(true)).unwrap();
     assert_eq!(meta.data.info.symbol_id, "2884");
-    assert_eq!(meta.data.info.typ, "ODDLOT");
+    assert_eq!(meta.data.info.typ, SecurityType::OddLot);
 }
 
 #[tokio::test]
@@ -142,11 +162,11 @@ async fn test_intraday_async_meta_pass() {
     let it = RestfulBuilder::new().build_async().unwrap();
     let meta = it.call(MetaRequest::new()).await.unwrap();
     assert_eq!(meta.data.info.symbol_id, "2884");
-    assert_eq!(meta.data.info.typ, "EQUITY");
+    assert_eq!(meta.data.info.typ, SecurityType::Equity);
 
     let meta = it.call(MetaRequest::default().odd_lot(true)).await.unwrap();
     assert_eq!(meta.data.info.symbol_id, "2884");
-    assert_eq!(meta.data.info.typ, "ODDLOT");
+    assert_eq!(meta.data.info.typ, SecurityType::OddLot);
 }
 
 #[test]
@@ -187,13 +207,13 @@ fn test_intraday_dealts_pass() {
     let it = RestfulBuilder::new().build().unwrap();
     let dealts = it.call(DealtsRequest::new().limit(9).offset(1)).unwrap();
     assert_eq!(dealts.data.info.symbol_id, "2884");
-    assert_eq!(dealts.data.info.typ, "EQUITY");
+    assert_eq!(dealts.data.info.typ, SecurityType::Equity);
 
     let dealts = it
         .call(DealtsRequest::default().odd_lot(true).limit(9).offset(1))
         .unwrap();
     assert_eq!(dealts.data.info.symbol_id, "2884");
-    assert_eq!(dealts.data.info.typ, "ODDLOT");
+    assert_eq!(dealts.data.info.typ, SecurityType::OddLot);
 }
 
 #[tokio::test]
@@ -204,14 +224,14 @@ async fn test_intraday_async_dealts_pass() {
         .await
         .unwrap();
     assert_eq!(dealts.data.info.symbol_id, "2884");
-    assert_eq!(dealts.data.info.typ, "EQUITY");
+    assert_eq!(dealts.data.info.typ, SecurityType::Equity);
 
     let dealts = it
         .call(DealtsRequest::default().odd_lot(true).limit(9).offset(1))
         .await
         .unwrap();
     assert_eq!(dealts.data.info.symbol_id, "2884");
-    assert_eq!(dealts.data.info.typ, "ODDLOT");
+    assert_eq!(dealts.data.info.typ, SecurityType::OddLot);
 }
 
 #[test]
@@ -252,11 +272,11 @@ fn test_intraday_volumes_pass() {
     let it = RestfulBuilder::new().build().unwrap();
     let volumes = it.call(VolumesRequest::new()).unwrap();
     assert_eq!(volumes.data.info.symbol_id, "2884");
-    assert_eq!(volumes.data.info.typ, "EQUITY");
+    assert_eq!(volumes.data.info.typ, SecurityType::Equity);
 
     let volumes = it.call(VolumesRequest::default().odd_lot(true)).unwrap();
     assert_eq!(volumes.data.info.symbol_id, "2884");
-    assert_eq!(volumes.data.info.typ, "ODDLOT");
+    assert_eq!(volumes.data.info.typ, SecurityType::OddLot);
 }
 
 #[tokio::test]
@@ -264,14 +284,14 @@ async fn test_intraday_async_volumes_pass() {
     let it = RestfulBuilder::new().build_async().unwrap();
     let volumes = it.call(VolumesRequest::new()).await.unwrap();
     assert_eq!(volumes.data.info.symbol_id, "2884");
-    assert_eq!(volumes.data.info.typ, "EQUITY");
+    assert_eq!(volumes.data.info.typ, SecurityType::Equity);
 
     let volumes = it
         .call(VolumesRequest::default().odd_lot(true))
         .await
         .unwrap();
     assert_eq!(volumes.data.info.symbol_id, "2884");
-    assert_eq!(volumes.data.info.typ, "ODDLOT");
+    assert_eq!(volumes.data.info.typ, SecurityType::OddLot);
 }
 
 #[test]
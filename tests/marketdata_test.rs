@@ -1,6 +1,7 @@
 use fugle::{
     errors::FugleError,
     http::{marketdata::CandleField, marketdata::CandlesRequest, RestfulBuilder},
+    schema::SecurityType,
 };
 mod util;
 
@@ -34,7 +35,7 @@ fn test_marketdata_candles_pass() {
         )
         .unwrap();
     assert_eq!(candles.symbol_id, "2884");
-    assert_eq!(candles.typ, "EQUITY");
+    assert_eq!(candles.typ, SecurityType::Equity);
     assert_ne!(candles.candles[0].turnover, 0);
     assert_ne!(candles.candles[0].volume, 0);
 }
@@ -70,7 +71,7 @@ async fn test_marketdata_async_candles_pass() {
         .await
         .unwrap();
     assert_eq!(candles.symbol_id, "2884");
-    assert_eq!(candles.typ, "EQUITY");
+    assert_eq!(candles.typ, SecurityType::Equity);
     assert_ne!(candles.candles[0].turnover, 0);
     assert_ne!(candles.candles[0].volume, 0);
 }
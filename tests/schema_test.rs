@@ -1,12 +1,14 @@
 use std::{fs::File, path::Path};
 
 use rust_decimal::Decimal;
+use serde::{de::DeserializeOwned, Serialize};
+use serde_json::Value;
 
 use fugle::{
     errors::{ErrorResponse, FugleError},
     schema::{
-        CandlesResponse, ChartResponse, DealtsResponse, MetaResponse, QuoteResponse,
-        VolumesResponse,
+        strict, CandlesResponse, ChartResponse, DateTimeExt, DealtsResponse, Exchange, Market,
+        MetaResponse, QuoteResponse, SecurityType, VolumesResponse, TAIPEI,
     },
 };
 
@@ -17,8 +19,8 @@ fn test_volumes_response_deserialize() {
 
     assert_eq!("0.3.0", res.api_version);
     assert_eq!("2884", res.data.info.symbol_id);
-    assert_eq!("TSE", res.data.info.market);
-    assert_eq!("EQUITY", res.data.info.typ);
+    assert_eq!(Market::Tse, res.data.info.market);
+    assert_eq!(SecurityType::Equity, res.data.info.typ);
     assert_eq!(3, res.data.volumes.len());
 }
 
@@ -29,13 +31,13 @@ fn test_chart_response_deserialize() {
 
     assert_eq!("0.3.0", res.api_version);
     assert_eq!("2884", res.data.info.symbol_id);
-    assert_eq!("TSE", res.data.info.market);
-    assert_eq!("EQUITY", res.data.info.typ);
+    assert_eq!(Market::Tse, res.data.info.market);
+    assert_eq!(SecurityType::Equity, res.data.info.typ);
     assert_eq!(263, res.data.chart.open.len());
     assert_eq!(263, res.data.chart.high.len());
     assert_eq!(263, res.data.chart.low.len());
     assert_eq!(263, res.data.chart.close.len());
-    assert_eq!(263, res.data.chart.unix_timestamp.len());
+    assert_eq!(263, res.data.chart.at.len());
 }
 
 #[test]
@@ -45,8 +47,8 @@ fn test_quote_response_deserialize() {
 
     assert_eq!("0.3.0", res.api_version);
     assert_eq!("2884", res.data.info.symbol_id);
-    assert_eq!("TSE", res.data.info.market);
-    assert_eq!("EQUITY", res.data.info.typ);
+    assert_eq!(Market::Tse, res.data.info.market);
+    assert_eq!(SecurityType::Equity, res.data.info.typ);
     assert_eq!(
         (-0.19f64).to_string(),
         res.data.quote.change_percent.to_string()
@@ -79,8 +81,8 @@ fn test_meta_response_deserialize() {
 
     assert_eq!("0.3.0", res.api_version);
     assert_eq!("2884", res.data.info.symbol_id);
-    assert_eq!("TSE", res.data.info.market);
-    assert_eq!("EQUITY", res.data.info.typ);
+    assert_eq!(Market::Tse, res.data.info.market);
+    assert_eq!(SecurityType::Equity, res.data.info.typ);
     assert_eq!(
         26.5f64.to_string(),
         res.data.meta.price_reference.to_string()
@@ -105,9 +107,16 @@ fn test_dealts_response_deserialize() {
 
     assert_eq!("0.3.0", res.api_version);
     assert_eq!("2884", res.data.info.symbol_id);
-    assert_eq!("TSE", res.data.info.market);
-    assert_eq!("EQUITY", res.data.info.typ);
+    assert_eq!(Market::Tse, res.data.info.market);
+    assert_eq!(SecurityType::Equity, res.data.info.typ);
     assert_eq!(5, res.data.dealts.len());
+
+    // 2021-10-22T13:30:00.000+08:00 keeps its offset.
+    let at = res.data.dealts[0].at;
+    assert_eq!(TAIPEI, at.offset());
+    assert_eq!(13, at.hour());
+    assert_eq!(5, at.in_utc().hour());
+    assert_eq!(at, at.in_utc());
 }
 
 #[test]
@@ -120,8 +129,8 @@ fn test_volumes_response_with_oddlot_deserialize() {
 
     assert_eq!("0.3.0", res.api_version);
     assert_eq!("2884", res.data.info.symbol_id);
-    assert_eq!("TSE", res.data.info.market);
-    assert_eq!("ODDLOT", res.data.info.typ);
+    assert_eq!(Market::Tse, res.data.info.market);
+    assert_eq!(SecurityType::OddLot, res.data.info.typ);
     assert_eq!(3, res.data.volumes.len());
 }
 
@@ -132,14 +141,14 @@ fn test_chart_response_with_oddlot_deserialize() {
     let res: ChartResponse = serde_json::from_reader(json_file).unwrap();
 
     assert_eq!("0.3.0", res.api_version);
-    assert_eq!("TSE", res.data.info.market);
-    assert_eq!("ODDLOT", res.data.info.typ);
+    assert_eq!(Market::Tse, res.data.info.market);
+    assert_eq!(SecurityType::OddLot, res.data.info.typ);
     assert_eq!("2884", res.data.info.symbol_id);
     assert_eq!(87, res.data.chart.open.len());
     assert_eq!(87, res.data.chart.high.len());
     assert_eq!(87, res.data.chart.low.len());
     assert_eq!(87, res.data.chart.close.len());
-    assert_eq!(87, res.data.chart.unix_timestamp.len());
+    assert_eq!(87, res.data.chart.at.len());
 }
 
 #[test]
@@ -150,8 +159,8 @@ fn test_quote_response_with_oddlot_deserialize() {
 
     assert_eq!("0.3.0", res.api_version);
     assert_eq!("2884", res.data.info.symbol_id);
-    assert_eq!("TSE", res.data.info.market);
-    assert_eq!("ODDLOT", res.data.info.typ);
+    assert_eq!(Market::Tse, res.data.info.market);
+    assert_eq!(SecurityType::OddLot, res.data.info.typ);
     assert_eq!(
         (-0.19f64).to_string(),
         res.data.quote.change_percent.to_string()
@@ -184,8 +193,8 @@ fn test_meta_response_with_oddlot_deserialize() {
 
     assert_eq!("0.3.0", res.api_version);
     assert_eq!("2884", res.data.info.symbol_id);
-    assert_eq!("TSE", res.data.info.market);
-    assert_eq!("ODDLOT", res.data.info.typ);
+    assert_eq!(Market::Tse, res.data.info.market);
+    assert_eq!(SecurityType::OddLot, res.data.info.typ);
     assert_eq!(
         26.5f64.to_string(),
         res.data.meta.price_reference.to_string()
@@ -210,8 +219,8 @@ fn test_dealts_response_with_oddlot_deserialize() {
 
     assert_eq!("0.3.0", res.api_version);
     assert_eq!("2884", res.data.info.symbol_id);
-    assert_eq!("TSE", res.data.info.market);
-    assert_eq!("ODDLOT", res.data.info.typ);
+    assert_eq!(Market::Tse, res.data.info.market);
+    assert_eq!(SecurityType::OddLot, res.data.info.typ);
     assert_eq!(5, res.data.dealts.len());
 }
 
@@ -221,9 +230,9 @@ fn test_candles_response_deserialize() {
     let res: CandlesResponse = serde_json::from_reader(json_file).unwrap();
 
     assert_eq!("2884", res.symbol_id);
-    assert_eq!("TSE", res.market);
-    assert_eq!("EQUITY", res.typ);
-    assert_eq!("TWSE", res.exchange);
+    assert_eq!(Market::Tse, res.market);
+    assert_eq!(SecurityType::Equity, res.typ);
+    assert_eq!(Exchange::Twse, res.exchange);
     assert_eq!(6, res.candles.len());
     assert_eq!(Decimal::new(3340, 2), res.candles[0].open);
     assert_eq!(Decimal::new(3325, 2), res.candles[1].high);
@@ -305,3 +314,70 @@ fn test_error_response_deserialize() {
         _ => unreachable!(),
     }
 }
+
+// whether the value is what the schemas default a field fugle does not send to.
+fn is_default(value: &Value) -> bool {
+    match value {
+        Value::Null => true,
+        Value::Bool(b) => !b,
+        Value::Number(n) => n.as_f64() == Some(0.0),
+        Value::String(s) => s.is_empty(),
+        Value::Array(a) => a.is_empty(),
+        Value::Object(o) => o.values().all(is_default),
+    }
+}
+
+// every serialized field must be the same as the one fugle sent,
+// the fields the schemas do not keep are left out.
+fn assert_subset(got: &Value, sent: &Value, path: &str) {
+    match (got, sent) {
+        (Value::Object(got), Value::Object(sent)) => {
+            for (key, value) in got {
+                let path = format!("{}.{}", path, key);
+                match sent.get(key) {
+                    Some(sent) => assert_subset(value, sent, &path),
+                    None => assert!(is_default(value), "{} is not sent by fugle", path),
+                }
+            }
+        }
+        (Value::Array(got), Value::Array(sent)) => {
+            assert_eq!(got.len(), sent.len(), "{}", path);
+            for (i, (got, sent)) in got.iter().zip(sent).enumerate() {
+                assert_subset(got, sent, &format!("{}[{}]", path, i));
+            }
+        }
+        (Value::Number(got), Value::Number(sent)) => {
+            assert_eq!(got.as_f64(), sent.as_f64(), "{}", path)
+        }
+        _ => assert_eq!(got, sent, "{}", path),
+    }
+}
+
+fn assert_round_trip<T: DeserializeOwned + Serialize>(file: &str) {
+    let json = std::fs::read_to_string(Path::new("tests/testdata").join(file)).unwrap();
+    let sent: Value = serde_json::from_str(&json).unwrap();
+    let res: T = serde_json::from_str(&json).unwrap();
+
+    let got = serde_json::to_value(&res).unwrap();
+    assert_subset(&got, &sent, file);
+    let again: T = serde_json::from_value(got.clone()).unwrap();
+    assert_eq!(got, serde_json::to_value(&again).unwrap(), "{}", file);
+
+    // what fugle sends passes the strict parse.
+    assert!(strict::from_str_strict::<T>(&json).is_ok(), "{}", file);
+}
+
+#[test]
+fn test_response_serialize_round_trip() {
+    assert_round_trip::<ChartResponse>("chart_response.json");
+    assert_round_trip::<ChartResponse>("chart_response_with_oddlot.json");
+    assert_round_trip::<QuoteResponse>("quote_response.json");
+    assert_round_trip::<QuoteResponse>("quote_response_with_oddlot.json");
+    assert_round_trip::<MetaResponse>("meta_response.json");
+    assert_round_trip::<MetaResponse>("meta_response_with_oddlot.json");
+    assert_round_trip::<DealtsResponse>("dealts_response.json");
+    assert_round_trip::<DealtsResponse>("dealts_response_with_oddlot.json");
+    assert_round_trip::<VolumesResponse>("volumes_response.json");
+    assert_round_trip::<VolumesResponse>("volumes_response_with_oddlot.json");
+    assert_round_trip::<CandlesResponse>("candles_response.json");
+}
@@ -51,11 +51,9 @@ where
         done_tx.send(()).expect("unable to send completion signal");
     });
 
-    'looper: loop {
-        tokio::select! {
-            _ = done_rx => { break 'looper }
-            _ = timeout.tick() => { panic!("routine took too long") }
-        }
+    tokio::select! {
+        _ = done_rx => {}
+        _ = timeout.tick() => { panic!("routine took too long") }
     }
 }
 
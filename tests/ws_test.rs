@@ -1,6 +1,8 @@
+#![cfg(any(feature = "websocket", feature = "async-websocket"))]
+
 use std::time::Duration;
 
-use fugle::websocket::IntradayBuilder;
+use fugle::{schema::SecurityType, websocket::IntradayBuilder};
 use serial_test::serial;
 
 mod util;
@@ -38,7 +40,7 @@ fn test_intraday_chart_pass() {
         let rx = ws.chart().unwrap();
         let chart = rx.recv().unwrap();
         assert_eq!(chart.data.info.symbol_id, "2884");
-        assert_eq!(chart.data.info.typ, "EQUITY");
+        assert_eq!(chart.data.info.typ, SecurityType::Equity);
     })
 }
 
@@ -63,7 +65,7 @@ fn test_intraday_meta_pass() {
         let rx = ws.meta().unwrap();
         let meta = rx.recv().unwrap();
         assert_eq!(meta.data.info.symbol_id, "2884");
-        assert_eq!(meta.data.info.typ, "EQUITY");
+        assert_eq!(meta.data.info.typ, SecurityType::Equity);
     })
 }
 
@@ -88,7 +90,7 @@ fn test_intraday_quote_pass() {
         let rx = ws.quote().unwrap();
         let quote = rx.recv().unwrap();
         assert_eq!(quote.data.info.symbol_id, "2884");
-        assert_eq!(quote.data.info.typ, "EQUITY");
+        assert_eq!(quote.data.info.typ, SecurityType::Equity);
     })
 }
 
@@ -113,7 +115,7 @@ async fn test_intraday_async_chart_pass() {
         let mut rx = ws.async_chart().await.unwrap();
         let chart = rx.recv().await.unwrap();
         assert_eq!(chart.data.info.symbol_id, "2884");
-        assert_eq!(chart.data.info.typ, "EQUITY");
+        assert_eq!(chart.data.info.typ, SecurityType::Equity);
     })
     .await
 }
@@ -139,7 +141,7 @@ async fn test_intraday_async_meta_pass() {
         let mut rx = ws.async_meta().await.unwrap();
         let meta = rx.recv().await.unwrap();
         assert_eq!(meta.data.info.symbol_id, "2884");
-        assert_eq!(meta.data.info.typ, "EQUITY");
+        assert_eq!(meta.data.info.typ, SecurityType::Equity);
     })
     .await
 }
@@ -165,7 +167,524 @@ async fn test_intraday_async_quote_pass() {
         let mut rx = ws.async_quote().await.unwrap();
         let quote = rx.recv().await.unwrap();
         assert_eq!(quote.data.info.symbol_id, "2884");
-        assert_eq!(quote.data.info.typ, "EQUITY");
+        assert_eq!(quote.data.info.typ, SecurityType::Equity);
     })
     .await
 }
+
+#[test]
+fn test_intraday_builder_symbols() {
+    use fugle::websocket::Symbol;
+
+    let ws = IntradayBuilder::new()
+        .symbol_id("2884")
+        .odd_lot()
+        .symbol(Symbol::new("2330"))
+        .symbol(Symbol::odd_lot("2884"))
+        .symbol(Symbol::new("2884"))
+        .build();
+
+    assert_eq!(
+        ws.symbols(),
+        &[
+            Symbol::odd_lot("2884"),
+            Symbol::new("2330"),
+            Symbol::new("2884")
+        ]
+    );
+}
+
+#[test]
+#[cfg(feature = "websocket")]
+fn test_intraday_subscribe_not_listened() {
+    use fugle::{
+        errors::FugleError,
+        websocket::{Channel, Symbol},
+    };
+
+    let mut ws = IntradayBuilder::new().symbol_id("2884").build();
+    assert_err!(
+        ws.subscribe(Symbol::new("2330"), Channel::Quote),
+        Err(FugleError::ChannelNotListened)
+    );
+    assert!(ws
+        .unsubscribe(Symbol::new("2884"), Channel::Quote, Duration::from_secs(1))
+        .is_none());
+    assert!(ws.subscriptions().is_empty());
+}
+
+#[tokio::test]
+#[cfg(feature = "async-websocket")]
+async fn test_intraday_async_subscribe_not_listened() {
+    use fugle::{
+        errors::FugleError,
+        websocket::{Channel, Symbol},
+    };
+
+    let mut ws = IntradayBuilder::new().symbol_id("2884").build();
+    assert_err!(
+        ws.async_subscribe(Symbol::new("2330"), Channel::Chart)
+            .await,
+        Err(FugleError::ChannelNotListened)
+    );
+    assert!(ws
+        .async_unsubscribe(Symbol::new("2884"), Channel::Chart, Duration::from_secs(1))
+        .await
+        .is_none());
+}
+
+// a local websocket server which sends the frame to every client,
+// then reports the request uri and the x-test header it received.
+#[cfg(feature = "websocket")]
+// the handshake callback returns the error response tungstenite defines.
+#[allow(clippy::result_large_err)]
+fn local_server(frame: &'static str) -> (String, std::sync::mpsc::Receiver<(String, String)>) {
+    use tungstenite::handshake::server::{Request, Response};
+
+    let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap().to_string();
+    let (tx, rx) = std::sync::mpsc::channel();
+
+    std::thread::spawn(move || {
+        for stream in listener.incoming() {
+            let tx = tx.clone();
+            std::thread::spawn(move || {
+                let mut seen = (String::new(), String::new());
+                let mut ws =
+                    tungstenite::accept_hdr(stream.unwrap(), |req: &Request, res: Response| {
+                        seen.0 = req.uri().to_string();
+                        if let Some(v) = req.headers().get("x-test") {
+                            seen.1 = v.to_str().unwrap().to_string();
+                        }
+                        Ok(res)
+                    })
+                    .unwrap();
+                let _ = tx.send(seen);
+                ws.write_message(tungstenite::Message::Text(frame.to_string()))
+                    .unwrap();
+                while ws.read_message().is_ok() {}
+            });
+        }
+    });
+
+    (addr, rx)
+}
+
+const QUOTE_FRAME: &str = r#"{"apiVersion":"0.3.0","data":{"info":{"symbolId":"2884"}}}"#;
+
+#[test]
+#[cfg(feature = "websocket")]
+fn test_intraday_local_server() {
+    let (addr, seen) = local_server(QUOTE_FRAME);
+    let url = format!("ws://{}/intraday/", addr);
+    let mut ws = IntradayBuilder::new()
+        .url(&url)
+        .symbol_id("2884")
+        .header("X-Test", "yes")
+        .connect_timeout(Duration::from_secs(1))
+        .build();
+
+    let rx = ws.quote().unwrap();
+    let quote = rx.recv_timeout(Duration::from_secs(3)).unwrap();
+    assert_eq!(quote.data.info.symbol_id, "2884");
+
+    let (uri, header) = seen.recv().unwrap();
+    assert_eq!(
+        uri,
+        "/intraday/quote?symbolId=2884&apiToken=demo&oddLot=false"
+    );
+    assert_eq!(header, "yes");
+}
+
+#[test]
+#[cfg(feature = "websocket")]
+fn test_intraday_listen_again() {
+    use fugle::websocket::{Channel, Symbol};
+    use std::sync::mpsc::TryRecvError;
+
+    let (addr, _seen) = local_server(QUOTE_FRAME);
+    let url = format!("ws://{}", addr);
+    let mut ws = IntradayBuilder::new()
+        .token("test_intraday_listen_again")
+        .connection_limit(1)
+        .url(&url)
+        .symbol_id("2884")
+        .build();
+
+    // the second listening replaces the connection instead of opening one more.
+    let first = ws.quote().unwrap();
+    let second = ws.quote().unwrap();
+    assert_eq!(ws.subscriptions().len(), 1);
+    assert!(second.recv_timeout(Duration::from_secs(3)).is_ok());
+    while first.recv_timeout(Duration::from_secs(3)).is_ok() {}
+    assert_eq!(first.try_recv().unwrap_err(), TryRecvError::Disconnected);
+
+    ws.subscribe(Symbol::new("2884"), Channel::Quote).unwrap();
+    assert_eq!(ws.subscriptions().len(), 1);
+    assert!(ws
+        .unsubscribe(Symbol::new("2884"), Channel::Quote, Duration::from_secs(3))
+        .unwrap()
+        .is_clean());
+    assert!(ws.subscriptions().is_empty());
+}
+
+#[test]
+#[cfg(feature = "websocket")]
+fn test_intraday_local_proxy() {
+    use std::io::{copy, Read, Write};
+
+    let (addr, _seen) = local_server(QUOTE_FRAME);
+    let proxy = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    let proxy_addr = proxy.local_addr().unwrap().to_string();
+    let (connect_tx, connect_rx) = std::sync::mpsc::channel();
+
+    // a local HTTP CONNECT proxy which tunnels one client to the requested target.
+    std::thread::spawn(move || {
+        let (mut client, _) = proxy.accept().unwrap();
+        let mut head = vec![];
+        let mut byte = [0u8; 1];
+        while !head.ends_with(b"\r\n\r\n") {
+            client.read_exact(&mut byte).unwrap();
+            head.push(byte[0]);
+        }
+        let line = String::from_utf8(head).unwrap();
+        let target = line.split(' ').nth(1).unwrap().to_string();
+        connect_tx.send(target.clone()).unwrap();
+
+        let upstream = std::net::TcpStream::connect(target).unwrap();
+        client
+            .write_all(b"HTTP/1.1 200 Connection established\r\n\r\n")
+            .unwrap();
+
+        let (mut client_rd, mut upstream_wr) =
+            (client.try_clone().unwrap(), upstream.try_clone().unwrap());
+        std::thread::spawn(move || copy(&mut client_rd, &mut upstream_wr));
+        let (mut upstream_rd, mut client_wr) = (upstream, client);
+        let _ = copy(&mut upstream_rd, &mut client_wr);
+    });
+
+    let url = format!("ws://{}", addr);
+    let mut ws = IntradayBuilder::new()
+        .url(&url)
+        .proxy(&proxy_addr)
+        .symbol_id("2884")
+        .build();
+
+    let rx = ws.quote().unwrap();
+    let quote = rx.recv_timeout(Duration::from_secs(3)).unwrap();
+    assert_eq!(quote.data.info.symbol_id, "2884");
+    assert_eq!(connect_rx.recv().unwrap(), addr);
+}
+
+#[test]
+#[cfg(feature = "websocket")]
+fn test_intraday_connect_timeout() {
+    // accepts the connection but never answers the handshake.
+    let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    let url = format!("ws://{}", listener.local_addr().unwrap());
+
+    let mut ws = IntradayBuilder::new()
+        .url(&url)
+        .symbol_id("2884")
+        .connect_timeout(Duration::from_millis(200))
+        .build();
+
+    util::timeout_after(Duration::from_secs(3), move || {
+        assert!(ws.quote().is_err());
+    });
+    drop(listener);
+}
+
+#[tokio::test]
+#[cfg(feature = "async-websocket")]
+// the handshake callback returns the error response tungstenite defines.
+#[allow(clippy::result_large_err)]
+async fn test_intraday_async_local_server() {
+    use futures_util::{SinkExt, StreamExt};
+    use tokio_tungstenite::tungstenite::{
+        handshake::server::{Request, Response},
+        Message,
+    };
+
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    let (seen_tx, seen_rx) = tokio::sync::oneshot::channel();
+
+    tokio::spawn(async move {
+        let (stream, _) = listener.accept().await.unwrap();
+        let mut header = String::new();
+        let mut ws = tokio_tungstenite::accept_hdr_async(stream, |req: &Request, res: Response| {
+            if let Some(v) = req.headers().get("x-test") {
+                header = v.to_str().unwrap().to_string();
+            }
+            Ok(res)
+        })
+        .await
+        .unwrap();
+        seen_tx.send(header).unwrap();
+        ws.send(Message::Text(QUOTE_FRAME.to_string()))
+            .await
+            .unwrap();
+        while let Some(Ok(_)) = ws.next().await {}
+    });
+
+    let url = format!("ws://{}", addr);
+    let mut ws = IntradayBuilder::new()
+        .url(&url)
+        .symbol_id("2884")
+        .header("X-Test", "yes")
+        .connect_timeout(Duration::from_secs(1))
+        .build();
+
+    let mut rx = ws.async_quote().await.unwrap();
+    let quote = tokio::time::timeout(Duration::from_secs(3), rx.recv())
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(quote.data.info.symbol_id, "2884");
+    assert_eq!(seen_rx.await.unwrap(), "yes");
+}
+
+#[test]
+#[cfg(feature = "websocket")]
+fn test_intraday_connection_limit() {
+    use fugle::{
+        errors::FugleError,
+        websocket::{Channel, Symbol},
+    };
+
+    let (addr, _seen) = local_server(QUOTE_FRAME);
+    let url = format!("ws://{}/intraday", addr);
+    let mut ws = IntradayBuilder::new()
+        .token("test_intraday_connection_limit")
+        .url(&url)
+        .symbol_id("2884")
+        .connection_limit(1)
+        .build();
+    assert_eq!(ws.connection_usage().available(), Some(1));
+
+    let _rx = ws.quote().unwrap();
+    let usage = ws.connection_usage();
+    assert_eq!(usage.in_use, 1);
+    assert_eq!(usage.available(), Some(0));
+
+    assert!(matches!(
+        ws.subscribe(Symbol::new("2330"), Channel::Quote),
+        Err(FugleError::ConnectionLimitExceeded(1))
+    ));
+    assert_eq!(ws.subscriptions().len(), 1);
+}
+
+#[test]
+#[cfg(all(feature = "websocket", feature = "async-websocket"))]
+fn test_intraday_async_without_tokio() {
+    use futures_util::future::BoxFuture;
+
+    let (addr, _seen) = local_server(QUOTE_FRAME);
+    let url = format!("ws://{}/intraday", addr);
+    let mut ws = IntradayBuilder::new()
+        .url(&url)
+        .symbol_id("2884")
+        .executor(|task: BoxFuture<'static, ()>| {
+            std::thread::spawn(move || async_io::block_on(task));
+        })
+        .build();
+
+    async_io::block_on(async {
+        let mut rx = ws.async_quote().await.unwrap();
+        let quote = rx.recv().await.unwrap();
+        assert_eq!(quote.data.info.symbol_id, "2884");
+
+        let exits = ws.async_shutdown(Duration::from_secs(3)).await;
+        assert!(exits.iter().all(|e| e.is_clean()));
+    });
+}
+
+#[test]
+#[cfg(all(
+    feature = "websocket",
+    feature = "async-websocket",
+    feature = "async-query"
+))]
+fn test_intraday_async_rest_without_tokio() {
+    use fugle::errors::FugleError;
+    use futures_util::future::BoxFuture;
+
+    let (addr, _seen) = local_server(QUOTE_FRAME);
+    let url = format!("ws://{}/intraday", addr);
+    let mut ws = IntradayBuilder::new()
+        .url(&url)
+        .symbol_id("2884")
+        .executor(|task: BoxFuture<'static, ()>| {
+            std::thread::spawn(move || async_io::block_on(task));
+        })
+        .rest_fallback(Duration::from_millis(50), Duration::from_millis(200))
+        .build();
+
+    // the REST requests fail up front instead of inside the workers.
+    async_io::block_on(async {
+        assert!(matches!(
+            ws.async_quote_feed().await,
+            Err(FugleError::TokioRuntimeRequired)
+        ));
+        assert!(matches!(
+            ws.async_quote().await,
+            Err(FugleError::TokioRuntimeRequired)
+        ));
+    });
+}
+
+#[test]
+#[cfg(feature = "websocket")]
+fn test_intraday_multiplexed() {
+    let (addr, _seen) = local_server(QUOTE_FRAME);
+    let url = format!("ws://{}/intraday", addr);
+    let mut ws = IntradayBuilder::new()
+        .url(&url)
+        .symbol_id("2884")
+        .multiplexed()
+        .build();
+
+    let rx = ws.quote().unwrap();
+    let quote = rx.recv_timeout(Duration::from_secs(3)).unwrap();
+    assert_eq!(quote.data.info.symbol_id, "2884");
+
+    let exits = ws.shutdown(Duration::from_secs(3));
+    assert_eq!(exits.len(), 1);
+    assert!(exits.iter().all(|e| e.is_clean()));
+}
+
+// like local_server but keeps writing the frame until the client has gone.
+#[cfg(feature = "websocket")]
+fn flooding_server(frame: &'static str) -> String {
+    let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap().to_string();
+
+    std::thread::spawn(move || {
+        for stream in listener.incoming() {
+            std::thread::spawn(move || {
+                let mut ws = tungstenite::accept(stream.unwrap()).unwrap();
+                while ws
+                    .write_message(tungstenite::Message::Text(frame.to_string()))
+                    .is_ok()
+                {
+                    std::thread::sleep(Duration::from_millis(10));
+                }
+            });
+        }
+    });
+
+    addr
+}
+
+#[test]
+#[cfg(feature = "websocket")]
+fn test_intraday_multiplexed_broadcast() {
+    use fugle::websocket::Overflow;
+
+    let addr = flooding_server(QUOTE_FRAME);
+    let url = format!("ws://{}/intraday", addr);
+    let mut ws = IntradayBuilder::new()
+        .url(&url)
+        .symbol_id("2884")
+        .multiplexed()
+        .build();
+
+    let broadcast = ws.quote_broadcast().unwrap();
+    let _full = broadcast.subscribe(1, Overflow::Block);
+    let rx = ws.meta().unwrap();
+
+    // the full consumer holds back its own worker only, not the multiplexed ones.
+    std::thread::sleep(Duration::from_millis(300));
+    while rx.try_recv().is_ok() {}
+    let meta = rx.recv_timeout(Duration::from_secs(3)).unwrap();
+    assert_eq!(meta.data.info.symbol_id, "2884");
+}
+
+#[test]
+#[cfg(all(feature = "websocket", feature = "query"))]
+fn test_intraday_rest_fallback() {
+    let (addr, _seen) = local_server(QUOTE_FRAME);
+    let url = format!("ws://{}/intraday", addr);
+    let token = "test_intraday_rest_fallback";
+    let mut ws = IntradayBuilder::new()
+        .token(token)
+        .url(&url)
+        .symbol_id("2884")
+        .connection_limit(0)
+        .rest_fallback(Duration::from_millis(50), Duration::from_millis(200))
+        .build();
+
+    // polling the REST endpoint instead of failing on the connection limit.
+    let rx = ws.quote().unwrap();
+    std::thread::sleep(Duration::from_millis(100));
+    assert_eq!(ws.connection_usage().in_use, 0);
+
+    // the websocket is tried again and taken once the limit has been raised.
+    let _raised = IntradayBuilder::new()
+        .token(token)
+        .connection_limit(1)
+        .build();
+    let quote = rx.recv_timeout(Duration::from_secs(10)).unwrap();
+    assert_eq!(quote.data.info.symbol_id, "2884");
+    assert_eq!(ws.connection_usage().in_use, 1);
+
+    let exits = ws.shutdown(Duration::from_secs(5));
+    assert!(exits.iter().all(|e| e.is_clean()));
+}
+
+#[test]
+#[cfg(all(feature = "websocket", feature = "query"))]
+fn test_intraday_rest_fallback_bad_url() {
+    use fugle::errors::FugleError;
+
+    // a malformed url fails the listening instead of polling forever.
+    let (addr, _seen) = local_server(QUOTE_FRAME);
+    let url = format!("http://{}/intraday", addr);
+    let mut ws = IntradayBuilder::new()
+        .url(&url)
+        .symbol_id("2884")
+        .rest_fallback(Duration::from_millis(50), Duration::from_millis(200))
+        .build();
+    assert!(matches!(
+        ws.quote(),
+        Err(FugleError::Tungstenite(e)) if matches!(*e, tungstenite::Error::Url(_))
+    ));
+    assert!(ws.subscriptions().is_empty());
+}
+
+#[tokio::test]
+#[cfg(all(
+    feature = "websocket",
+    feature = "async-websocket",
+    feature = "async-query"
+))]
+async fn test_intraday_async_rest_fallback() {
+    let (addr, _seen) = local_server(QUOTE_FRAME);
+    let url = format!("ws://{}/intraday", addr);
+    let token = "test_intraday_async_rest_fallback";
+    let mut ws = IntradayBuilder::new()
+        .token(token)
+        .url(&url)
+        .symbol_id("2884")
+        .connection_limit(0)
+        .rest_fallback(Duration::from_millis(50), Duration::from_millis(200))
+        .build();
+
+    let mut rx = ws.async_quote().await.unwrap();
+    tokio::time::sleep(Duration::from_millis(100)).await;
+    assert_eq!(ws.connection_usage().in_use, 0);
+
+    let _raised = IntradayBuilder::new()
+        .token(token)
+        .connection_limit(1)
+        .build();
+    let quote = tokio::time::timeout(Duration::from_secs(10), rx.recv())
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(quote.data.info.symbol_id, "2884");
+
+    let exits = ws.async_shutdown(Duration::from_secs(5)).await;
+    assert!(exits.iter().all(|e| e.is_clean()));
+}